@@ -22,6 +22,43 @@ macro_rules! line_error {
     };
 }
 
+/// Reads a snapshot that is protected by the password. Snapshots written by former versions of the commandline are
+/// encrypted with a key that `naive_kdf` derived from the password and a zeroed salt. They are read with that key and
+/// rewritten right away with a key that is derived from the password with Argon2id.
+async fn read_snapshot(
+    stronghold: &mut iota_stronghold::Stronghold,
+    client_path: Vec<u8>,
+    password: &[u8],
+    filename: Option<String>,
+    path: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let password = password.to_vec();
+    let error = match stronghold
+        .read_snapshot_with_password(client_path.clone(), None, &password, filename.clone(), path.clone())
+        .await?
+    {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+
+    let mut key = [0u8; 32];
+    let salt = [0u8; 32];
+    naive_kdf(&password, &salt, &mut key);
+    match stronghold
+        .read_snapshot(client_path, None, &key.to_vec(), filename.clone(), path.clone())
+        .await?
+    {
+        Ok(()) => {
+            println!("Migrating snapshot of a former version to a password protected snapshot.");
+            stronghold
+                .write_all_to_snapshot_with_password(&password, filename, path)
+                .await??;
+            Ok(())
+        }
+        Err(_) => Err(Box::new(error)),
+    }
+}
+
 /// Writes data to the unencrypted store. Requires a password, the plaintext and the record path.  Record path must be a
 /// number.
 async fn write_to_store_command(
@@ -31,30 +68,28 @@ async fn write_to_store_command(
     stronghold: &mut iota_stronghold::Stronghold,
     client_path: Vec<u8>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut key = [0u8; 32];
-    let salt = [0u8; 32];
-    naive_kdf(pass.as_bytes(), &salt, &mut key);
+    let password = pass.as_bytes().to_vec();
 
     let snapshot = home_dir()?.join("snapshots").join("commandline.stronghold");
 
     if snapshot.exists() {
-        stronghold
-            .read_snapshot(client_path, None, &key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+        read_snapshot(
+            stronghold,
+            client_path,
+            &password,
+            Some("commandline".to_string()),
+            None,
+        )
+        .await?;
     }
 
     stronghold
         .write_to_store(rid.into(), plain.as_bytes().to_vec(), None)
-        .await
-        .unwrap();
+        .await?;
 
     stronghold
-        .write_all_to_snapshot(&key.to_vec(), Some("commandline".to_string()), None)
-        .await
-        .unwrap()
-        .unwrap();
+        .write_all_to_snapshot_with_password(&password, Some("commandline".to_string()), None)
+        .await??;
     Ok(())
 }
 
@@ -67,18 +102,19 @@ async fn encrypt_command(
     stronghold: &mut iota_stronghold::Stronghold,
     client_path: Vec<u8>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut key = [0u8; 32];
-    let salt = [0u8; 32];
-    naive_kdf(pass.as_bytes(), &salt, &mut key);
+    let password = pass.as_bytes().to_vec();
 
     let snapshot = home_dir()?.join("snapshots").join("commandline.stronghold");
 
     if snapshot.exists() {
-        stronghold
-            .read_snapshot(client_path, None, &key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+        read_snapshot(
+            stronghold,
+            client_path,
+            &password,
+            Some("commandline".to_string()),
+            None,
+        )
+        .await?;
     }
 
     stronghold
@@ -88,15 +124,11 @@ async fn encrypt_command(
             RecordHint::new("some hint").expect(line_error!()),
             vec![],
         )
-        .await
-        .unwrap()
-        .unwrap();
+        .await??;
 
     stronghold
-        .write_all_to_snapshot(&key.to_vec(), Some("commandline".to_string()), None)
-        .await
-        .unwrap()
-        .unwrap();
+        .write_all_to_snapshot_with_password(&password, Some("commandline".to_string()), None)
+        .await??;
 
     Ok(())
 }
@@ -108,9 +140,7 @@ async fn snapshot_command(
     stronghold: &mut iota_stronghold::Stronghold,
     client_path: Vec<u8>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut key = [0u8; 32];
-    let salt = [0u8; 32];
-    naive_kdf(pass.as_bytes(), &salt, &mut key);
+    let password = pass.as_bytes().to_vec();
 
     let path = Path::new(path);
 
@@ -122,16 +152,10 @@ async fn snapshot_command(
     out.push(Path::new("recompute.stronghold"));
 
     if input.exists() {
+        read_snapshot(stronghold, client_path, &password, None, Some(input)).await?;
         stronghold
-            .read_snapshot(client_path, None, &key.to_vec(), None, Some(input))
-            .await
-            .unwrap()
-            .unwrap();
-        stronghold
-            .write_all_to_snapshot(&key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+            .write_all_to_snapshot_with_password(&password, Some("commandline".to_string()), None)
+            .await??;
     } else {
         return Err(Box::from("The path you entered does not contain a valid snapshot"));
     }
@@ -146,23 +170,23 @@ async fn list_command(
     stronghold: &mut iota_stronghold::Stronghold,
     client_path: Vec<u8>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut key = [0u8; 32];
-    let salt = [0u8; 32];
-    naive_kdf(pass.as_bytes(), &salt, &mut key);
+    let password = pass.as_bytes().to_vec();
 
     let snapshot = home_dir()?.join("snapshots").join("commandline.stronghold");
 
     if snapshot.exists() {
-        stronghold
-            .read_snapshot(client_path, None, &key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+        read_snapshot(
+            stronghold,
+            client_path,
+            &password,
+            Some("commandline".to_string()),
+            None,
+        )
+        .await?;
 
         let list = stronghold
             .list_hints_and_ids(Location::generic(path, path).vault_path().to_vec())
-            .await
-            .unwrap();
+            .await?;
 
         println!("{:?}", list);
     } else {
@@ -181,22 +205,26 @@ async fn read_from_store_command(
     stronghold: &mut iota_stronghold::Stronghold,
     client_path: Vec<u8>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut key = [0u8; 32];
-    let salt = [0u8; 32];
-    naive_kdf(pass.as_bytes(), &salt, &mut key);
+    let password = pass.as_bytes().to_vec();
 
     let snapshot = home_dir()?.join("snapshots").join("commandline.stronghold");
 
     if snapshot.exists() {
-        stronghold
-            .read_snapshot(client_path, None, &key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+        read_snapshot(
+            stronghold,
+            client_path,
+            &password,
+            Some("commandline".to_string()),
+            None,
+        )
+        .await?;
 
-        let data = stronghold.read_from_store(rpath.into()).await.unwrap();
+        let data = stronghold.read_from_store(rpath.into()).await?;
 
-        println!("Data: {:?}", std::str::from_utf8(&data.unwrap()).unwrap());
+        match data {
+            Some(data) => println!("Data: {:?}", String::from_utf8_lossy(&data)),
+            None => println!("No Data in the store for this key."),
+        }
     } else {
         return Err(Box::from(
             "Could not find a snapshot at the home path.  Try writing first. ",
@@ -213,25 +241,24 @@ async fn delete_from_store_command(
     stronghold: &mut iota_stronghold::Stronghold,
     client_path: Vec<u8>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut key = [0u8; 32];
-    let salt = [0u8; 32];
-    naive_kdf(pass.as_bytes(), &salt, &mut key);
+    let password = pass.as_bytes().to_vec();
 
     let snapshot = home_dir()?.join("snapshots").join("commandline.stronghold");
 
     if snapshot.exists() {
-        stronghold
-            .read_snapshot(client_path, None, &key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+        read_snapshot(
+            stronghold,
+            client_path,
+            &password,
+            Some("commandline".to_string()),
+            None,
+        )
+        .await?;
 
-        stronghold.delete_from_store(rpath.into()).await.unwrap();
+        stronghold.delete_from_store(rpath.into()).await?;
         stronghold
-            .write_all_to_snapshot(&key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+            .write_all_to_snapshot_with_password(&password, Some("commandline".to_string()), None)
+            .await??;
     } else {
         return Err(Box::from(
             "Could not find a snapshot at the home path. Try writing first.",
@@ -249,29 +276,24 @@ async fn revoke_command(
     stronghold: &mut iota_stronghold::Stronghold,
     client_path: Vec<u8>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut key = [0u8; 32];
-    let salt = [0u8; 32];
-    naive_kdf(pass.as_bytes(), &salt, &mut key);
+    let password = pass.as_bytes().to_vec();
 
     let snapshot = home_dir()?.join("snapshots").join("commandline.stronghold");
 
     if snapshot.exists() {
-        stronghold
-            .read_snapshot(client_path, None, &key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
-        stronghold
-            .delete_data(Location::generic(id, id), false)
-            .await
-            .unwrap()
-            .unwrap();
+        read_snapshot(
+            stronghold,
+            client_path,
+            &password,
+            Some("commandline".to_string()),
+            None,
+        )
+        .await?;
+        stronghold.delete_data(Location::generic(id, id), false).await??;
 
         stronghold
-            .write_all_to_snapshot(&key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+            .write_all_to_snapshot_with_password(&password, Some("commandline".to_string()), None)
+            .await??;
     } else {
         return Err(Box::from(
             "Could not find a snapshot at the home path.  Try writing first. ",
@@ -288,36 +310,29 @@ async fn garbage_collect_vault_command(
     stronghold: &mut iota_stronghold::Stronghold,
     client_path: Vec<u8>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut key = [0u8; 32];
-    let salt = [0u8; 32];
-    naive_kdf(pass.as_bytes(), &salt, &mut key);
+    let password = pass.as_bytes().to_vec();
 
     let snapshot = home_dir()?.join("snapshots").join("commandline.stronghold");
 
     if snapshot.exists() {
-        stronghold
-            .read_snapshot(client_path, None, &key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+        read_snapshot(
+            stronghold,
+            client_path,
+            &password,
+            Some("commandline".to_string()),
+            None,
+        )
+        .await?;
 
         let location = Location::generic(id, id);
-        stronghold
-            .garbage_collect(location.vault_path().to_vec())
-            .await
-            .unwrap();
-        let list = stronghold
-            .list_hints_and_ids(location.vault_path().to_vec())
-            .await
-            .unwrap();
+        stronghold.garbage_collect(location.vault_path().to_vec()).await?;
+        let list = stronghold.list_hints_and_ids(location.vault_path().to_vec()).await?;
 
         println!("{:?}", list);
 
         stronghold
-            .write_all_to_snapshot(&key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+            .write_all_to_snapshot_with_password(&password, Some("commandline".to_string()), None)
+            .await??;
     } else {
         return Err(Box::from(
             "Could not find a snapshot at the home path.  Try writing first.",
@@ -335,32 +350,28 @@ async fn purge_command(
     stronghold: &mut iota_stronghold::Stronghold,
     client_path: Vec<u8>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut key = [0u8; 32];
-    let salt = [0u8; 32];
-    naive_kdf(pass.as_bytes(), &salt, &mut key);
+    let password = pass.as_bytes().to_vec();
 
     let snapshot = home_dir()?.join("snapshots").join("commandline.stronghold");
 
     if snapshot.exists() {
-        stronghold
-            .read_snapshot(client_path, None, &key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+        read_snapshot(
+            stronghold,
+            client_path,
+            &password,
+            Some("commandline".to_string()),
+            None,
+        )
+        .await?;
         let location = Location::generic(id, id);
-        stronghold.delete_data(location.clone(), true).await.unwrap().unwrap();
-        let list = stronghold
-            .list_hints_and_ids(location.vault_path().to_vec())
-            .await
-            .unwrap();
+        stronghold.delete_data(location.clone(), true).await??;
+        let list = stronghold.list_hints_and_ids(location.vault_path().to_vec()).await?;
 
         println!("{:?}", list);
 
         stronghold
-            .write_all_to_snapshot(&key.to_vec(), Some("commandline".to_string()), None)
-            .await
-            .unwrap()
-            .unwrap();
+            .write_all_to_snapshot_with_password(&password, Some("commandline".to_string()), None)
+            .await??;
     } else {
         return Err(Box::from(
             "Could not find a snapshot at the home path.  Try writing first.",
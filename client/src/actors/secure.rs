@@ -12,6 +12,7 @@ use crate::{
     internals::Provider,
     procedures::{Procedure, ProcedureError, ProcedureOutput, Runner},
     state::secure::SecureClient,
+    VaultFlags,
 };
 use actix::{Actor, ActorContext, Context, Handler, Message, MessageResult, Supervised};
 use engine::{
@@ -23,7 +24,7 @@ use engine::{
 };
 
 #[cfg(feature = "p2p")]
-use engine::runtime::GuardedVec;
+use engine::{runtime::GuardedVec, vault::RecordPolicy};
 #[cfg(feature = "p2p")]
 use p2p::{identity::Keypair, AuthenticKeypair, NoiseKeypair, PeerId};
 use std::collections::HashMap;
@@ -69,6 +70,7 @@ pub mod messages {
 
         pub payload: Vec<u8>,
         pub hint: RecordHint,
+        pub flags: Vec<VaultFlags>,
     }
 
     impl Message for WriteToVault {
@@ -102,6 +104,34 @@ pub mod messages {
         type Result = Vec<(RecordId, RecordHint)>;
     }
 
+    #[derive(Clone, GuardDebug, Serialize, Deserialize)]
+    pub struct ListIdsAndCounters {
+        pub vault_path: Vec<u8>,
+    }
+
+    impl Message for ListIdsAndCounters {
+        type Result = Result<Vec<(RecordId, RecordHint, Option<usize>)>, RecordError>;
+    }
+
+    #[derive(Clone, GuardDebug, Serialize, Deserialize)]
+    pub struct ListVersions {
+        pub vault_path: Vec<u8>,
+    }
+
+    impl Message for ListVersions {
+        type Result = Result<Vec<(usize, RecordId, RecordHint)>, RecordError>;
+    }
+
+    #[derive(Clone, GuardDebug, Serialize, Deserialize)]
+    pub struct RollbackTo {
+        pub vault_path: Vec<u8>,
+        pub version: usize,
+    }
+
+    impl Message for RollbackTo {
+        type Result = Result<(), RecordError>;
+    }
+
     #[derive(Clone, GuardDebug, Serialize, Deserialize)]
     pub struct CheckRecord {
         pub location: Location,
@@ -156,6 +186,42 @@ pub mod messages {
         type Result = ();
     }
 
+    /// Increment the bincode encoded `u32` counter at `key` in the store. A missing counter is created with `1`.
+    #[derive(Clone, GuardDebug, Serialize, Deserialize)]
+    pub struct IncrementStoreCounter {
+        pub key: Vec<u8>,
+    }
+
+    impl Message for IncrementStoreCounter {
+        /// The incremented counter, or [`None`] if the entry is not a counter.
+        type Result = Option<u32>;
+    }
+
+    /// Append `item` to the bincode encoded list of byte strings at `key` in the store. A missing list is created.
+    #[derive(Clone, GuardDebug, Serialize, Deserialize)]
+    pub struct AppendToStoreList {
+        pub key: Vec<u8>,
+        pub item: Vec<u8>,
+    }
+
+    impl Message for AppendToStoreList {
+        /// [`None`] if the entry is not a list.
+        type Result = Option<()>;
+    }
+
+    /// Remove all occurrences of `item` from the bincode encoded list of byte strings at `key` in the store. The
+    /// entry is deleted if the list becomes empty.
+    #[derive(Clone, GuardDebug, Serialize, Deserialize)]
+    pub struct RemoveFromStoreList {
+        pub key: Vec<u8>,
+        pub item: Vec<u8>,
+    }
+
+    impl Message for RemoveFromStoreList {
+        /// [`None`] if the entry is not a list.
+        type Result = Option<()>;
+    }
+
     pub struct GetData {}
 
     impl Message for GetData {
@@ -266,7 +332,10 @@ pub mod testing {
     }
 
     impl_handler!(ReadFromVault, Option<Vec<u8>>, (self, msg, _ctx), {
-        let (vid, rid) = Self::resolve_location(msg.location);
+        let (vid, rid) = match self.resolve_read(msg.location) {
+            Ok(ids) => ids,
+            Err(e) => panic!("Internal Error: {}", e),
+        };
 
         let key = self.keystore.take_key(vid)?;
 
@@ -300,10 +369,14 @@ impl_handler!(messages::Terminate, (), (self, _msg, ctx), {
 impl_handler!(messages::ClearCache, (), (self, _msg, _ctx), {
     self.keystore.clear_keys();
     self.db.clear();
+    self.clear_counter_indexes();
 });
 
 impl_handler!(messages::CheckRecord, bool, (self, msg, _ctx), {
-    let (vault_id, record_id) = Self::resolve_location(msg.location);
+    let (vault_id, record_id) = match self.resolve_read(msg.location) {
+        Ok(ids) => ids,
+        Err(_) => return false,
+    };
 
     return match self.keystore.take_key(vault_id) {
         Some(key) => {
@@ -316,7 +389,8 @@ impl_handler!(messages::CheckRecord, bool, (self, msg, _ctx), {
 });
 
 impl_handler!(messages::WriteToVault, Result<(), RecordError>, (self, msg, _ctx), {
-    self.write_to_vault(&msg.location, msg.hint, msg.payload)
+    let policy = VaultFlags::policy(&msg.flags);
+    self.write_to_vault(&msg.location, msg.hint, msg.payload, policy)
 });
 
 impl_handler!(messages::RevokeData, Result<(), RecordError>, (self, msg, _ctx), {
@@ -329,21 +403,39 @@ impl_handler!(messages::GarbageCollect, bool, (self, msg, _ctx), {
 });
 
 impl_handler!(messages::ListIds, Vec<(RecordId, RecordHint)>, (self, msg, _ctx), {
-    let vault_id = Self::derive_vault_id(msg.vault_path);
-    let key = match self.keystore.take_key(vault_id) {
-        Some(k) => k,
-        None => return Vec::new(),
-    };
+    let vault_path = msg.vault_path;
+    self.list_hints_and_ids(vault_path)
+});
+
+impl_handler!(
+    messages::ListIdsAndCounters,
+    Result<Vec<(RecordId, RecordHint, Option<usize>)>, RecordError>,
+    (self, msg, _ctx),
+    {
+        let vault_path = msg.vault_path;
+        self.list_hints_ids_and_counters(vault_path)
+    }
+);
 
-    let list = self.db.list_hints_and_ids(&key, vault_id);
-    self.keystore.insert_key(vault_id, key);
-    list
+impl_handler!(
+    messages::ListVersions,
+    Result<Vec<(usize, RecordId, RecordHint)>, RecordError>,
+    (self, msg, _ctx),
+    {
+        let vault_path = msg.vault_path;
+        self.list_versions(vault_path)
+    }
+);
+
+impl_handler!(messages::RollbackTo, Result<(), RecordError>, (self, msg, _ctx), {
+    self.rollback_to(msg.vault_path, msg.version)
 });
 
 impl_handler!(messages::ReloadData, (), (self, msg, _ctx), {
     let (keystore, state, store) = *msg.data;
     self.keystore.rebuild_keystore(keystore);
     self.db = state;
+    self.clear_counter_indexes();
     self.rebuild_cache(self.client_id, store);
 });
 
@@ -364,6 +456,45 @@ impl_handler!(messages::DeleteFromStore, (), (self, msg, _ctx), {
     self.store_delete_item(msg.key);
 });
 
+impl_handler!(messages::IncrementStoreCounter, Option<u32>, (self, msg, _ctx), {
+    // the actor handles one message at a time, so that reading and writing the counter can not interleave with other
+    // increments.
+    let counter = match self.read_from_store(msg.key.clone()) {
+        Some(data) => bincode::deserialize::<u32>(&data).ok()?.saturating_add(1),
+        None => 1,
+    };
+    let data = bincode::serialize(&counter).expect("serialize counter");
+    self.write_to_store(msg.key, data, None);
+    Some(counter)
+});
+
+impl_handler!(messages::AppendToStoreList, Option<()>, (self, msg, _ctx), {
+    // as for the counters, the list can not be modified by other messages between reading and writing it.
+    let mut list = match self.read_from_store(msg.key.clone()) {
+        Some(data) => bincode::deserialize::<Vec<Vec<u8>>>(&data).ok()?,
+        None => Vec::new(),
+    };
+    list.push(msg.item);
+    let data = bincode::serialize(&list).expect("serialize list");
+    self.write_to_store(msg.key, data, None);
+    Some(())
+});
+
+impl_handler!(messages::RemoveFromStoreList, Option<()>, (self, msg, _ctx), {
+    let mut list = match self.read_from_store(msg.key.clone()) {
+        Some(data) => bincode::deserialize::<Vec<Vec<u8>>>(&data).ok()?,
+        None => return Some(()),
+    };
+    list.retain(|item| item != &msg.item);
+    if list.is_empty() {
+        self.store_delete_item(msg.key);
+    } else {
+        let data = bincode::serialize(&list).expect("serialize list");
+        self.write_to_store(msg.key, data, None);
+    }
+    Some(())
+});
+
 impl_handler!(
     messages::GetData,
     MessageResult<messages::GetData>,
@@ -382,23 +513,22 @@ impl Handler<messages::Procedures> for SecureClient {
 
     fn handle(&mut self, msg: messages::Procedures, _: &mut Self::Context) -> Self::Result {
         let mut out = Vec::new();
-        let mut log = Vec::new();
-        // Execute the procedures sequentially.
+        // Execute the procedures sequentially within one transaction, so that either all or none of them are applied.
+        self.db.begin_transaction();
         for proc in msg.procedures {
-            if let Some(output) = proc.output() {
-                log.push(output);
-            }
-            let output = match proc.execute(self) {
+            self.procedure = Some(proc.kind());
+            let res = proc.execute(self);
+            self.procedure = None;
+            let output = match res {
                 Ok(o) => o,
                 Err(e) => {
-                    for location in log {
-                        let _ = self.revoke_data(&location);
-                    }
+                    self.rollback_transaction();
                     return Err(e);
                 }
             };
             out.push(output);
         }
+        self.db.commit_transaction();
         Ok(out)
     }
 }
@@ -412,7 +542,7 @@ impl Handler<p2p_messages::GenerateP2pKeypair> for SecureClient {
         let bytes = keypair
             .to_protobuf_encoding()
             .map_err(|e| ProcedureError::Procedure(e.to_string().into()))?;
-        self.write_to_vault(&msg.location, msg.hint, bytes)?;
+        self.write_to_vault(&msg.location, msg.hint, bytes, RecordPolicy::default())?;
         Ok(())
     }
 }
@@ -426,7 +556,7 @@ impl Handler<p2p_messages::WriteP2pKeypair> for SecureClient {
             .keypair
             .to_protobuf_encoding()
             .map_err(|e| ProcedureError::Procedure(e.to_string().into()))?;
-        self.write_to_vault(&msg.location, msg.hint, bytes)?;
+        self.write_to_vault(&msg.location, msg.hint, bytes, RecordPolicy::default())?;
         Ok(())
     }
 }
@@ -7,16 +7,13 @@ use actix::{Actor, Handler, Message, Supervised};
 
 use std::path::PathBuf;
 
-use engine::{
-    snapshot,
-    vault::{ClientId, DbView, Key, VaultId},
-};
+use engine::vault::{ClientId, DbView, Key, VaultId};
 
 use crate::{
     internals,
     state::{
         secure::Store,
-        snapshot::{ReadError, Snapshot, SnapshotState, WriteError},
+        snapshot::{MigrationError, ReadError, RekeyError, Snapshot, SnapshotKey, SnapshotState, WriteError},
     },
     Provider,
 };
@@ -47,7 +44,7 @@ pub mod messages {
     use super::*;
 
     pub struct WriteSnapshot {
-        pub key: snapshot::Key,
+        pub key: SnapshotKey,
         pub filename: Option<String>,
         pub path: Option<PathBuf>,
     }
@@ -65,9 +62,8 @@ pub mod messages {
         type Result = ();
     }
 
-    #[derive(Default)]
     pub struct ReadFromSnapshot {
-        pub key: snapshot::Key,
+        pub key: SnapshotKey,
         pub filename: Option<String>,
         pub path: Option<PathBuf>,
         pub id: ClientId,
@@ -77,6 +73,27 @@ pub mod messages {
     impl Message for ReadFromSnapshot {
         type Result = Result<returntypes::ReturnReadSnapshot, ReadError>;
     }
+
+    pub struct MigrateSnapshot {
+        pub path: PathBuf,
+        pub old_key: SnapshotKey,
+        pub new_key: SnapshotKey,
+    }
+
+    impl Message for MigrateSnapshot {
+        type Result = Result<(), MigrationError>;
+    }
+
+    pub struct RekeySnapshot {
+        pub old_key: SnapshotKey,
+        pub new_key: SnapshotKey,
+        pub filename: Option<String>,
+        pub path: Option<PathBuf>,
+    }
+
+    impl Message for RekeySnapshot {
+        type Result = Result<(), RekeyError>;
+    }
 }
 
 impl Actor for Snapshot {
@@ -134,3 +151,20 @@ impl Handler<messages::WriteSnapshot> for Snapshot {
         Ok(())
     }
 }
+
+impl Handler<messages::MigrateSnapshot> for Snapshot {
+    type Result = Result<(), MigrationError>;
+
+    fn handle(&mut self, msg: messages::MigrateSnapshot, _ctx: &mut Self::Context) -> Self::Result {
+        Snapshot::migrate_snapshot(&msg.path, msg.old_key, msg.new_key)
+    }
+}
+
+impl Handler<messages::RekeySnapshot> for Snapshot {
+    type Result = Result<(), RekeyError>;
+
+    /// Re-encrypts the snapshot file independently of the state that is currently held by this actor.
+    fn handle(&mut self, msg: messages::RekeySnapshot, _ctx: &mut Self::Context) -> Self::Result {
+        Snapshot::rekey_snapshot(msg.filename.as_deref(), msg.path.as_deref(), msg.old_key, msg.new_key)
+    }
+}
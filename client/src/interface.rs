@@ -10,17 +10,19 @@
 use crate::{
     actors::{
         secure_messages::{
-            CheckRecord, CheckVault, ClearCache, DeleteFromStore, GarbageCollect, GetData, ListIds, Procedures,
-            ReadFromStore, ReloadData, RevokeData, WriteToStore, WriteToVault,
+            AppendToStoreList, CheckRecord, CheckVault, ClearCache, DeleteFromStore, GarbageCollect, GetData,
+            IncrementStoreCounter, ListIds, ListIdsAndCounters, ListVersions, Procedures, ReadFromStore, ReloadData,
+            RemoveFromStoreList, RevokeData, RollbackTo, WriteToStore, WriteToVault,
         },
-        snapshot_messages::{FillSnapshot, ReadFromSnapshot, WriteSnapshot},
+        snapshot_messages::{FillSnapshot, MigrateSnapshot, ReadFromSnapshot, RekeySnapshot, WriteSnapshot},
         GetAllClients, GetClient, GetSnapshot, GetTarget, RecordError, Registry, RemoveClient, SpawnClient,
         SwitchTarget,
     },
     procedures::{Procedure, ProcedureError, ProcedureOutput, StrongholdProcedure},
     state::{
         secure::SecureClient,
-        snapshot::{ReadError, WriteError},
+        snapshot::{MigrationError, ReadError, RekeyError, SnapshotKey, WriteError},
+        webauthn::{Authenticator, AuthenticatorConfig},
     },
     utils::{LoadFromPath, StrongholdFlags, VaultFlags},
     Location,
@@ -52,8 +54,13 @@ use p2p::{
     identity::Keypair, DialErr, InitKeypair, ListenErr, ListenRelayErr, Multiaddr, OutboundFailure, PeerId,
     RelayNotSupported,
 };
-#[cfg(feature = "p2p")]
+#[cfg(any(feature = "p2p", all(unix, feature = "ssh-agent")))]
 use std::io;
+#[cfg(all(unix, feature = "ssh-agent"))]
+use {
+    crate::state::ssh_agent::{SshAgent, SshAgentConfig},
+    std::path::Path,
+};
 
 pub type StrongholdResult<T> = Result<T, ActorError>;
 
@@ -194,13 +201,14 @@ impl Stronghold {
 
     /// Writes data into the Stronghold. Uses the current target actor as the client and writes to the specified
     /// location of [`Location`] type. The payload must be specified as a [`Vec<u8>`] and a [`RecordHint`] can be
-    /// provided. Also accepts [`VaultFlags`] for when a new Vault is created.
+    /// provided. The [`VaultFlags`] define the usage policy of the record, which is stored with the record and checked
+    /// before its secret is used by a procedure.
     pub async fn write_to_vault(
         &self,
         location: Location,
         payload: Vec<u8>,
         hint: RecordHint,
-        _options: Vec<VaultFlags>,
+        options: Vec<VaultFlags>,
     ) -> StrongholdResult<Result<(), FatalEngineError>> {
         let target = self.target().await?;
         // write to vault
@@ -209,6 +217,7 @@ impl Stronghold {
                 location,
                 payload,
                 hint,
+                flags: options,
             })
             .await?
             .map_err(FatalEngineError::from);
@@ -252,6 +261,30 @@ impl Stronghold {
         Ok(())
     }
 
+    /// Atomically increments the bincode encoded `u32` counter at `key` in the store and returns the new value.
+    /// Returns `None` if the entry at `key` is not a counter.
+    pub(crate) async fn increment_store_counter(&self, key: Vec<u8>) -> StrongholdResult<Option<u32>> {
+        let target = self.target().await?;
+        let counter = target.send(IncrementStoreCounter { key }).await?;
+        Ok(counter)
+    }
+
+    /// Atomically appends `item` to the bincode encoded list of byte strings at `key` in the store. Returns `None` if
+    /// the entry at `key` is not such a list.
+    pub(crate) async fn append_to_store_list(&self, key: Vec<u8>, item: Vec<u8>) -> StrongholdResult<Option<()>> {
+        let target = self.target().await?;
+        let appended = target.send(AppendToStoreList { key, item }).await?;
+        Ok(appended)
+    }
+
+    /// Atomically removes `item` from the bincode encoded list of byte strings at `key` in the store. Returns `None`
+    /// if the entry at `key` is not such a list.
+    pub(crate) async fn remove_from_store_list(&self, key: Vec<u8>, item: Vec<u8>) -> StrongholdResult<Option<()>> {
+        let target = self.target().await?;
+        let removed = target.send(RemoveFromStoreList { key, item }).await?;
+        Ok(removed)
+    }
+
     /// Revokes the data from the specified location of type [`Location`]. Revoked data is not readable and can be
     /// removed from a vault with a call to `garbage_collect`.  if the `should_gc` flag is set to `true`, this call
     /// with automatically cleanup the revoke. Otherwise, the data is just marked as revoked.
@@ -307,6 +340,59 @@ impl Stronghold {
         Ok(list)
     }
 
+    /// Returns a list of the available [`RecordId`] and [`RecordHint`] values in a vault by the given `vault_path`,
+    /// together with the counter index of each record that was written to a [`Location::Counter`].
+    pub async fn list_hints_ids_and_counters<V: Into<Vec<u8>>>(
+        &self,
+        vault_path: V,
+    ) -> StrongholdResult<Result<Vec<(RecordId, RecordHint, Option<usize>)>, FatalEngineError>> {
+        let target = self.target().await?;
+        let list = target
+            .send(ListIdsAndCounters {
+                vault_path: vault_path.into(),
+            })
+            .await?
+            .map_err(FatalEngineError::from);
+        Ok(list)
+    }
+
+    /// Returns the versions of the counter chain in a vault by the given `vault_path`, ordered from the first to the
+    /// latest version. Each version is returned with its counter index, [`RecordId`] and [`RecordHint`].
+    ///
+    /// The latest version is read and the next version is written through [`Location::counter_head`].
+    pub async fn list_versions<V: Into<Vec<u8>>>(
+        &self,
+        vault_path: V,
+    ) -> StrongholdResult<Result<Vec<(usize, RecordId, RecordHint)>, FatalEngineError>> {
+        let target = self.target().await?;
+        let list = target
+            .send(ListVersions {
+                vault_path: vault_path.into(),
+            })
+            .await?
+            .map_err(FatalEngineError::from);
+        Ok(list)
+    }
+
+    /// Rolls the counter chain in a vault back to the specified `version`, which becomes the head of the chain. All
+    /// later versions are revoked and can be removed with a call to `garbage_collect`.  Fails if the version does not
+    /// exist.
+    pub async fn rollback_to<V: Into<Vec<u8>>>(
+        &self,
+        vault_path: V,
+        version: usize,
+    ) -> StrongholdResult<Result<(), FatalEngineError>> {
+        let target = self.target().await?;
+        let res = target
+            .send(RollbackTo {
+                vault_path: vault_path.into(),
+                version,
+            })
+            .await?
+            .map_err(FatalEngineError::from);
+        Ok(res)
+    }
+
     /// Executes a runtime command given a single [`StrongholdProcedure`]s
     pub async fn runtime_exec<P>(&self, procedure: P) -> StrongholdResult<Result<P::Output, ProcedureError>>
     where
@@ -317,7 +403,8 @@ impl Stronghold {
         Ok(mapped)
     }
 
-    /// Sequentially execute multiple [`StrongholdProcedure`]s.
+    /// Sequentially execute multiple [`StrongholdProcedure`]s.  The chain is executed as one transaction: if one of the
+    /// procedures fails, all records that were changed by the previous procedures are restored to their original state.
     pub async fn runtime_exec_chained(
         &self,
         procedures: Vec<StrongholdProcedure>,
@@ -357,6 +444,40 @@ impl Stronghold {
         keydata: &T,
         filename: Option<String>,
         path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), ReadError>> {
+        let mut key: [u8; 32] = [0u8; 32];
+        let keydata = keydata.as_ref();
+
+        key.copy_from_slice(keydata);
+
+        self.read_snapshot_with_key(client_path, former_client_path, SnapshotKey::Key(key), filename, path)
+            .await
+    }
+
+    /// Reads data from a given snapshot file that was written with
+    /// [`write_all_to_snapshot_with_password`](Self::write_all_to_snapshot_with_password). The snapshot key is
+    /// re-derived from the password with the key derivation parameters that are stored in the snapshot. See
+    /// [`read_snapshot`](Self::read_snapshot) for the other parameters.
+    pub async fn read_snapshot_with_password<T: Zeroize + AsRef<Vec<u8>>>(
+        &mut self,
+        client_path: Vec<u8>,
+        former_client_path: Option<Vec<u8>>,
+        password: &T,
+        filename: Option<String>,
+        path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), ReadError>> {
+        let key = SnapshotKey::Password(password.as_ref().clone());
+        self.read_snapshot_with_key(client_path, former_client_path, key, filename, path)
+            .await
+    }
+
+    async fn read_snapshot_with_key(
+        &mut self,
+        client_path: Vec<u8>,
+        former_client_path: Option<Vec<u8>>,
+        key: SnapshotKey,
+        filename: Option<String>,
+        path: Option<PathBuf>,
     ) -> StrongholdResult<Result<(), ReadError>> {
         let client_id = ClientId::load_from_path(&client_path, &client_path);
         let former_client_id = former_client_path.map(|cp| ClientId::load_from_path(&cp, &cp));
@@ -370,11 +491,6 @@ impl Stronghold {
             self.target().await?
         };
 
-        let mut key: [u8; 32] = [0u8; 32];
-        let keydata = keydata.as_ref();
-
-        key.copy_from_slice(keydata);
-
         // get address of snapshot actor
         let snapshot_actor = self.registry.send(GetSnapshot {}).await?;
 
@@ -412,14 +528,37 @@ impl Stronghold {
         filename: Option<String>,
         path: Option<PathBuf>,
     ) -> StrongholdResult<Result<(), WriteError>> {
-        // this should be delegated to the secure client actor
-        // wrapping the interior functionality inside it.
-        let clients: Vec<(ClientId, Addr<SecureClient>)> = self.registry.send(GetAllClients).await?;
-
         let mut key: [u8; 32] = [0u8; 32];
         let keydata = keydata.as_ref();
         key.copy_from_slice(keydata);
 
+        self.write_all_to_snapshot_with_key(SnapshotKey::Key(key), filename, path)
+            .await
+    }
+
+    /// Writes the entire state of the [`Stronghold`] into a snapshot that is protected by a password. The snapshot
+    /// key is derived from the password with Argon2id, the random salt and the cost parameters are stored in the
+    /// snapshot header. The password should implement and use Zeroize.
+    pub async fn write_all_to_snapshot_with_password<T: Zeroize + AsRef<Vec<u8>>>(
+        &mut self,
+        password: &T,
+        filename: Option<String>,
+        path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), WriteError>> {
+        let key = SnapshotKey::Password(password.as_ref().clone());
+        self.write_all_to_snapshot_with_key(key, filename, path).await
+    }
+
+    async fn write_all_to_snapshot_with_key(
+        &mut self,
+        key: SnapshotKey,
+        filename: Option<String>,
+        path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), WriteError>> {
+        // this should be delegated to the secure client actor
+        // wrapping the interior functionality inside it.
+        let clients: Vec<(ClientId, Addr<SecureClient>)> = self.registry.send(GetAllClients).await?;
+
         // get snapshot actor
         let snapshot = self.registry.send(GetSnapshot {}).await?;
 
@@ -436,6 +575,111 @@ impl Stronghold {
         Ok(res)
     }
 
+    /// Migrates the snapshot file at `path` from any former version of the snapshot format to the current one. The
+    /// snapshot is decrypted with the old keydata, upgraded in memory and written back atomically, encrypted with the
+    /// new keydata. The same keydata may be passed for both keys. The Keydata should implement and use Zeroize.
+    ///
+    /// Snapshots that are read with [`read_snapshot`](Self::read_snapshot) are upgraded as well and written in the
+    /// current format by the next call to [`write_all_to_snapshot`](Self::write_all_to_snapshot).
+    pub async fn migrate_snapshot<T: Zeroize + AsRef<Vec<u8>>>(
+        &self,
+        path: PathBuf,
+        old_keydata: &T,
+        new_keydata: &T,
+    ) -> StrongholdResult<Result<(), MigrationError>> {
+        let mut old_key: [u8; 32] = [0u8; 32];
+        old_key.copy_from_slice(old_keydata.as_ref());
+
+        let mut new_key: [u8; 32] = [0u8; 32];
+        new_key.copy_from_slice(new_keydata.as_ref());
+
+        self.migrate_snapshot_with_key(path, SnapshotKey::Key(old_key), SnapshotKey::Key(new_key))
+            .await
+    }
+
+    /// Migrates the password protected snapshot file at `path` to the current version of the snapshot format, see
+    /// [`migrate_snapshot`](Self::migrate_snapshot). The snapshot key is re-derived from the old password with the key
+    /// derivation parameters that are stored in the snapshot, the migrated snapshot is protected by the new password.
+    /// The passwords should implement and use Zeroize.
+    pub async fn migrate_snapshot_with_password<T: Zeroize + AsRef<Vec<u8>>>(
+        &self,
+        path: PathBuf,
+        old_password: &T,
+        new_password: &T,
+    ) -> StrongholdResult<Result<(), MigrationError>> {
+        let old_key = SnapshotKey::Password(old_password.as_ref().clone());
+        let new_key = SnapshotKey::Password(new_password.as_ref().clone());
+        self.migrate_snapshot_with_key(path, old_key, new_key).await
+    }
+
+    async fn migrate_snapshot_with_key(
+        &self,
+        path: PathBuf,
+        old_key: SnapshotKey,
+        new_key: SnapshotKey,
+    ) -> StrongholdResult<Result<(), MigrationError>> {
+        let snapshot = self.registry.send(GetSnapshot {}).await?;
+        let res = snapshot.send(MigrateSnapshot { path, old_key, new_key }).await?;
+        Ok(res)
+    }
+
+    /// Changes the key that protects a snapshot file. The state of all clients inside the snapshot is decrypted with
+    /// the old keydata and re-encrypted with the new keydata, including clients that were never loaded into this
+    /// [`Stronghold`] instance. A filename and path can be specified. The file is replaced atomically, on failure the
+    /// old file is left untouched. The Keydata should implement and use Zeroize.
+    pub async fn rekey_snapshot<T: Zeroize + AsRef<Vec<u8>>>(
+        &self,
+        old_keydata: &T,
+        new_keydata: &T,
+        filename: Option<String>,
+        path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), RekeyError>> {
+        let mut old_key: [u8; 32] = [0u8; 32];
+        old_key.copy_from_slice(old_keydata.as_ref());
+
+        let mut new_key: [u8; 32] = [0u8; 32];
+        new_key.copy_from_slice(new_keydata.as_ref());
+
+        self.rekey_snapshot_with_key(SnapshotKey::Key(old_key), SnapshotKey::Key(new_key), filename, path)
+            .await
+    }
+
+    /// Changes the password that protects a snapshot file written with
+    /// [`write_all_to_snapshot_with_password`](Self::write_all_to_snapshot_with_password), see
+    /// [`rekey_snapshot`](Self::rekey_snapshot). The snapshot key is re-derived from the old password with the key
+    /// derivation parameters that are stored in the snapshot, the new key is derived from the new password with a fresh
+    /// salt. The passwords should implement and use Zeroize.
+    pub async fn rekey_snapshot_with_password<T: Zeroize + AsRef<Vec<u8>>>(
+        &self,
+        old_password: &T,
+        new_password: &T,
+        filename: Option<String>,
+        path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), RekeyError>> {
+        let old_key = SnapshotKey::Password(old_password.as_ref().clone());
+        let new_key = SnapshotKey::Password(new_password.as_ref().clone());
+        self.rekey_snapshot_with_key(old_key, new_key, filename, path).await
+    }
+
+    async fn rekey_snapshot_with_key(
+        &self,
+        old_key: SnapshotKey,
+        new_key: SnapshotKey,
+        filename: Option<String>,
+        path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), RekeyError>> {
+        let snapshot = self.registry.send(GetSnapshot {}).await?;
+        let res = snapshot
+            .send(RekeySnapshot {
+                old_key,
+                new_key,
+                filename,
+                path,
+            })
+            .await?;
+        Ok(res)
+    }
+
     /// Used to kill a stronghold actor or clear the cache of the given actor system based on the client_path. If
     /// `kill_actor` is `true`, the actor will be removed from the system.  Otherwise, the cache of the
     /// current target actor will be cleared.
@@ -459,12 +703,6 @@ impl Stronghold {
         Ok(())
     }
 
-    /// Unimplemented until Policies are implemented.
-    #[allow(dead_code)]
-    fn check_config_flags() {
-        unimplemented!()
-    }
-
     /// A test function for reading data from a vault.
     // API CHANGE!
     #[cfg(test)]
@@ -486,6 +724,31 @@ impl Stronghold {
     }
 }
 
+impl Stronghold {
+    /// Create a WebAuthn authenticator, whose credentials are generated in the vault at `vault_path` of the current
+    /// target client. The metadata and signature counters of the credentials are kept in the store of the client.
+    pub fn webauthn_authenticator<V: Into<Vec<u8>>>(
+        &self,
+        vault_path: V,
+        config: AuthenticatorConfig,
+    ) -> Authenticator {
+        Authenticator::new(self.clone(), vault_path.into(), config)
+    }
+}
+
+#[cfg(all(unix, feature = "ssh-agent"))]
+impl Stronghold {
+    /// Spawn an SSH agent that listens on the Unix socket at `path` and offers the Ed25519 keys of the `config` as
+    /// identities. Signatures are computed with the [`Ed25519Sign`][crate::procedures::Ed25519Sign] procedure on the
+    /// current target client, so that the usage policies of the keys apply.
+    ///
+    /// The public keys of the identities are computed once when the agent is spawned. The agent runs until the
+    /// returned [`SshAgent`] is dropped.
+    pub async fn spawn_ssh_agent(&self, path: impl AsRef<Path>, config: SshAgentConfig) -> io::Result<SshAgent> {
+        SshAgent::spawn(self.clone(), path.as_ref(), config).await
+    }
+}
+
 #[cfg(feature = "p2p")]
 impl Stronghold {
     /// Spawn the p2p-network actor and swarm.
@@ -731,7 +994,7 @@ impl Stronghold {
         location: Location,
         payload: Vec<u8>,
         hint: RecordHint,
-        _options: Vec<VaultFlags>,
+        options: Vec<VaultFlags>,
     ) -> P2pResult<Result<(), FatalEngineError>> {
         let actor = self.network_actor().await?;
 
@@ -743,6 +1006,7 @@ impl Stronghold {
                 location: location.clone(),
                 payload: payload.clone(),
                 hint,
+                flags: options,
             },
         };
         let res = actor.send(send_request).await??.map_err(FatalEngineError::from);
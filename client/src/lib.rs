@@ -39,7 +39,7 @@ mod tests;
 pub use crate::{
     interface::{ActorError, FatalEngineError, Stronghold, StrongholdResult},
     internals::Provider,
-    state::snapshot::{ReadError, WriteError},
+    state::snapshot::{MigrationError, ReadError, RekeyError, WriteError},
     utils::{Location, StrongholdFlags, VaultFlags},
 };
 pub use engine::{
@@ -66,6 +66,18 @@ pub mod p2p {
     };
 }
 
+#[cfg(all(unix, feature = "ssh-agent"))]
+pub mod ssh_agent {
+    pub use crate::state::ssh_agent::{SshAgent, SshAgentConfig};
+}
+
+pub mod webauthn {
+    pub use crate::state::webauthn::{
+        Authenticator, AuthenticatorConfig, AuthenticatorError, GetAssertionRequest, GetAssertionResponse,
+        MakeCredentialRequest, MakeCredentialResponse, RelyingParty, User, COSE_ALGORITHM_EDDSA,
+    };
+}
+
 pub use actix::MailboxError;
 
 #[macro_export]
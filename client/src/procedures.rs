@@ -1,15 +1,39 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+mod encoding;
+mod ethereum;
+mod otp;
 mod primitives;
+mod sealed_box;
+mod shamir;
+mod slip10;
+mod token;
 mod types;
+mod wrap;
+mod x509;
 
+pub use encoding::{KeyEncodingError, KeyFormat, PublicKeyFormat};
+pub use ethereum::{AccessListItem, EthereumTransaction, ETHEREUM_ADDRESS_LENGTH};
+pub use otp::{OtpAuth, OtpError, OtpHash, OtpType, OTP_MAX_DIGITS, OTP_MIN_DIGITS};
 pub use primitives::{
-    AeadCipher, AeadDecrypt, AeadEncrypt, BIP39Generate, BIP39Recover, Chain, ChainCode, CopyRecord, Ed25519Sign,
-    GarbageCollect, GenerateKey, Hkdf, Hmac, KeyType, MnemonicLanguage, Pbkdf2Hmac, PublicKey, RevokeData, Sha2Hash,
-    Slip10Derive, Slip10DeriveInput, Slip10Generate, StrongholdProcedure, WriteVault, X25519DiffieHellman,
+    AeadCipher, AeadDecrypt, AeadEncrypt, Argon2id, Argon2idDerive, BIP39Generate, BIP39Recover, Chain, ChainCode,
+    CopyRecord, CreateCertificateRequest, CreateSelfSignedCertificate, Ed25519Address, Ed25519AddressBatch,
+    Ed25519Sign, EthereumAddress, EthereumSignMessage, EthereumSignTransaction, EthereumSignTypedData, ExportPublicKey,
+    ExportWrapped, GarbageCollect, GenerateKey, Hkdf, Hmac, Hotp, ImportKey, ImportWrapped, KeyType, MnemonicLanguage,
+    Pbkdf2Hmac, ProcedureKind, PublicKey, RevokeData, RotateVaultKey, Scrypt, ScryptDerive, SealedBoxDecrypt,
+    SealedBoxEncrypt, Secp256k1EcdsaSign, Sha2Hash, ShamirCombine, ShamirSplit, SignCertificateRequest, SignJws,
+    SignPaseto, Slip10Derive, Slip10DeriveInput, Slip10Generate, Slip10Xpub, StrongholdProcedure, Totp, TotpImport,
+    WrappingKey, WriteVault, X25519DiffieHellman, ARGON2_MAX_MEMORY_COST, ARGON2_MAX_PARALLELISM, ARGON2_MAX_TIME_COST,
+    ED25519_ADDRESS_BATCH_MAX_COUNT, KDF_MAX_LENGTH, SCRYPT_MAX_LOG_N, SCRYPT_MAX_P, SCRYPT_MAX_R,
+    SECP256K1_SECRET_KEY_LENGTH, SECP256K1_SIGNATURE_LENGTH,
 };
+pub use sealed_box::SEALED_BOX_OVERHEAD;
+pub use shamir::{ShamirError, SHAMIR_MAX_SECRET_LENGTH, SHAMIR_MAX_SHARES, SHAMIR_MIN_SECRET_LENGTH};
+pub use slip10::{Curve, DerivationPath, DerivationPathError};
+pub use token::{JwsAlgorithm, TokenError};
 pub use types::{
     DeriveSecret, FatalProcedureError, GenerateSecret, Procedure, ProcedureError, ProcedureOutput, UseSecret,
 };
 pub(crate) use types::{Products, Runner};
+pub use x509::{CertificateEncoding, CertificateProfile, ExtendedKeyUsage, KeyUsage, SubjectAltName, X509Error};
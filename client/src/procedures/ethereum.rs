@@ -0,0 +1,230 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ethereum accounts on secp256k1 keys: EIP-55 addresses, the message hashes of EIP-191 and EIP-712, and the RLP
+//! encoding of legacy (EIP-155) and EIP-1559 transactions.
+
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+/// Length of an Ethereum address.
+pub const ETHEREUM_ADDRESS_LENGTH: usize = 20;
+
+/// Type of EIP-1559 transactions in the EIP-2718 envelope.
+const EIP1559_TX_TYPE: u8 = 0x02;
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+/// Gets the Ethereum address of a public key: the last 20 bytes of the Keccak-256 hash of the uncompressed point.
+pub fn address(pk: &VerifyingKey) -> [u8; ETHEREUM_ADDRESS_LENGTH] {
+    let point = pk.to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    let mut address = [0; ETHEREUM_ADDRESS_LENGTH];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// Encodes an address as `0x` prefixed hex string with the EIP-55 mixed-case checksum.
+pub fn checksum_address(address: &[u8; ETHEREUM_ADDRESS_LENGTH]) -> String {
+    let hex: String = address.iter().map(|b| format!("{:02x}", b)).collect();
+    let hash = keccak256(hex.as_bytes());
+    let checksummed: String = hex
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (4 * (1 - i % 2))) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
+/// Hash of an EIP-191 personal message: `keccak256("\x19Ethereum Signed Message:\n" || len(msg) || msg)`.
+pub fn personal_message_hash(msg: &[u8]) -> [u8; 32] {
+    let mut data = format!("\x19Ethereum Signed Message:\n{}", msg.len()).into_bytes();
+    data.extend_from_slice(msg);
+    keccak256(&data)
+}
+
+/// Hash of EIP-712 typed data: `keccak256("\x19\x01" || domainSeparator || hashStruct(message))`.
+pub fn typed_data_hash(domain_separator: &[u8; 32], struct_hash: &[u8; 32]) -> [u8; 32] {
+    let mut data = Vec::with_capacity(66);
+    data.extend_from_slice(&[0x19, 0x01]);
+    data.extend_from_slice(domain_separator);
+    data.extend_from_slice(struct_hash);
+    keccak256(&data)
+}
+
+/// An entry of the access list of an EIP-1559 transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessListItem {
+    pub address: [u8; ETHEREUM_ADDRESS_LENGTH],
+
+    pub storage_keys: Vec<[u8; 32]>,
+}
+
+/// The fields of an unsigned Ethereum transaction.
+///
+/// Amounts are given in wei. A transaction without recipient (`to: None`) creates a contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EthereumTransaction {
+    /// A legacy transaction. If a chain id is set, the transaction is signed with the replay protection of EIP-155.
+    Legacy {
+        chain_id: Option<u64>,
+        nonce: u64,
+        gas_price: u128,
+        gas_limit: u64,
+        to: Option<[u8; ETHEREUM_ADDRESS_LENGTH]>,
+        value: u128,
+        data: Vec<u8>,
+    },
+    /// A type 2 transaction of EIP-1559.
+    Eip1559 {
+        chain_id: u64,
+        nonce: u64,
+        max_priority_fee_per_gas: u128,
+        max_fee_per_gas: u128,
+        gas_limit: u64,
+        to: Option<[u8; ETHEREUM_ADDRESS_LENGTH]>,
+        value: u128,
+        data: Vec<u8>,
+        access_list: Vec<AccessListItem>,
+    },
+}
+
+impl EthereumTransaction {
+    /// Hash of the transaction that is signed.
+    pub fn signing_hash(&self) -> [u8; 32] {
+        match self {
+            EthereumTransaction::Legacy { chain_id, .. } => {
+                let mut fields = self.fields();
+                if let Some(chain_id) = chain_id {
+                    fields.extend([Rlp::uint(*chain_id as u128), Rlp::uint(0), Rlp::uint(0)]);
+                }
+                keccak256(&Rlp::List(fields).encode())
+            }
+            EthereumTransaction::Eip1559 { .. } => keccak256(&self.envelope(Rlp::List(self.fields()))),
+        }
+    }
+
+    /// Encodes the transaction with its signature as raw transaction, ready to be broadcasted.
+    pub fn encode_signed(&self, signature: &Signature, recovery_id: RecoveryId) -> Vec<u8> {
+        let v = match self {
+            EthereumTransaction::Legacy { chain_id: None, .. } => 27 + recovery_id.to_byte() as u128,
+            EthereumTransaction::Legacy {
+                chain_id: Some(chain_id),
+                ..
+            } => 35 + 2 * (*chain_id as u128) + recovery_id.to_byte() as u128,
+            EthereumTransaction::Eip1559 { .. } => recovery_id.to_byte() as u128,
+        };
+        let (r, s) = signature.split_bytes();
+        let mut fields = self.fields();
+        fields.extend([Rlp::uint(v), Rlp::uint_bytes(&r), Rlp::uint_bytes(&s)]);
+        self.envelope(Rlp::List(fields))
+    }
+
+    fn envelope(&self, payload: Rlp) -> Vec<u8> {
+        match self {
+            EthereumTransaction::Legacy { .. } => payload.encode(),
+            EthereumTransaction::Eip1559 { .. } => [&[EIP1559_TX_TYPE][..], &payload.encode()].concat(),
+        }
+    }
+
+    fn fields(&self) -> Vec<Rlp> {
+        let to = |to: &Option<[u8; ETHEREUM_ADDRESS_LENGTH]>| Rlp::Bytes(to.map(|to| to.to_vec()).unwrap_or_default());
+        match self {
+            EthereumTransaction::Legacy {
+                nonce,
+                gas_price,
+                gas_limit,
+                to: recipient,
+                value,
+                data,
+                ..
+            } => vec![
+                Rlp::uint(*nonce as u128),
+                Rlp::uint(*gas_price),
+                Rlp::uint(*gas_limit as u128),
+                to(recipient),
+                Rlp::uint(*value),
+                Rlp::Bytes(data.clone()),
+            ],
+            EthereumTransaction::Eip1559 {
+                chain_id,
+                nonce,
+                max_priority_fee_per_gas,
+                max_fee_per_gas,
+                gas_limit,
+                to: recipient,
+                value,
+                data,
+                access_list,
+            } => {
+                let access_list = access_list
+                    .iter()
+                    .map(|item| {
+                        let keys = item.storage_keys.iter().map(|key| Rlp::Bytes(key.to_vec())).collect();
+                        Rlp::List(vec![Rlp::Bytes(item.address.to_vec()), Rlp::List(keys)])
+                    })
+                    .collect();
+                vec![
+                    Rlp::uint(*chain_id as u128),
+                    Rlp::uint(*nonce as u128),
+                    Rlp::uint(*max_priority_fee_per_gas),
+                    Rlp::uint(*max_fee_per_gas),
+                    Rlp::uint(*gas_limit as u128),
+                    to(recipient),
+                    Rlp::uint(*value),
+                    Rlp::Bytes(data.clone()),
+                    Rlp::List(access_list),
+                ]
+            }
+        }
+    }
+}
+
+/// An RLP item.
+enum Rlp {
+    Bytes(Vec<u8>),
+    List(Vec<Rlp>),
+}
+
+impl Rlp {
+    /// Integers are encoded as big endian bytes without leading zeros.
+    fn uint(i: u128) -> Self {
+        Self::uint_bytes(&i.to_be_bytes())
+    }
+
+    fn uint_bytes(bs: &[u8]) -> Self {
+        let start = bs.iter().position(|b| *b != 0).unwrap_or(bs.len());
+        Rlp::Bytes(bs[start..].to_vec())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Rlp::Bytes(bs) if bs.len() == 1 && bs[0] < 0x80 => bs.clone(),
+            Rlp::Bytes(bs) => [Self::header(0x80, bs.len()), bs.clone()].concat(),
+            Rlp::List(items) => {
+                let payload: Vec<u8> = items.iter().flat_map(Rlp::encode).collect();
+                [Self::header(0xc0, payload.len()), payload].concat()
+            }
+        }
+    }
+
+    fn header(offset: u8, len: usize) -> Vec<u8> {
+        if len < 56 {
+            vec![offset + len as u8]
+        } else {
+            let len = len.to_be_bytes();
+            let start = len.iter().position(|b| *b != 0).unwrap_or(len.len());
+            [&[offset + 55 + (len.len() - start) as u8][..], &len[start..]].concat()
+        }
+    }
+}
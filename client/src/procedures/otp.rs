@@ -0,0 +1,258 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! One-time passwords of RFC 4226 (HOTP) and RFC 6238 (TOTP), and the `otpauth://` key URIs that are used to
+//! provision them.
+//!
+//! Besides the HMAC-SHA1 of the RFCs, which is the default of most authenticator apps, the SHA-2 variants of RFC 6238
+//! are supported.
+
+use super::types::ProcedureOutput;
+use crypto::{
+    hashes::sha::{SHA256_LEN, SHA512_LEN},
+    macs::hmac::{HMAC_SHA256, HMAC_SHA512},
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use thiserror::Error as DeriveError;
+use zeroize::Zeroize;
+
+/// Minimal number of digits of a one-time password.
+pub const OTP_MIN_DIGITS: u32 = 6;
+
+/// Maximal number of digits of a one-time password.
+pub const OTP_MAX_DIGITS: u32 = 8;
+
+/// Default number of digits of a key URI.
+const DEFAULT_DIGITS: u32 = 6;
+
+/// Default time step of a key URI in seconds.
+const DEFAULT_PERIOD: u64 = 30;
+
+const URI_PREFIX: &str = "otpauth://";
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(DeriveError, Debug, Clone, PartialEq, Eq)]
+pub enum OtpError {
+    #[error("one-time passwords have between {min} and {max} digits, got {has}")]
+    InvalidDigits { has: u32, min: u32, max: u32 },
+
+    #[error("time step has to be greater than zero")]
+    InvalidTimeStep,
+
+    #[error("invalid key uri: {0}")]
+    InvalidUri(&'static str),
+
+    #[error("invalid value of the key uri parameter `{0}`")]
+    InvalidParameter(String),
+
+    #[error("unsupported algorithm `{0}`")]
+    UnsupportedAlgorithm(String),
+}
+
+/// Hash function of the HMAC of a one-time password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OtpHash {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl OtpHash {
+    /// Compute the HMAC over `msg` with `key`.
+    pub(crate) fn hmac(self, msg: &[u8], key: &[u8]) -> Vec<u8> {
+        match self {
+            OtpHash::Sha1 => {
+                // HMAC accepts keys of any length.
+                let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("any key length");
+                mac.update(msg);
+                mac.finalize().into_bytes().to_vec()
+            }
+            OtpHash::Sha256 => {
+                let mut mac = [0; SHA256_LEN];
+                HMAC_SHA256(msg, key, &mut mac);
+                mac.to_vec()
+            }
+            OtpHash::Sha512 => {
+                let mut mac = [0; SHA512_LEN];
+                HMAC_SHA512(msg, key, &mut mac);
+                mac.to_vec()
+            }
+        }
+    }
+}
+
+/// Moving factor of the one-time passwords of a key URI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OtpType {
+    /// Time-based passwords with the time step in seconds.
+    Totp { period: u64 },
+    /// Counter-based passwords with the initial counter.
+    Hotp { counter: u64 },
+}
+
+/// Parameters of an `otpauth://totp` or `otpauth://hotp` key URI, without the secret.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OtpAuth {
+    pub issuer: Option<String>,
+
+    pub account: String,
+
+    pub hash_type: OtpHash,
+
+    pub digits: u32,
+
+    pub otp_type: OtpType,
+}
+
+impl From<OtpAuth> for ProcedureOutput {
+    fn from(auth: OtpAuth) -> Self {
+        // serializing the parameters can not fail.
+        bincode::serialize(&auth).expect("serialize otpauth").into()
+    }
+}
+
+impl TryFrom<ProcedureOutput> for OtpAuth {
+    type Error = bincode::Error;
+    fn try_from(value: ProcedureOutput) -> Result<Self, Self::Error> {
+        bincode::deserialize(&Vec::<u8>::from(value))
+    }
+}
+
+/// Check that a one-time password with `digits` digits can be computed.
+pub fn check_digits(digits: u32) -> Result<(), OtpError> {
+    if (OTP_MIN_DIGITS..=OTP_MAX_DIGITS).contains(&digits) {
+        Ok(())
+    } else {
+        Err(OtpError::InvalidDigits {
+            has: digits,
+            min: OTP_MIN_DIGITS,
+            max: OTP_MAX_DIGITS,
+        })
+    }
+}
+
+/// Get the moving factor of TOTP at `timestamp` (seconds since the unix epoch).
+pub fn time_counter(timestamp: u64, time_step: u64) -> Result<u64, OtpError> {
+    timestamp.checked_div(time_step).ok_or(OtpError::InvalidTimeStep)
+}
+
+/// Compute the one-time password from the HMAC over the moving factor with the dynamic truncation of RFC 4226.
+pub fn code(mac: &[u8], digits: u32) -> Result<String, OtpError> {
+    check_digits(digits)?;
+    let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([mac[offset], mac[offset + 1], mac[offset + 2], mac[offset + 3]]) & 0x7fff_ffff;
+    Ok(format!(
+        "{:0width$}",
+        binary % 10u32.pow(digits),
+        width = digits as usize
+    ))
+}
+
+/// Parse a key URI of the form `otpauth://<totp|hotp>/<issuer>:<account>?secret=<base32>&...` into its parameters
+/// and the decoded secret. Key URIs without an `algorithm` parameter use SHA-1.
+pub fn parse_uri(uri: &str) -> Result<(OtpAuth, Vec<u8>), OtpError> {
+    let rest = uri
+        .strip_prefix(URI_PREFIX)
+        .ok_or(OtpError::InvalidUri("not an otpauth uri"))?;
+    let (is_totp, rest) = match rest.split_once('/') {
+        Some(("totp", rest)) => (true, rest),
+        Some(("hotp", rest)) => (false, rest),
+        _ => return Err(OtpError::InvalidUri("unsupported type")),
+    };
+    let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let label = percent_decode(label).ok_or(OtpError::InvalidUri("invalid label"))?;
+    let (mut issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (Some(issuer.to_string()), account.trim_start().to_string()),
+        None => (None, label),
+    };
+
+    let mut secret = None;
+    let mut hash_type = OtpHash::Sha1;
+    let mut digits = DEFAULT_DIGITS;
+    let mut period = DEFAULT_PERIOD;
+    let mut counter = None;
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        let invalid = || OtpError::InvalidParameter(key.to_string());
+        match key {
+            "secret" => secret = Some(base32_decode(value).ok_or_else(invalid)?),
+            "issuer" => issuer = Some(percent_decode(value).ok_or_else(invalid)?),
+            "algorithm" => {
+                hash_type = match value.to_ascii_uppercase().as_str() {
+                    "SHA1" => OtpHash::Sha1,
+                    "SHA256" => OtpHash::Sha256,
+                    "SHA512" => OtpHash::Sha512,
+                    _ => return Err(OtpError::UnsupportedAlgorithm(value.to_string())),
+                }
+            }
+            "digits" => digits = value.parse().map_err(|_| invalid())?,
+            "period" => period = value.parse().map_err(|_| invalid())?,
+            "counter" => counter = Some(value.parse().map_err(|_| invalid())?),
+            // unknown parameters, e.g. the image of the issuer, are ignored.
+            _ => {}
+        }
+    }
+
+    let secret = secret.ok_or(OtpError::InvalidUri("missing secret"))?;
+    check_digits(digits)?;
+    let otp_type = if is_totp {
+        if period == 0 {
+            return Err(OtpError::InvalidTimeStep);
+        }
+        OtpType::Totp { period }
+    } else {
+        let counter = counter.ok_or(OtpError::InvalidUri("missing counter"))?;
+        OtpType::Hotp { counter }
+    };
+
+    let auth = OtpAuth {
+        issuer,
+        account,
+        hash_type,
+        digits,
+        otp_type,
+    };
+    Ok((auth, secret))
+}
+
+/// Decode unpadded or padded base32 of RFC 4648, ignoring the case of the letters.
+fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer = 0u64;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|a| *a == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    buffer.zeroize();
+    if bytes.is_empty() {
+        None
+    } else {
+        Some(bytes)
+    }
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        match b {
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b'+' => bytes.push(b' '),
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
@@ -1,8 +1,19 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use super::types::*;
-use crate::{state::secure::SecureClient, Location};
+use super::{
+    encoding::{self, KeyFormat, PublicKeyFormat},
+    ethereum::{self, EthereumTransaction},
+    otp::{self, OtpAuth, OtpHash},
+    sealed_box, shamir,
+    slip10::{self, Curve, DerivationPath, ExtendedKey},
+    token::{self, JwsAlgorithm},
+    types::*,
+    wrap::WrappedRecord,
+    x509::{self, CertificateEncoding, CertificateProfile, SubjectAltName},
+};
+use crate::{state::secure::SecureClient, Location, VaultFlags};
+use bech32::ToBase32;
 pub use crypto::keys::slip10::{Chain, ChainCode};
 use crypto::{
     ciphers::{
@@ -10,19 +21,69 @@ use crypto::{
         chacha::XChaCha20Poly1305,
         traits::{Aead, Tag},
     },
-    hashes::sha::{Sha256, Sha384, Sha512, SHA256_LEN, SHA384_LEN, SHA512_LEN},
+    hashes::{
+        blake2b::Blake2b256,
+        sha::{Sha256, Sha384, Sha512, SHA256_LEN, SHA384_LEN, SHA512_LEN},
+        Digest,
+    },
     keys::{
         bip39,
         pbkdf::{PBKDF2_HMAC_SHA256, PBKDF2_HMAC_SHA384, PBKDF2_HMAC_SHA512},
-        slip10, x25519,
+        x25519,
     },
     macs::hmac::{HMAC_SHA256, HMAC_SHA384, HMAC_SHA512},
     signatures::ed25519,
     utils::rand::fill,
 };
-use engine::{runtime::GuardedVec, vault::RecordHint};
+use engine::{
+    runtime::GuardedVec,
+    vault::{RecordHint, RecordPolicy},
+};
+use k256::ecdsa::SigningKey;
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
 use stronghold_utils::GuardDebug;
+use zeroize::Zeroize;
+
+/// Length of a secp256k1 secret key.
+pub const SECP256K1_SECRET_KEY_LENGTH: usize = 32;
+
+/// Length of a recoverable secp256k1 signature.
+pub const SECP256K1_SIGNATURE_LENGTH: usize = 65;
+
+/// Type byte of Ed25519 addresses.
+const ED25519_ADDRESS_TYPE: u8 = 0;
+
+/// Maximum number of addresses that are derived by one [`Ed25519AddressBatch`].
+pub const ED25519_ADDRESS_BATCH_MAX_COUNT: u32 = 1000;
+
+/// Upper bound for the memory cost of [`Argon2id`] and [`Argon2idDerive`] in KiB (256 MiB). Procedures may be run
+/// by remote peers and many times in a row, so the bound is lower than the one for the password of a snapshot.
+pub const ARGON2_MAX_MEMORY_COST: u32 = 256 * 1024;
+
+/// Upper bound for the time cost of [`Argon2id`] and [`Argon2idDerive`], the same bound that applies to the password
+/// of a snapshot.
+pub const ARGON2_MAX_TIME_COST: u32 = engine::snapshot::kdf::MAX_TIME_COST;
+
+/// Upper bound for the degree of parallelism of [`Argon2id`] and [`Argon2idDerive`], the same bound that applies to
+/// the password of a snapshot.
+pub const ARGON2_MAX_PARALLELISM: u32 = engine::snapshot::kdf::MAX_PARALLELISM;
+
+/// Upper bound for the `log_n` parameter of [`Scrypt`] and [`ScryptDerive`]. Together with [`SCRYPT_MAX_R`] it limits
+/// the memory of scrypt, `128 * r * 2^log_n` bytes, to 256 MiB, the same as [`ARGON2_MAX_MEMORY_COST`].
+pub const SCRYPT_MAX_LOG_N: u8 = 18;
+
+/// Upper bound for the `r` parameter of [`Scrypt`] and [`ScryptDerive`].
+pub const SCRYPT_MAX_R: u32 = 8;
+
+/// Upper bound for the `p` parameter of [`Scrypt`] and [`ScryptDerive`].
+pub const SCRYPT_MAX_P: u32 = 16;
+
+/// Upper bound for the length of a key that is derived with Argon2id or scrypt.
+pub const KDF_MAX_LENGTH: usize = 1024;
 
 /// Enum that wraps all cryptographic procedures that are supported by Stronghold.
 ///  
@@ -33,18 +94,47 @@ pub enum StrongholdProcedure {
     WriteVault(WriteVault),
     RevokeData(RevokeData),
     GarbageCollect(GarbageCollect),
+    RotateVaultKey(RotateVaultKey),
     CopyRecord(CopyRecord),
     Slip10Generate(Slip10Generate),
     Slip10Derive(Slip10Derive),
+    Slip10Xpub(Slip10Xpub),
     BIP39Generate(BIP39Generate),
     BIP39Recover(BIP39Recover),
     PublicKey(PublicKey),
+    ImportKey(ImportKey),
+    ExportPublicKey(ExportPublicKey),
+    CreateCertificateRequest(CreateCertificateRequest),
+    CreateSelfSignedCertificate(CreateSelfSignedCertificate),
+    SignCertificateRequest(SignCertificateRequest),
     GenerateKey(GenerateKey),
     Ed25519Sign(Ed25519Sign),
+    Ed25519Address(Ed25519Address),
+    Ed25519AddressBatch(Ed25519AddressBatch),
+    SignJws(SignJws),
+    SignPaseto(SignPaseto),
+    ShamirSplit(ShamirSplit),
+    ShamirCombine(ShamirCombine),
+    SealedBoxEncrypt(SealedBoxEncrypt),
+    SealedBoxDecrypt(SealedBoxDecrypt),
+    ExportWrapped(ExportWrapped),
+    ImportWrapped(ImportWrapped),
+    Secp256k1EcdsaSign(Secp256k1EcdsaSign),
+    EthereumAddress(EthereumAddress),
+    EthereumSignMessage(EthereumSignMessage),
+    EthereumSignTypedData(EthereumSignTypedData),
+    EthereumSignTransaction(EthereumSignTransaction),
     X25519DiffieHellman(X25519DiffieHellman),
     Hmac(Hmac),
+    Totp(Totp),
+    Hotp(Hotp),
+    TotpImport(TotpImport),
     Hkdf(Hkdf),
     Pbkdf2Hmac(Pbkdf2Hmac),
+    Argon2id(Argon2id),
+    Argon2idDerive(Argon2idDerive),
+    Scrypt(Scrypt),
+    ScryptDerive(ScryptDerive),
     AeadEncrypt(AeadEncrypt),
     AeadDecrypt(AeadDecrypt),
 }
@@ -58,27 +148,200 @@ impl Procedure for StrongholdProcedure {
             WriteVault(proc) => proc.execute(runner).map(|o| o.into()),
             RevokeData(proc) => proc.execute(runner).map(|o| o.into()),
             GarbageCollect(proc) => proc.execute(runner).map(|o| o.into()),
+            RotateVaultKey(proc) => proc.execute(runner).map(|o| o.into()),
             CopyRecord(proc) => proc.execute(runner).map(|o| o.into()),
             Slip10Generate(proc) => proc.execute(runner).map(|o| o.into()),
             Slip10Derive(proc) => proc.execute(runner).map(|o| o.into()),
+            Slip10Xpub(proc) => proc.execute(runner).map(|o| o.into()),
             BIP39Generate(proc) => proc.execute(runner).map(|o| o.into()),
             BIP39Recover(proc) => proc.execute(runner).map(|o| o.into()),
             GenerateKey(proc) => proc.execute(runner).map(|o| o.into()),
             PublicKey(proc) => proc.execute(runner).map(|o| o.into()),
+            ImportKey(proc) => proc.execute(runner).map(|o| o.into()),
+            ExportPublicKey(proc) => proc.execute(runner).map(|o| o.into()),
+            CreateCertificateRequest(proc) => proc.execute(runner).map(|o| o.into()),
+            CreateSelfSignedCertificate(proc) => proc.execute(runner).map(|o| o.into()),
+            SignCertificateRequest(proc) => proc.execute(runner).map(|o| o.into()),
             Ed25519Sign(proc) => proc.execute(runner).map(|o| o.into()),
+            Ed25519Address(proc) => proc.execute(runner).map(|o| o.into()),
+            Ed25519AddressBatch(proc) => proc.execute(runner).map(|o| o.into()),
+            SignJws(proc) => proc.execute(runner).map(|o| o.into()),
+            SignPaseto(proc) => proc.execute(runner).map(|o| o.into()),
+            ShamirSplit(proc) => proc.execute(runner).map(|o| o.into()),
+            ShamirCombine(proc) => proc.execute(runner).map(|o| o.into()),
+            SealedBoxEncrypt(proc) => proc.execute(runner).map(|o| o.into()),
+            SealedBoxDecrypt(proc) => proc.execute(runner).map(|o| o.into()),
+            ExportWrapped(proc) => proc.execute(runner).map(|o| o.into()),
+            ImportWrapped(proc) => proc.execute(runner).map(|o| o.into()),
+            Secp256k1EcdsaSign(proc) => proc.execute(runner).map(|o| o.into()),
+            EthereumAddress(proc) => proc.execute(runner).map(|o| o.into()),
+            EthereumSignMessage(proc) => proc.execute(runner).map(|o| o.into()),
+            EthereumSignTypedData(proc) => proc.execute(runner).map(|o| o.into()),
+            EthereumSignTransaction(proc) => proc.execute(runner).map(|o| o.into()),
             X25519DiffieHellman(proc) => proc.execute(runner).map(|o| o.into()),
             Hmac(proc) => proc.execute(runner).map(|o| o.into()),
+            Totp(proc) => proc.execute(runner).map(|o| o.into()),
+            Hotp(proc) => proc.execute(runner).map(|o| o.into()),
+            TotpImport(proc) => proc.execute(runner).map(|o| o.into()),
             Hkdf(proc) => proc.execute(runner).map(|o| o.into()),
             Pbkdf2Hmac(proc) => proc.execute(runner).map(|o| o.into()),
+            Argon2id(proc) => proc.execute(runner).map(|o| o.into()),
+            Argon2idDerive(proc) => proc.execute(runner).map(|o| o.into()),
+            Scrypt(proc) => proc.execute(runner).map(|o| o.into()),
+            ScryptDerive(proc) => proc.execute(runner).map(|o| o.into()),
             AeadEncrypt(proc) => proc.execute(runner).map(|o| o.into()),
             AeadDecrypt(proc) => proc.execute(runner).map(|o| o.into()),
         }
     }
 }
 
+/// The kind of a [`StrongholdProcedure`], used to restrict the usage of a record through its [`VaultFlags`].
+///
+/// The discriminant of a kind is persisted in the policy of a record and must therefore never change.
+///
+/// [`VaultFlags`]: crate::VaultFlags
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ProcedureKind {
+    WriteVault = 0,
+    RevokeData = 1,
+    GarbageCollect = 2,
+    RotateVaultKey = 3,
+    CopyRecord = 4,
+    Slip10Generate = 5,
+    Slip10Derive = 6,
+    BIP39Generate = 7,
+    BIP39Recover = 8,
+    PublicKey = 9,
+    GenerateKey = 10,
+    Ed25519Sign = 11,
+    X25519DiffieHellman = 12,
+    Hmac = 13,
+    Hkdf = 14,
+    Pbkdf2Hmac = 15,
+    AeadEncrypt = 16,
+    AeadDecrypt = 17,
+    Secp256k1EcdsaSign = 18,
+    Slip10Xpub = 19,
+    EthereumAddress = 20,
+    EthereumSignMessage = 21,
+    EthereumSignTypedData = 22,
+    EthereumSignTransaction = 23,
+    Ed25519Address = 24,
+    Ed25519AddressBatch = 25,
+    ShamirSplit = 26,
+    ShamirCombine = 27,
+    SealedBoxEncrypt = 28,
+    SealedBoxDecrypt = 29,
+    ExportWrapped = 30,
+    ImportWrapped = 31,
+    Argon2id = 32,
+    Argon2idDerive = 33,
+    Scrypt = 34,
+    ScryptDerive = 35,
+    Totp = 36,
+    Hotp = 37,
+    TotpImport = 38,
+    SignJws = 39,
+    SignPaseto = 40,
+    ImportKey = 41,
+    ExportPublicKey = 42,
+    CreateCertificateRequest = 43,
+    CreateSelfSignedCertificate = 44,
+    SignCertificateRequest = 45,
+}
+
+// the usage bitmask of a policy has one bit per kind. Kinds are added with increasing discriminants, so it suffices to
+// check the last kind.
+const _: () = assert!(
+    (ProcedureKind::SignCertificateRequest as u64) < u64::BITS as u64,
+    "the discriminants of procedure kinds must fit into the usage bitmask of a policy"
+);
+
+impl ProcedureKind {
+    /// The kinds of the procedures that move the secret of a record out of the reach of its policy, either as a copy
+    /// in the vault or exported from the Stronghold. Procedures that do so have to be added here, so that they are
+    /// denied by [`VaultFlags::NonExportable`].
+    ///
+    /// [`VaultFlags::NonExportable`]: crate::VaultFlags::NonExportable
+    pub const EXPORTING: [ProcedureKind; 3] = [
+        ProcedureKind::CopyRecord,
+        ProcedureKind::ShamirSplit,
+        ProcedureKind::ExportWrapped,
+    ];
+
+    /// The bit of this kind in the usage bitmask of a [`RecordPolicy`].
+    pub(crate) const fn usage(self) -> u64 {
+        1 << self as u64
+    }
+
+    /// The usage bitmask of all [`ProcedureKind::EXPORTING`] kinds.
+    pub(crate) const fn export_usage() -> u64 {
+        let mut usage = 0;
+        let mut i = 0;
+        while i < Self::EXPORTING.len() {
+            usage |= Self::EXPORTING[i].usage();
+            i += 1;
+        }
+        usage
+    }
+}
+
 impl StrongholdProcedure {
-    pub(crate) fn input(&self) -> Option<Location> {
+    /// The kind of this procedure.
+    pub fn kind(&self) -> ProcedureKind {
+        use StrongholdProcedure::*;
         match self {
+            WriteVault(_) => ProcedureKind::WriteVault,
+            RevokeData(_) => ProcedureKind::RevokeData,
+            GarbageCollect(_) => ProcedureKind::GarbageCollect,
+            RotateVaultKey(_) => ProcedureKind::RotateVaultKey,
+            CopyRecord(_) => ProcedureKind::CopyRecord,
+            Slip10Generate(_) => ProcedureKind::Slip10Generate,
+            Slip10Derive(_) => ProcedureKind::Slip10Derive,
+            Slip10Xpub(_) => ProcedureKind::Slip10Xpub,
+            BIP39Generate(_) => ProcedureKind::BIP39Generate,
+            BIP39Recover(_) => ProcedureKind::BIP39Recover,
+            PublicKey(_) => ProcedureKind::PublicKey,
+            ImportKey(_) => ProcedureKind::ImportKey,
+            ExportPublicKey(_) => ProcedureKind::ExportPublicKey,
+            CreateCertificateRequest(_) => ProcedureKind::CreateCertificateRequest,
+            CreateSelfSignedCertificate(_) => ProcedureKind::CreateSelfSignedCertificate,
+            SignCertificateRequest(_) => ProcedureKind::SignCertificateRequest,
+            GenerateKey(_) => ProcedureKind::GenerateKey,
+            Ed25519Sign(_) => ProcedureKind::Ed25519Sign,
+            X25519DiffieHellman(_) => ProcedureKind::X25519DiffieHellman,
+            Hmac(_) => ProcedureKind::Hmac,
+            Totp(_) => ProcedureKind::Totp,
+            Hotp(_) => ProcedureKind::Hotp,
+            TotpImport(_) => ProcedureKind::TotpImport,
+            Hkdf(_) => ProcedureKind::Hkdf,
+            Pbkdf2Hmac(_) => ProcedureKind::Pbkdf2Hmac,
+            Argon2id(_) => ProcedureKind::Argon2id,
+            Argon2idDerive(_) => ProcedureKind::Argon2idDerive,
+            Scrypt(_) => ProcedureKind::Scrypt,
+            ScryptDerive(_) => ProcedureKind::ScryptDerive,
+            AeadEncrypt(_) => ProcedureKind::AeadEncrypt,
+            AeadDecrypt(_) => ProcedureKind::AeadDecrypt,
+            Secp256k1EcdsaSign(_) => ProcedureKind::Secp256k1EcdsaSign,
+            EthereumAddress(_) => ProcedureKind::EthereumAddress,
+            EthereumSignMessage(_) => ProcedureKind::EthereumSignMessage,
+            EthereumSignTypedData(_) => ProcedureKind::EthereumSignTypedData,
+            EthereumSignTransaction(_) => ProcedureKind::EthereumSignTransaction,
+            Ed25519Address(_) => ProcedureKind::Ed25519Address,
+            Ed25519AddressBatch(_) => ProcedureKind::Ed25519AddressBatch,
+            SignJws(_) => ProcedureKind::SignJws,
+            SignPaseto(_) => ProcedureKind::SignPaseto,
+            ShamirSplit(_) => ProcedureKind::ShamirSplit,
+            ShamirCombine(_) => ProcedureKind::ShamirCombine,
+            SealedBoxEncrypt(_) => ProcedureKind::SealedBoxEncrypt,
+            SealedBoxDecrypt(_) => ProcedureKind::SealedBoxDecrypt,
+            ExportWrapped(_) => ProcedureKind::ExportWrapped,
+            ImportWrapped(_) => ProcedureKind::ImportWrapped,
+        }
+    }
+
+    pub(crate) fn inputs(&self) -> Vec<Location> {
+        let mut inputs = match self {
             StrongholdProcedure::CopyRecord(CopyRecord { source: input, .. })
             | StrongholdProcedure::Slip10Derive(Slip10Derive {
                 input: Slip10DeriveInput::Seed(input),
@@ -88,16 +351,65 @@ impl StrongholdProcedure {
                 input: Slip10DeriveInput::Key(input),
                 ..
             })
+            | StrongholdProcedure::Slip10Xpub(Slip10Xpub {
+                input: Slip10DeriveInput::Seed(input),
+                ..
+            })
+            | StrongholdProcedure::Slip10Xpub(Slip10Xpub {
+                input: Slip10DeriveInput::Key(input),
+                ..
+            })
             | StrongholdProcedure::PublicKey(PublicKey { private_key: input, .. })
+            | StrongholdProcedure::ExportPublicKey(ExportPublicKey { private_key: input, .. })
+            | StrongholdProcedure::CreateCertificateRequest(CreateCertificateRequest { private_key: input, .. })
+            | StrongholdProcedure::CreateSelfSignedCertificate(CreateSelfSignedCertificate {
+                private_key: input,
+                ..
+            })
+            | StrongholdProcedure::SignCertificateRequest(SignCertificateRequest { ca_key: input, .. })
             | StrongholdProcedure::Ed25519Sign(Ed25519Sign { private_key: input, .. })
+            | StrongholdProcedure::Ed25519Address(Ed25519Address { private_key: input, .. })
+            | StrongholdProcedure::ShamirSplit(ShamirSplit { source: input, .. })
+            | StrongholdProcedure::SignJws(SignJws { private_key: input, .. })
+            | StrongholdProcedure::SignPaseto(SignPaseto { private_key: input, .. })
+            | StrongholdProcedure::Ed25519AddressBatch(Ed25519AddressBatch {
+                input: Slip10DeriveInput::Seed(input),
+                ..
+            })
+            | StrongholdProcedure::Ed25519AddressBatch(Ed25519AddressBatch {
+                input: Slip10DeriveInput::Key(input),
+                ..
+            })
+            | StrongholdProcedure::Secp256k1EcdsaSign(Secp256k1EcdsaSign { private_key: input, .. })
+            | StrongholdProcedure::EthereumAddress(EthereumAddress { private_key: input })
+            | StrongholdProcedure::EthereumSignMessage(EthereumSignMessage { private_key: input, .. })
+            | StrongholdProcedure::EthereumSignTypedData(EthereumSignTypedData { private_key: input, .. })
+            | StrongholdProcedure::EthereumSignTransaction(EthereumSignTransaction { private_key: input, .. })
             | StrongholdProcedure::X25519DiffieHellman(X25519DiffieHellman { private_key: input, .. })
             | StrongholdProcedure::Hkdf(Hkdf { ikm: input, .. })
+            | StrongholdProcedure::Argon2idDerive(Argon2idDerive { password: input, .. })
+            | StrongholdProcedure::ScryptDerive(ScryptDerive { password: input, .. })
             | StrongholdProcedure::Hmac(Hmac { key: input, .. })
+            | StrongholdProcedure::Totp(Totp { key: input, .. })
+            | StrongholdProcedure::Hotp(Hotp { key: input, .. })
             | StrongholdProcedure::AeadEncrypt(AeadEncrypt { key: input, .. })
-            | StrongholdProcedure::AeadDecrypt(AeadDecrypt { key: input, .. }) => Some(input.clone()),
-            _ => None,
+            | StrongholdProcedure::AeadDecrypt(AeadDecrypt { key: input, .. })
+            | StrongholdProcedure::SealedBoxDecrypt(SealedBoxDecrypt { private_key: input, .. })
+            | StrongholdProcedure::ExportWrapped(ExportWrapped { source: input, .. })
+            | StrongholdProcedure::ImportWrapped(ImportWrapped { key: input, .. }) => vec![input.clone()],
+            _ => Vec::new(),
+        };
+        // the key encryption key is used in addition to the exported record.
+        if let StrongholdProcedure::ExportWrapped(ExportWrapped {
+            wrapping_key: WrappingKey::Symmetric(kek),
+            ..
+        }) = self
+        {
+            inputs.push(kek.clone());
         }
+        inputs
     }
+
     pub(crate) fn output(&self) -> Option<Location> {
         match self {
             StrongholdProcedure::WriteVault(WriteVault { location: output, .. })
@@ -106,10 +418,22 @@ impl StrongholdProcedure {
             | StrongholdProcedure::Slip10Derive(Slip10Derive { output, .. })
             | StrongholdProcedure::BIP39Generate(BIP39Generate { output, .. })
             | StrongholdProcedure::BIP39Recover(BIP39Recover { output, .. })
+            | StrongholdProcedure::ShamirCombine(ShamirCombine { output, .. })
             | StrongholdProcedure::GenerateKey(GenerateKey { output, .. })
+            | StrongholdProcedure::ImportKey(ImportKey { output, .. })
             | StrongholdProcedure::X25519DiffieHellman(X25519DiffieHellman { shared_key: output, .. })
             | StrongholdProcedure::Hkdf(Hkdf { okm: output, .. })
-            | StrongholdProcedure::Pbkdf2Hmac(Pbkdf2Hmac { output, .. }) => Some(output.clone()),
+            | StrongholdProcedure::Pbkdf2Hmac(Pbkdf2Hmac { output, .. })
+            | StrongholdProcedure::Argon2id(Argon2id { output, .. })
+            | StrongholdProcedure::Argon2idDerive(Argon2idDerive { output, .. })
+            | StrongholdProcedure::Scrypt(Scrypt { output, .. })
+            | StrongholdProcedure::TotpImport(TotpImport { output, .. })
+            | StrongholdProcedure::ScryptDerive(ScryptDerive { output, .. })
+            | StrongholdProcedure::ImportWrapped(ImportWrapped { target: output, .. })
+            | StrongholdProcedure::SealedBoxDecrypt(SealedBoxDecrypt {
+                output: Some((output, _)),
+                ..
+            }) => Some(output.clone()),
             _ => None,
         }
     }
@@ -150,13 +474,23 @@ macro_rules! procedures {
 
 procedures! {
     // Stronghold procedures that implement the `GenerateSecret` trait.
-    GenerateSecret => { WriteVault, BIP39Generate, BIP39Recover, Slip10Generate, GenerateKey, Pbkdf2Hmac },
+    GenerateSecret => {
+        WriteVault, BIP39Generate, BIP39Recover, ShamirCombine, Slip10Generate, GenerateKey, ImportKey, Pbkdf2Hmac,
+        Argon2id, Scrypt, TotpImport
+    },
     // Stronghold procedures that implement the `DeriveSecret` trait.
-    DeriveSecret => { CopyRecord, Slip10Derive, X25519DiffieHellman, Hkdf },
+    DeriveSecret => { CopyRecord, Slip10Derive, X25519DiffieHellman, Hkdf, Argon2idDerive, ScryptDerive },
     // Stronghold procedures that implement the `UseSecret` trait.
-    UseSecret => { PublicKey, Ed25519Sign, Hmac, AeadEncrypt, AeadDecrypt },
+    UseSecret => {
+        PublicKey, ExportPublicKey, Ed25519Sign, CreateCertificateRequest, CreateSelfSignedCertificate,
+        SignCertificateRequest, Ed25519Address, Ed25519AddressBatch, Secp256k1EcdsaSign, EthereumAddress,
+        EthereumSignMessage, EthereumSignTypedData, EthereumSignTransaction, SignJws, SignPaseto, ShamirSplit, Slip10Xpub,
+        Hmac, Totp, Hotp, AeadEncrypt, AeadDecrypt
+    },
     // Stronghold procedures that directly implement the `Procedure` trait.
-    _ => { RevokeData, GarbageCollect }
+    _ => {
+        RevokeData, GarbageCollect, RotateVaultKey, SealedBoxEncrypt, SealedBoxDecrypt, ExportWrapped, ImportWrapped
+    }
 }
 
 /// Write data to the specified [`Location`].
@@ -167,6 +501,13 @@ pub struct WriteVault {
     pub location: Location,
 
     pub hint: RecordHint,
+
+    /// Optional lifetime of the record. Once it has passed, the record is treated as revoked and removed on the next
+    /// garbage collection.
+    pub lifetime: Option<Duration>,
+
+    /// Policy options of the record, see [`VaultFlags`].
+    pub flags: Vec<VaultFlags>,
 }
 
 impl GenerateSecret for WriteVault {
@@ -182,6 +523,15 @@ impl GenerateSecret for WriteVault {
     fn target(&self) -> (&Location, RecordHint) {
         (&self.location, self.hint)
     }
+
+    fn policy(&self) -> RecordPolicy {
+        let mut policy = VaultFlags::policy(&self.flags);
+        if let Some(lifetime) = self.lifetime {
+            let expires = SystemTime::now() + lifetime;
+            policy.expires = Some(policy.expires.map_or(expires, |e| e.min(expires)));
+        }
+        policy
+    }
 }
 
 /// Revoke the data from the specified [`Location`]. Revoked data is not readable and can be
@@ -220,6 +570,23 @@ impl Procedure for GarbageCollect {
     }
 }
 
+/// Re-encrypts all records in a Vault based on the given `vault_path` with a freshly generated key. The data and the
+/// revocation metadata of the records are re-encrypted as well, and the new key replaces the old one in the key store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RotateVaultKey {
+    pub vault_path: Vec<u8>,
+}
+
+impl Procedure for RotateVaultKey {
+    type Output = ();
+
+    fn execute<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let vault_id = SecureClient::derive_vault_id(self.vault_path);
+        runner.rotate_vault_key(vault_id)?;
+        Ok(())
+    }
+}
+
 /// Copy the content of a record from one location to another.
 ///
 /// Note: This does not remove the old record. Users that would like to move the record instead
@@ -259,19 +626,29 @@ pub enum MnemonicLanguage {
     Japanese,
 }
 
+impl MnemonicLanguage {
+    fn wordlist(&self) -> bip39::wordlist::Wordlist<'static> {
+        match self {
+            MnemonicLanguage::English => bip39::wordlist::ENGLISH,
+            MnemonicLanguage::Japanese => bip39::wordlist::JAPANESE,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum AeadCipher {
     Aes256Gcm,
     XChaCha20Poly1305,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum KeyType {
     Ed25519,
     X25519,
+    Secp256k1Ecdsa,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Sha2Hash {
     Sha256,
     Sha384,
@@ -383,6 +760,74 @@ impl GenerateSecret for Slip10Generate {
     }
 }
 
+/// Split the secret at the `source` location into `shares` Shamir shares, of which `threshold` are required to recover
+/// the secret with [`ShamirCombine`].
+///
+/// The secret is encrypted with the optional passphrase before it is split, following the scheme of SLIP39. Each
+/// share is returned as a mnemonic sentence of the wordlist of the `language`. Secrets have to be an even number of
+/// bytes between 16 and 255, e.g. a 64 byte seed or a SLIP10 key.
+#[derive(GuardDebug, Clone, Serialize, Deserialize)]
+pub struct ShamirSplit {
+    pub source: Location,
+
+    pub threshold: u8,
+
+    pub shares: u8,
+
+    pub passphrase: Option<String>,
+
+    pub language: MnemonicLanguage,
+}
+
+impl UseSecret for ShamirSplit {
+    type Output = Vec<String>;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let passphrase = self.passphrase.unwrap_or_default();
+        shamir::split(
+            &guard.borrow(),
+            self.threshold,
+            self.shares,
+            passphrase.as_bytes(),
+            &self.language.wordlist(),
+        )
+        .map_err(|e| e.to_string().into())
+    }
+
+    fn source(&self) -> &Location {
+        &self.source
+    }
+}
+
+/// Recover a secret from the shares of [`ShamirSplit`] and store it in the `output` location.
+#[derive(GuardDebug, Clone, Serialize, Deserialize)]
+pub struct ShamirCombine {
+    pub shares: Vec<String>,
+
+    pub passphrase: Option<String>,
+
+    pub language: MnemonicLanguage,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+}
+
+impl GenerateSecret for ShamirCombine {
+    type Output = ();
+
+    fn generate(self) -> Result<Products<()>, FatalProcedureError> {
+        let passphrase = self.passphrase.clone().unwrap_or_default();
+        let secret = shamir::combine(&self.shares, passphrase.as_bytes(), &self.language.wordlist())
+            .map_err(|e| FatalProcedureError::from(e.to_string()))?;
+        Ok(Products { secret, output: () })
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+}
+
 #[derive(GuardDebug, Clone, Serialize, Deserialize)]
 pub enum Slip10DeriveInput {
     /// Note that BIP39 seeds are allowed to be used as SLIP10 seeds
@@ -390,11 +835,34 @@ pub enum Slip10DeriveInput {
     Key(Location),
 }
 
-/// Derive a SLIP10 child key from a seed or a parent key, store it in output location and
-/// return the corresponding chain code
+impl Slip10DeriveInput {
+    fn location(&self) -> &Location {
+        match self {
+            Slip10DeriveInput::Key(loc) => loc,
+            Slip10DeriveInput::Seed(loc) => loc,
+        }
+    }
+
+    fn extended_key(&self, curve: Curve, guard: GuardedVec<u8>) -> Result<ExtendedKey, crypto::Error> {
+        match self {
+            Slip10DeriveInput::Key(_) => ExtendedKey::try_from_slice(&guard.borrow()),
+            Slip10DeriveInput::Seed(_) => Ok(ExtendedKey::from_seed(curve, &guard.borrow())),
+        }
+    }
+}
+
+/// Derive a SLIP10 child key from a seed or a parent key on the specified curve, store it in output location and
+/// return the corresponding chain code.
+///
+/// Non-hardened segments in the chain are only supported by the secp256k1 and NIST P-256 curves. Derived keys have
+/// to be derived further on the curve of their parent. The chain may only be empty if the input is a seed.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Slip10Derive {
-    pub chain: Chain,
+    /// Defaults to [`Curve::Ed25519`], so that serialized procedures without a curve keep their meaning.
+    #[serde(default)]
+    pub curve: Curve,
+
+    pub chain: DerivationPath,
 
     pub input: Slip10DeriveInput,
 
@@ -407,25 +875,25 @@ impl DeriveSecret for Slip10Derive {
     type Output = ChainCode;
 
     fn derive(self, guard: GuardedVec<u8>) -> Result<Products<ChainCode>, FatalProcedureError> {
-        let dk = match self.input {
-            Slip10DeriveInput::Key(_) => {
-                slip10::Key::try_from(&*guard.borrow()).and_then(|parent| parent.derive(&self.chain))
-            }
-            Slip10DeriveInput::Seed(_) => {
-                slip10::Seed::from_bytes(&guard.borrow()).derive(slip10::Curve::Ed25519, &self.chain)
-            }
-        }?;
+        self.chain.validate(self.curve).map_err(|e| e.to_string())?;
+        // an empty chain would store a copy of the parent key.
+        if self.chain.is_empty() && matches!(self.input, Slip10DeriveInput::Key(_)) {
+            return Err(FatalProcedureError::from(
+                "the chain for deriving from a key must not be empty".to_string(),
+            ));
+        }
+        let dk = self
+            .input
+            .extended_key(self.curve, guard)
+            .and_then(|parent| parent.derive(self.curve, &self.chain))?;
         Ok(Products {
-            secret: dk.into(),
+            secret: (&dk).into(),
             output: dk.chain_code(),
         })
     }
 
     fn source(&self) -> &Location {
-        match &self.input {
-            Slip10DeriveInput::Key(loc) => loc,
-            Slip10DeriveInput::Seed(loc) => loc,
-        }
+        self.input.location()
     }
 
     fn target(&self) -> (&Location, RecordHint) {
@@ -433,6 +901,149 @@ impl DeriveSecret for Slip10Derive {
     }
 }
 
+/// Export the BIP32 extended public key (xpub) of the node at the end of the chain, derived from a seed or a parent
+/// key on the specified curve. The xpub is returned base58check encoded, so that watch-only wallets can derive the
+/// non-hardened children of the node without access to the vault.
+///
+/// The depth and the parent fingerprint of the xpub are relative to the input, so they are only exact if the input
+/// is a seed.
+///
+/// Extended public keys are only defined for the secp256k1 and NIST P-256 curves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Slip10Xpub {
+    pub curve: Curve,
+
+    pub chain: DerivationPath,
+
+    pub input: Slip10DeriveInput,
+}
+
+impl UseSecret for Slip10Xpub {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        if self.curve == Curve::Ed25519 {
+            return Err(FatalProcedureError::from(
+                "extended public keys are not supported on Ed25519".to_string(),
+            ));
+        }
+        self.chain.validate(self.curve).map_err(|e| e.to_string())?;
+        let parent = self.input.extended_key(self.curve, guard)?;
+        let xpub = slip10::xpub(self.curve, &parent, &self.chain)?;
+        Ok(xpub)
+    }
+
+    fn source(&self) -> &Location {
+        self.input.location()
+    }
+}
+
+fn ed25519_address(hrp: &str, pk: &ed25519::PublicKey) -> Result<String, FatalProcedureError> {
+    let mut address = [0; 1 + 32];
+    address[0] = ED25519_ADDRESS_TYPE;
+    address[1..].copy_from_slice(&Blake2b256::digest(&pk.to_bytes()));
+    bech32::encode(hrp, address.to_base32(), bech32::Variant::Bech32).map_err(|e| e.to_string().into())
+}
+
+fn hmac(hash_type: &Sha2Hash, msg: &[u8], key: &[u8]) -> Vec<u8> {
+    match hash_type {
+        Sha2Hash::Sha256 => {
+            let mut mac = [0; SHA256_LEN];
+            HMAC_SHA256(msg, key, &mut mac);
+            mac.to_vec()
+        }
+        Sha2Hash::Sha384 => {
+            let mut mac = [0; SHA384_LEN];
+            HMAC_SHA384(msg, key, &mut mac);
+            mac.to_vec()
+        }
+        Sha2Hash::Sha512 => {
+            let mut mac = [0; SHA512_LEN];
+            HMAC_SHA512(msg, key, &mut mac);
+            mac.to_vec()
+        }
+    }
+}
+
+fn check_kdf_param<T: PartialOrd + std::fmt::Display>(name: &str, value: T, max: T) -> Result<(), FatalProcedureError> {
+    if value > max {
+        return Err(FatalProcedureError::from(format!(
+            "{} {} exceeds the maximum of {}",
+            name, value, max
+        )));
+    }
+    Ok(())
+}
+
+fn argon2id(
+    password: &[u8],
+    salt: &[u8],
+    memory_cost: u32,
+    time_cost: u32,
+    parallelism: u32,
+    length: usize,
+) -> Result<Vec<u8>, FatalProcedureError> {
+    // the parameters may be sent by a remote peer, so they are bounded before any memory is allocated.
+    check_kdf_param("memory cost", memory_cost, ARGON2_MAX_MEMORY_COST)?;
+    check_kdf_param("time cost", time_cost, ARGON2_MAX_TIME_COST)?;
+    check_kdf_param("parallelism", parallelism, ARGON2_MAX_PARALLELISM)?;
+    check_kdf_param("length", length, KDF_MAX_LENGTH)?;
+    let params = argon2::Params::new(memory_cost, time_cost, parallelism, Some(length))
+        .map_err(|e| FatalProcedureError::from(format!("invalid argon2 parameters: {}", e)))?;
+    let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut key = vec![0; length];
+    argon2
+        .hash_password_into(password, salt, &mut key)
+        .map_err(|e| FatalProcedureError::from(format!("argon2 failed: {}", e)))?;
+    Ok(key)
+}
+
+fn scrypt(
+    password: &[u8],
+    salt: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+    length: usize,
+) -> Result<Vec<u8>, FatalProcedureError> {
+    check_kdf_param("log_n", log_n, SCRYPT_MAX_LOG_N)?;
+    check_kdf_param("r", r, SCRYPT_MAX_R)?;
+    check_kdf_param("p", p, SCRYPT_MAX_P)?;
+    check_kdf_param("length", length, KDF_MAX_LENGTH)?;
+    let params = scrypt::Params::new(log_n, r, p, length)
+        .map_err(|e| FatalProcedureError::from(format!("invalid scrypt parameters: {}", e)))?;
+    let mut key = vec![0; length];
+    scrypt::scrypt(password, salt, &params, &mut key)
+        .map_err(|e| FatalProcedureError::from(format!("scrypt failed: {}", e)))?;
+    Ok(key)
+}
+
+fn public_key(ty: &KeyType, guard: GuardedVec<u8>, compressed: bool) -> Result<Vec<u8>, FatalProcedureError> {
+    match ty {
+        KeyType::Ed25519 => {
+            let sk = ed25519_secret_key(guard)?;
+            Ok(sk.public_key().to_bytes().to_vec())
+        }
+        KeyType::X25519 => {
+            let sk = x25519_secret_key(guard)?;
+            Ok(sk.public_key().to_bytes().to_vec())
+        }
+        KeyType::Secp256k1Ecdsa => {
+            let sk = secp256k1_secret_key(guard)?;
+            let pk = sk.verifying_key().to_encoded_point(compressed);
+            Ok(pk.as_bytes().to_vec())
+        }
+    }
+}
+
+/// Random positive serial number of a certificate.
+fn serial_number() -> Result<[u8; 16], FatalProcedureError> {
+    let mut serial_number = [0; 16];
+    fill(&mut serial_number)?;
+    serial_number[0] &= 0x7f;
+    Ok(serial_number)
+}
+
 fn x25519_secret_key(guard: GuardedVec<u8>) -> Result<x25519::SecretKey, crypto::Error> {
     let raw = guard.borrow();
     let raw = (*raw).to_vec();
@@ -447,6 +1058,51 @@ fn x25519_secret_key(guard: GuardedVec<u8>) -> Result<x25519::SecretKey, crypto:
     x25519::SecretKey::try_from_slice(&raw)
 }
 
+fn secp256k1_secret_key(guard: GuardedVec<u8>) -> Result<SigningKey, FatalProcedureError> {
+    let raw = guard.borrow();
+    // a SLIP10 key is followed by its chain code.
+    let raw = match raw.len() {
+        slip10::EXTENDED_KEY_LENGTH => &raw[..SECP256K1_SECRET_KEY_LENGTH],
+        _ => &raw[..],
+    };
+    if raw.len() != SECP256K1_SECRET_KEY_LENGTH {
+        let e = crypto::Error::BufferSize {
+            has: raw.len(),
+            needs: SECP256K1_SECRET_KEY_LENGTH,
+            name: "data buffer",
+        };
+        return Err(e.into());
+    }
+    SigningKey::from_slice(raw).map_err(|e| FatalProcedureError::from(e.to_string()))
+}
+
+/// Signs the hash with the Ethereum encoding of the recovery id, `v = 27 + recovery id`.
+fn ethereum_signature(
+    sk: &SigningKey,
+    hash: &[u8; 32],
+) -> Result<[u8; SECP256K1_SIGNATURE_LENGTH], FatalProcedureError> {
+    let (sig, recovery_id) = sk
+        .sign_prehash_recoverable(hash)
+        .map_err(|e| FatalProcedureError::from(e.to_string()))?;
+    let mut bytes = [0; SECP256K1_SIGNATURE_LENGTH];
+    bytes[..64].copy_from_slice(&sig.to_bytes());
+    bytes[64] = 27 + recovery_id.to_byte();
+    Ok(bytes)
+}
+
+fn generate_secp256k1_secret_key() -> Result<Vec<u8>, FatalProcedureError> {
+    let mut raw = [0; SECP256K1_SECRET_KEY_LENGTH];
+    // retry in the unlikely case that the random bytes are not a valid scalar.
+    loop {
+        fill(&mut raw)?;
+        if SigningKey::from_slice(&raw).is_ok() {
+            let secret = raw.to_vec();
+            raw.zeroize();
+            return Ok(secret);
+        }
+    }
+}
+
 fn ed25519_secret_key(guard: GuardedVec<u8>) -> Result<ed25519::SecretKey, crypto::Error> {
     let raw = guard.borrow();
     let mut raw = (*raw).to_vec();
@@ -481,6 +1137,7 @@ impl GenerateSecret for GenerateKey {
         let secret = match self.ty {
             KeyType::Ed25519 => ed25519::SecretKey::generate().map(|sk| sk.to_bytes().to_vec())?,
             KeyType::X25519 => x25519::SecretKey::generate().map(|sk| sk.to_bytes().to_vec())?,
+            KeyType::Secp256k1Ecdsa => generate_secp256k1_secret_key()?,
         };
         Ok(Products { secret, output: () })
     }
@@ -490,29 +1147,83 @@ impl GenerateSecret for GenerateKey {
     }
 }
 
-/// Derive an Ed25519 public key from the corresponding private key stored at the specified
-/// location
+/// Derive a public key from the corresponding private key stored at the specified
+/// location.
+///
+/// Secp256k1 public keys are SEC1 encoded, either compressed (33 bytes) or uncompressed (65 bytes).
+/// The `compressed` flag is ignored for the other key types.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PublicKey {
     pub ty: KeyType,
 
     pub private_key: Location,
+
+    /// Defaults to `false`, so that serialized procedures without the flag keep their meaning.
+    #[serde(default)]
+    pub compressed: bool,
 }
 
 impl UseSecret for PublicKey {
-    type Output = [u8; 32];
+    type Output = Vec<u8>;
 
     fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
-        match self.ty {
-            KeyType::Ed25519 => {
-                let sk = ed25519_secret_key(guard)?;
-                Ok(sk.public_key().to_bytes())
-            }
-            KeyType::X25519 => {
-                let sk = x25519_secret_key(guard)?;
-                Ok(sk.public_key().to_bytes())
-            }
-        }
+        public_key(&self.ty, guard, self.compressed)
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Import an encoded private key into the `output` location. The key is validated and stored as raw secret, the
+/// same as keys of [`GenerateKey`].
+#[derive(GuardDebug, Clone, Serialize, Deserialize)]
+pub struct ImportKey {
+    pub ty: KeyType,
+
+    pub format: KeyFormat,
+
+    pub data: Vec<u8>,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+}
+
+impl GenerateSecret for ImportKey {
+    type Output = ();
+
+    fn generate(mut self) -> Result<Products<Self::Output>, FatalProcedureError> {
+        let decoded = encoding::decode_private_key(&self.ty, self.format, &self.data);
+        self.data.zeroize();
+        let mut secret = decoded.map_err(|e| FatalProcedureError::from(e.to_string()))?;
+        Ok(Products {
+            secret: std::mem::take(&mut *secret),
+            output: (),
+        })
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+}
+
+/// Get the public key of the private key at the specified location in the given encoding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportPublicKey {
+    pub ty: KeyType,
+
+    pub format: PublicKeyFormat,
+
+    pub private_key: Location,
+}
+
+impl UseSecret for ExportPublicKey {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let public_key = public_key(&self.ty, guard, false)?;
+        encoding::encode_public_key(&self.ty, self.format, &public_key).map_err(|e| e.to_string().into())
     }
 
     fn source(&self) -> &Location {
@@ -545,19 +1256,426 @@ impl UseSecret for Ed25519Sign {
     }
 }
 
+/// Get the bech32 encoded address of the specified Ed25519 compatible key, e.g. an IOTA or Shimmer address for the
+/// human-readable part `iota` or `smr`.
+///
+/// The address is the Blake2b-256 hash of the public key, prefixed with the Ed25519 address type byte `0`.
+/// Compatible keys are the same as for [`Ed25519Sign`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct X25519DiffieHellman {
-    pub public_key: [u8; x25519::PUBLIC_KEY_LENGTH],
+pub struct Ed25519Address {
+    pub hrp: String,
 
     pub private_key: Location,
+}
 
-    pub shared_key: Location,
+impl UseSecret for Ed25519Address {
+    type Output = String;
 
-    pub hint: RecordHint,
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = ed25519_secret_key(guard)?;
+        ed25519_address(&self.hrp, &sk.public_key())
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
 }
 
-impl DeriveSecret for X25519DiffieHellman {
-    type Output = ();
+/// Derive the bech32 encoded [`Ed25519Address`]es of `count` consecutive SLIP10 keys from a seed or a parent key.
+///
+/// The keys are derived at `chain/start'` to `chain/(start + count - 1)'`. Neither the derived keys nor the seed leave
+/// the vault, and the derived keys are not stored. At most [`ED25519_ADDRESS_BATCH_MAX_COUNT`] addresses are derived
+/// at once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ed25519AddressBatch {
+    pub hrp: String,
+
+    pub chain: DerivationPath,
+
+    pub start: u32,
+
+    pub count: u32,
+
+    pub input: Slip10DeriveInput,
+}
+
+impl UseSecret for Ed25519AddressBatch {
+    type Output = Vec<String>;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        if self.count > ED25519_ADDRESS_BATCH_MAX_COUNT {
+            return Err(FatalProcedureError::from(format!(
+                "batch of {} addresses exceeds the maximum of {}",
+                self.count, ED25519_ADDRESS_BATCH_MAX_COUNT
+            )));
+        }
+        self.chain.validate(Curve::Ed25519).map_err(|e| e.to_string())?;
+        let parent = self
+            .input
+            .extended_key(Curve::Ed25519, guard)?
+            .derive(Curve::Ed25519, &self.chain)?;
+        (0..self.count)
+            .map(|i| {
+                let index = self
+                    .start
+                    .checked_add(i)
+                    .ok_or_else(|| FatalProcedureError::from("index range overflows".to_string()))?;
+                let path = DerivationPath::default()
+                    .hardened_child(index)
+                    .map_err(|e| e.to_string())?;
+                let key = parent.derive(Curve::Ed25519, &path)?;
+                let sk = ed25519::SecretKey::from_bytes(key.secret_key());
+                ed25519_address(&self.hrp, &sk.public_key())
+            })
+            .collect()
+    }
+
+    fn source(&self) -> &Location {
+        self.input.location()
+    }
+}
+
+/// Use the specified secp256k1 key to sign the given prehashed message, e.g. a Keccak-256 or SHA-256 digest.
+///
+/// Compatible keys are secp256k1 secret keys and SLIP10 keys derived on the secp256k1 curve.
+///
+/// Returns the recoverable signature `r || s || v` with the recovery id `v` in the last byte.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Secp256k1EcdsaSign {
+    pub msg: [u8; 32],
+
+    pub private_key: Location,
+}
+
+impl UseSecret for Secp256k1EcdsaSign {
+    type Output = [u8; SECP256K1_SIGNATURE_LENGTH];
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = secp256k1_secret_key(guard)?;
+        let (sig, recovery_id) = sk
+            .sign_prehash_recoverable(&self.msg)
+            .map_err(|e| FatalProcedureError::from(e.to_string()))?;
+
+        let mut bytes = [0; SECP256K1_SIGNATURE_LENGTH];
+        bytes[..64].copy_from_slice(&sig.to_bytes());
+        bytes[64] = recovery_id.to_byte();
+        Ok(bytes)
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Sign a JWS with the key in the vault and return it in compact serialization.
+///
+/// The `header` is the JSON object of the JOSE header. Its `alg` parameter is set to `alg`, and its `kid` parameter
+/// to the key ID of the `private_key` if `key_ids` contains one.
+///
+/// Compatible keys are the same as for [`Ed25519Sign`] and [`Secp256k1EcdsaSign`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignJws {
+    pub alg: JwsAlgorithm,
+
+    pub header: String,
+
+    pub payload: Vec<u8>,
+
+    /// Key IDs of the key locations.
+    pub key_ids: HashMap<Location, String>,
+
+    pub private_key: Location,
+}
+
+impl UseSecret for SignJws {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let kid = self.key_ids.get(&self.private_key).map(String::as_str);
+        let signing_input = token::jws_signing_input(&self.header, self.alg, kid, &self.payload)
+            .map_err(|e| FatalProcedureError::from(e.to_string()))?;
+        let signature = match self.alg {
+            JwsAlgorithm::EdDSA => ed25519_secret_key(guard)?
+                .sign(signing_input.as_bytes())
+                .to_bytes()
+                .to_vec(),
+            JwsAlgorithm::ES256K => {
+                let hash = Sha256::digest(signing_input.as_bytes());
+                let (sig, _) = secp256k1_secret_key(guard)?
+                    .sign_prehash_recoverable(&hash)
+                    .map_err(|e| FatalProcedureError::from(e.to_string()))?;
+                sig.to_bytes().to_vec()
+            }
+        };
+        Ok(token::compact_jws(&signing_input, &signature))
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Sign a PASETO v4.public token with the Ed25519 key in the vault.
+///
+/// The `footer` is appended to the token unencrypted, the `implicit_assertion` is signed but not part of the token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignPaseto {
+    pub payload: Vec<u8>,
+
+    pub footer: Vec<u8>,
+
+    pub implicit_assertion: Vec<u8>,
+
+    pub private_key: Location,
+}
+
+impl UseSecret for SignPaseto {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let pae = token::paseto_v4_public_pae(&self.payload, &self.footer, &self.implicit_assertion);
+        let signature = ed25519_secret_key(guard)?.sign(&pae);
+        Ok(token::paseto_v4_public(
+            &self.payload,
+            &self.footer,
+            &signature.to_bytes(),
+        ))
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Create a PKCS#10 certification request for the Ed25519 key in the vault, signed with the key.
+///
+/// The `subject` is a distinguished name in the string format of RFC 4514, e.g. `CN=service,O=Example`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateCertificateRequest {
+    pub subject: String,
+
+    pub subject_alt_names: Vec<SubjectAltName>,
+
+    pub encoding: CertificateEncoding,
+
+    pub private_key: Location,
+}
+
+impl UseSecret for CreateCertificateRequest {
+    type Output = Vec<u8>;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = ed25519_secret_key(guard)?;
+        x509::certification_request(
+            &self.subject,
+            &self.subject_alt_names,
+            &sk.public_key().to_bytes(),
+            self.encoding,
+            |msg| sk.sign(msg).to_bytes().to_vec(),
+        )
+        .map_err(|e| e.to_string().into())
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Create a self-signed X.509 v3 certificate for the Ed25519 key in the vault.
+///
+/// The `subject` is a distinguished name in the string format of RFC 4514. The certificate gets a random serial
+/// number.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSelfSignedCertificate {
+    pub subject: String,
+
+    pub subject_alt_names: Vec<SubjectAltName>,
+
+    pub profile: CertificateProfile,
+
+    pub encoding: CertificateEncoding,
+
+    pub private_key: Location,
+}
+
+impl UseSecret for CreateSelfSignedCertificate {
+    type Output = Vec<u8>;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = ed25519_secret_key(guard)?;
+        x509::self_signed_certificate(
+            &self.subject,
+            &self.subject_alt_names,
+            &self.profile,
+            &serial_number()?,
+            &sk.public_key().to_bytes(),
+            self.encoding,
+            |msg| sk.sign(msg).to_bytes().to_vec(),
+        )
+        .map_err(|e| e.to_string().into())
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Issue an X.509 v3 certificate for a PKCS#10 certification request, signed with the Ed25519 key of a CA in the
+/// vault.
+///
+/// The subject, the subject alternative names and the public key are taken from the `request`, the validity and
+/// key usage from the `profile`. The issuer is the subject of the `ca_certificate`, which has to be a CA certificate
+/// of the `ca_key`. Requests and certificates are accepted in PEM or DER encoding. Only requests of Ed25519 keys are
+/// supported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignCertificateRequest {
+    pub request: Vec<u8>,
+
+    pub ca_certificate: Vec<u8>,
+
+    pub profile: CertificateProfile,
+
+    pub encoding: CertificateEncoding,
+
+    pub ca_key: Location,
+}
+
+impl UseSecret for SignCertificateRequest {
+    type Output = Vec<u8>;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = ed25519_secret_key(guard)?;
+        x509::issue_certificate(
+            &self.request,
+            &self.ca_certificate,
+            &sk.public_key().to_bytes(),
+            &self.profile,
+            &serial_number()?,
+            self.encoding,
+            |msg| sk.sign(msg).to_bytes().to_vec(),
+        )
+        .map_err(|e| e.to_string().into())
+    }
+
+    fn source(&self) -> &Location {
+        &self.ca_key
+    }
+}
+
+/// Get the EIP-55 checksummed Ethereum address of the specified secp256k1 key, e.g.
+/// `0x2c7536E3605D9C16a7a3D7b1898e529396a65c23`.
+///
+/// Compatible keys are the same as for [`Secp256k1EcdsaSign`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EthereumAddress {
+    pub private_key: Location,
+}
+
+impl UseSecret for EthereumAddress {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = secp256k1_secret_key(guard)?;
+        let address = ethereum::address(sk.verifying_key());
+        Ok(ethereum::checksum_address(&address))
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Sign an EIP-191 personal message (`personal_sign`) with the specified secp256k1 key.
+///
+/// Returns the signature `r || s || v` with `v` being `27` or `28`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EthereumSignMessage {
+    pub msg: Vec<u8>,
+
+    pub private_key: Location,
+}
+
+impl UseSecret for EthereumSignMessage {
+    type Output = [u8; SECP256K1_SIGNATURE_LENGTH];
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = secp256k1_secret_key(guard)?;
+        ethereum_signature(&sk, &ethereum::personal_message_hash(&self.msg))
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Sign EIP-712 typed data (`eth_signTypedData`) with the specified secp256k1 key. The typed data is given by the
+/// hash of its domain separator and the `hashStruct` of its message.
+///
+/// Returns the signature `r || s || v` with `v` being `27` or `28`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EthereumSignTypedData {
+    pub domain_separator: [u8; 32],
+
+    pub struct_hash: [u8; 32],
+
+    pub private_key: Location,
+}
+
+impl UseSecret for EthereumSignTypedData {
+    type Output = [u8; SECP256K1_SIGNATURE_LENGTH];
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = secp256k1_secret_key(guard)?;
+        ethereum_signature(
+            &sk,
+            &ethereum::typed_data_hash(&self.domain_separator, &self.struct_hash),
+        )
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Sign a legacy or EIP-1559 Ethereum transaction with the specified secp256k1 key.
+///
+/// Returns the RLP encoded signed transaction, that can be broadcasted with `eth_sendRawTransaction`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EthereumSignTransaction {
+    pub transaction: EthereumTransaction,
+
+    pub private_key: Location,
+}
+
+impl UseSecret for EthereumSignTransaction {
+    type Output = Vec<u8>;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = secp256k1_secret_key(guard)?;
+        let (sig, recovery_id) = sk
+            .sign_prehash_recoverable(&self.transaction.signing_hash())
+            .map_err(|e| FatalProcedureError::from(e.to_string()))?;
+        Ok(self.transaction.encode_signed(&sig, recovery_id))
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct X25519DiffieHellman {
+    pub public_key: [u8; x25519::PUBLIC_KEY_LENGTH],
+
+    pub private_key: Location,
+
+    pub shared_key: Location,
+
+    pub hint: RecordHint,
+}
+
+impl DeriveSecret for X25519DiffieHellman {
+    type Output = ();
 
     fn derive(self, guard: GuardedVec<u8>) -> Result<Products<()>, FatalProcedureError> {
         let sk = x25519_secret_key(guard)?;
@@ -592,23 +1710,37 @@ impl UseSecret for Hmac {
     type Output = Vec<u8>;
 
     fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
-        match self.hash_type {
-            Sha2Hash::Sha256 => {
-                let mut mac = [0; SHA256_LEN];
-                HMAC_SHA256(&self.msg, &*guard.borrow(), &mut mac);
-                Ok(mac.to_vec())
-            }
-            Sha2Hash::Sha384 => {
-                let mut mac = [0; SHA384_LEN];
-                HMAC_SHA384(&self.msg, &*guard.borrow(), &mut mac);
-                Ok(mac.to_vec())
-            }
-            Sha2Hash::Sha512 => {
-                let mut mac = [0; SHA512_LEN];
-                HMAC_SHA512(&self.msg, &*guard.borrow(), &mut mac);
-                Ok(mac.to_vec())
-            }
-        }
+        Ok(hmac(&self.hash_type, &self.msg, &guard.borrow()))
+    }
+
+    fn source(&self) -> &Location {
+        &self.key
+    }
+}
+
+/// Compute a time-based one-time password (RFC 6238) with a secret in the vault.
+///
+/// The `timestamp` is given in seconds since the unix epoch, the `time_step` in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Totp {
+    pub key: Location,
+
+    pub time_step: u64,
+
+    pub digits: u32,
+
+    pub hash_type: OtpHash,
+
+    pub timestamp: u64,
+}
+
+impl UseSecret for Totp {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let counter = otp::time_counter(self.timestamp, self.time_step).map_err(|e| e.to_string())?;
+        let mac = self.hash_type.hmac(&counter.to_be_bytes(), &guard.borrow());
+        otp::code(&mac, self.digits).map_err(|e| e.to_string().into())
     }
 
     fn source(&self) -> &Location {
@@ -616,6 +1748,55 @@ impl UseSecret for Hmac {
     }
 }
 
+/// Compute a counter-based one-time password (RFC 4226) with a secret in the vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hotp {
+    pub key: Location,
+
+    pub counter: u64,
+
+    pub digits: u32,
+
+    pub hash_type: OtpHash,
+}
+
+impl UseSecret for Hotp {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let mac = self.hash_type.hmac(&self.counter.to_be_bytes(), &guard.borrow());
+        otp::code(&mac, self.digits).map_err(|e| e.to_string().into())
+    }
+
+    fn source(&self) -> &Location {
+        &self.key
+    }
+}
+
+/// Store the secret of an `otpauth://totp` or `otpauth://hotp` key URI in the `output` location and return the other
+/// parameters of the URI, which are needed to compute the passwords with [`Totp`] or [`Hotp`].
+#[derive(GuardDebug, Clone, Serialize, Deserialize)]
+pub struct TotpImport {
+    pub uri: String,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+}
+
+impl GenerateSecret for TotpImport {
+    type Output = OtpAuth;
+
+    fn generate(self) -> Result<Products<Self::Output>, FatalProcedureError> {
+        let (auth, secret) = otp::parse_uri(&self.uri).map_err(|e| FatalProcedureError::from(e.to_string()))?;
+        Ok(Products { secret, output: auth })
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Hkdf {
     pub hash_type: Sha2Hash,
@@ -714,6 +1895,170 @@ impl GenerateSecret for Pbkdf2Hmac {
     }
 }
 
+/// Derive a key from a password with Argon2id.
+///
+/// The memory cost is given in KiB and is limited to [`ARGON2_MAX_MEMORY_COST`], the length of the key is limited to
+/// [`KDF_MAX_LENGTH`]. The salt must have a length of at least 8 bytes.
+#[derive(Clone, GuardDebug, Serialize, Deserialize)]
+pub struct Argon2id {
+    pub password: Vec<u8>,
+
+    pub salt: Vec<u8>,
+
+    pub memory_cost: u32,
+
+    pub time_cost: u32,
+
+    pub parallelism: u32,
+
+    /// Length of the derived key.
+    pub length: usize,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+}
+
+impl GenerateSecret for Argon2id {
+    type Output = ();
+
+    fn generate(self) -> Result<Products<Self::Output>, FatalProcedureError> {
+        let secret = argon2id(
+            &self.password,
+            &self.salt,
+            self.memory_cost,
+            self.time_cost,
+            self.parallelism,
+            self.length,
+        )?;
+        Ok(Products { secret, output: () })
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+}
+
+/// Derive a key with Argon2id from a password that is stored in the vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Argon2idDerive {
+    pub password: Location,
+
+    pub salt: Vec<u8>,
+
+    pub memory_cost: u32,
+
+    pub time_cost: u32,
+
+    pub parallelism: u32,
+
+    /// Length of the derived key.
+    pub length: usize,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+}
+
+impl DeriveSecret for Argon2idDerive {
+    type Output = ();
+
+    fn derive(self, guard: GuardedVec<u8>) -> Result<Products<()>, FatalProcedureError> {
+        let secret = argon2id(
+            &guard.borrow(),
+            &self.salt,
+            self.memory_cost,
+            self.time_cost,
+            self.parallelism,
+            self.length,
+        )?;
+        Ok(Products { secret, output: () })
+    }
+
+    fn source(&self) -> &Location {
+        &self.password
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+}
+
+/// Derive a key from a password with scrypt.
+///
+/// The CPU/memory cost is `2^log_n`, `r` is the block size and `p` the parallelism. The parameters are limited to
+/// [`SCRYPT_MAX_LOG_N`], [`SCRYPT_MAX_R`] and [`SCRYPT_MAX_P`], the length of the key to [`KDF_MAX_LENGTH`].
+#[derive(Clone, GuardDebug, Serialize, Deserialize)]
+pub struct Scrypt {
+    pub password: Vec<u8>,
+
+    pub salt: Vec<u8>,
+
+    pub log_n: u8,
+
+    pub r: u32,
+
+    pub p: u32,
+
+    /// Length of the derived key.
+    pub length: usize,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+}
+
+impl GenerateSecret for Scrypt {
+    type Output = ();
+
+    fn generate(self) -> Result<Products<Self::Output>, FatalProcedureError> {
+        let secret = scrypt(&self.password, &self.salt, self.log_n, self.r, self.p, self.length)?;
+        Ok(Products { secret, output: () })
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+}
+
+/// Derive a key with scrypt from a password that is stored in the vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScryptDerive {
+    pub password: Location,
+
+    pub salt: Vec<u8>,
+
+    pub log_n: u8,
+
+    pub r: u32,
+
+    pub p: u32,
+
+    /// Length of the derived key.
+    pub length: usize,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+}
+
+impl DeriveSecret for ScryptDerive {
+    type Output = ();
+
+    fn derive(self, guard: GuardedVec<u8>) -> Result<Products<()>, FatalProcedureError> {
+        let secret = scrypt(&guard.borrow(), &self.salt, self.log_n, self.r, self.p, self.length)?;
+        Ok(Products { secret, output: () })
+    }
+
+    fn source(&self) -> &Location {
+        &self.password
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AeadEncrypt {
     pub cipher: AeadCipher,
@@ -802,3 +2147,140 @@ impl UseSecret for AeadDecrypt {
         &self.key
     }
 }
+
+/// Encrypt the plaintext to the X25519 public key of a recipient, so that only the owner of the corresponding secret
+/// key can decrypt it with [`SealedBoxDecrypt`]. No secret of the vault is used.
+///
+/// The sealed box is encrypted with an ephemeral X25519 key, and XChaCha20Poly1305 with a key derived by HKDF-SHA256.
+/// It is [`SEALED_BOX_OVERHEAD`][super::SEALED_BOX_OVERHEAD] bytes longer than the plaintext.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedBoxEncrypt {
+    pub recipient_public_key: [u8; x25519::PUBLIC_KEY_LENGTH],
+
+    pub plaintext: Vec<u8>,
+}
+
+impl Procedure for SealedBoxEncrypt {
+    type Output = Vec<u8>;
+
+    fn execute<R: Runner>(self, _runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let recipient = x25519::PublicKey::from_bytes(self.recipient_public_key);
+        let sealed = sealed_box::seal(&recipient, &self.plaintext).map_err(FatalProcedureError::from)?;
+        Ok(sealed)
+    }
+}
+
+/// Decrypt a sealed box of [`SealedBoxEncrypt`] with the X25519 key at `private_key`.
+///
+/// If an `output` location is set, the plaintext is written into it as a new secret and an empty vector is
+/// returned, so that secrets can be imported without exposing them. Otherwise the plaintext is returned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedBoxDecrypt {
+    pub ciphertext: Vec<u8>,
+
+    pub private_key: Location,
+
+    pub output: Option<(Location, RecordHint)>,
+}
+
+impl Procedure for SealedBoxDecrypt {
+    type Output = Vec<u8>;
+
+    fn execute<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let ciphertext = self.ciphertext;
+        let open = |guard: GuardedVec<u8>| -> Result<Vec<u8>, FatalProcedureError> {
+            let sk = x25519_secret_key(guard)?;
+            Ok(sealed_box::open(&sk, &ciphertext)?)
+        };
+        match self.output {
+            Some((output, hint)) => {
+                let f = |guard| {
+                    open(guard).map(|secret| Products {
+                        secret,
+                        output: Vec::new(),
+                    })
+                };
+                Ok(runner.exec_proc(&self.private_key, &output, hint, f)?)
+            }
+            None => Ok(runner.get_guard(&self.private_key, open)?),
+        }
+    }
+}
+
+/// The key that a record is wrapped with by [`ExportWrapped`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WrappingKey {
+    /// A 32 byte AES-256 key wrap key in the vault, that is shared with the importing side.
+    Symmetric(Location),
+    /// The X25519 public key of the importing side.
+    Recipient([u8; x25519::PUBLIC_KEY_LENGTH]),
+}
+
+/// Export the record at `source` wrapped with the `wrapping_key`, so that it can be imported into the vault of
+/// another Stronghold with [`ImportWrapped`] without exposing the secret.
+///
+/// The wrapped record carries the [`RecordHint`] and the policy of the record after the use of the export. Records
+/// can be protected from being exported by denying this procedure or [`CopyRecord`] in their [`VaultFlags`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportWrapped {
+    pub source: Location,
+
+    pub wrapping_key: WrappingKey,
+}
+
+impl Procedure for ExportWrapped {
+    type Output = Vec<u8>;
+
+    fn execute<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        // an exported record can be imported into any vault, so it is treated as a copy of the record.
+        let (_, policy) = runner.get_metadata(&self.source)?;
+        if !policy.permits(ProcedureKind::CopyRecord.usage()) {
+            return Err(FatalProcedureError::from("the policy of the record denies copying it".to_owned()).into());
+        }
+        // the copy would be revoked on import.
+        if policy.uses == Some(1) {
+            return Err(FatalProcedureError::from("the record has no uses left to export".to_owned()).into());
+        }
+        let mut secret = runner.get_guard(&self.source, |guard| Ok(guard.borrow().to_vec()))?;
+        // the metadata is read after the use of the export is consumed, so that the copy does not gain a use.
+        let (hint, policy) = match runner.get_metadata(&self.source) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                secret.zeroize();
+                return Err(e.into());
+            }
+        };
+        let record = WrappedRecord { hint, policy, secret };
+        let wrapped = match self.wrapping_key {
+            WrappingKey::Symmetric(kek) => runner.get_guard(&kek, |guard| Ok(record.wrap_aes_kw(&guard.borrow())?))?,
+            WrappingKey::Recipient(pk) => record
+                .wrap_sealed_box(&x25519::PublicKey::from_bytes(pk))
+                .map_err(FatalProcedureError::from)?,
+        };
+        Ok(wrapped)
+    }
+}
+
+/// Import a record of [`ExportWrapped`] into the `target` location. The record is unwrapped with the AES-256 key
+/// wrap key or the X25519 secret key at `key`, depending on how it was wrapped, and is written with the hint and the
+/// policy that it was exported with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportWrapped {
+    pub wrapped: Vec<u8>,
+
+    pub key: Location,
+
+    pub target: Location,
+}
+
+impl Procedure for ImportWrapped {
+    type Output = ();
+
+    fn execute<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let wrapped = self.wrapped;
+        let mut record = runner.get_guard(&self.key, |guard| Ok(WrappedRecord::unwrap(&guard.borrow(), &wrapped)?))?;
+        let secret = std::mem::take(&mut record.secret);
+        runner.write_to_vault(&self.target, record.hint, secret, record.policy)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sealed boxes: anonymous public-key encryption to an X25519 key.
+//!
+//! A sealed box is encrypted with a fresh ephemeral X25519 key. The XChaCha20Poly1305 key and nonce are derived with
+//! HKDF-SHA256 from the shared secret of the ephemeral key and the recipient key, salted with both public keys. The
+//! box is encoded as `ephemeral public key || tag || ciphertext`.
+
+use crypto::{
+    ciphers::{
+        chacha::XChaCha20Poly1305,
+        traits::{Aead, Tag},
+    },
+    hashes::sha::Sha256,
+    keys::x25519,
+};
+use zeroize::Zeroize;
+
+/// Length of the overhead of a sealed box compared to its plaintext.
+pub const SEALED_BOX_OVERHEAD: usize = x25519::PUBLIC_KEY_LENGTH + XChaCha20Poly1305::TAG_LENGTH;
+
+const INFO: &[u8] = b"stronghold sealed box";
+
+/// Encrypts the plaintext to the public key of the recipient.
+pub fn seal(recipient: &x25519::PublicKey, plaintext: &[u8]) -> Result<Vec<u8>, crypto::Error> {
+    let ephemeral = x25519::SecretKey::generate()?;
+    let ephemeral_pk = ephemeral.public_key();
+    let (mut key, nonce) = derive_key(&ephemeral, recipient, &ephemeral_pk, recipient)?;
+
+    let mut ciphertext = vec![0; plaintext.len()];
+    let mut tag = Tag::<XChaCha20Poly1305>::default();
+    let res = XChaCha20Poly1305::try_encrypt(&key, &nonce, &[], plaintext, &mut ciphertext, &mut tag);
+    key.zeroize();
+    res?;
+
+    let mut sealed = Vec::with_capacity(SEALED_BOX_OVERHEAD + plaintext.len());
+    sealed.extend_from_slice(ephemeral_pk.as_slice());
+    sealed.extend(tag);
+    sealed.extend(ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypts a sealed box with the secret key of the recipient.
+pub fn open(sk: &x25519::SecretKey, sealed: &[u8]) -> Result<Vec<u8>, crypto::Error> {
+    if sealed.len() < SEALED_BOX_OVERHEAD {
+        return Err(crypto::Error::BufferSize {
+            name: "sealed box",
+            has: sealed.len(),
+            needs: SEALED_BOX_OVERHEAD,
+        });
+    }
+    let (ephemeral_pk, rest) = sealed.split_at(x25519::PUBLIC_KEY_LENGTH);
+    let (tag, ciphertext) = rest.split_at(XChaCha20Poly1305::TAG_LENGTH);
+    let ephemeral_pk = x25519::PublicKey::try_from_slice(ephemeral_pk)?;
+    let (mut key, nonce) = derive_key(sk, &ephemeral_pk, &ephemeral_pk, &sk.public_key())?;
+
+    let mut plaintext = vec![0; ciphertext.len()];
+    let res = XChaCha20Poly1305::try_decrypt(&key, &nonce, &[], &mut plaintext, ciphertext, tag);
+    key.zeroize();
+    res?;
+    Ok(plaintext)
+}
+
+/// Derives the key and nonce of the box from the shared secret of `sk` and `other`, salted with the ephemeral and the
+/// recipient public key.
+fn derive_key(
+    sk: &x25519::SecretKey,
+    other: &x25519::PublicKey,
+    ephemeral_pk: &x25519::PublicKey,
+    recipient_pk: &x25519::PublicKey,
+) -> Result<([u8; 32], [u8; 24]), crypto::Error> {
+    let mut shared = sk.diffie_hellman(other).to_bytes();
+    // a low order public key results in a shared secret that does not depend on the secret key.
+    if shared.iter().all(|b| *b == 0) {
+        return Err(crypto::Error::ConvertError {
+            from: "x25519 public key",
+            to: "shared secret",
+        });
+    }
+
+    let salt = [ephemeral_pk.as_slice(), recipient_pk.as_slice()].concat();
+    let mut okm = [0; 32 + 24];
+    hkdf::Hkdf::<Sha256>::new(Some(&salt), &shared)
+        .expand(INFO, &mut okm)
+        .expect("okm is the correct length");
+    shared.zeroize();
+
+    let mut key = [0; 32];
+    let mut nonce = [0; 24];
+    key.copy_from_slice(&okm[..32]);
+    nonce.copy_from_slice(&okm[32..]);
+    okm.zeroize();
+    Ok((key, nonce))
+}
@@ -0,0 +1,459 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Shamir's secret sharing of vault secrets.
+//!
+//! Secrets are split with the scheme of SLIP39: the secret is encrypted with a passphrase in a four round Feistel
+//! network, and split over GF(256) together with a digest share, so that combining shares of different secrets is
+//! detected. SLIP39 groups are not supported.
+//!
+//! Each share is encoded as a mnemonic sentence of a BIP39 wordlist, 11 bits per word. The sentences are therefore
+//! not interchangeable with the SLIP39 mnemonics of hardware wallets, which use a dedicated wordlist.
+
+use crypto::{
+    hashes::sha::{SHA256, SHA256_LEN},
+    keys::{bip39::wordlist::Wordlist, pbkdf::PBKDF2_HMAC_SHA256},
+    macs::hmac::HMAC_SHA256,
+    utils::rand::fill,
+};
+use thiserror::Error as DeriveError;
+use zeroize::Zeroize;
+
+/// Minimal length of a secret in bytes.
+pub const SHAMIR_MIN_SECRET_LENGTH: usize = 16;
+
+/// Maximal length of a secret in bytes.
+pub const SHAMIR_MAX_SECRET_LENGTH: usize = u8::MAX as usize;
+
+/// Maximal number of shares of a secret.
+pub const SHAMIR_MAX_SHARES: u8 = 16;
+
+/// Version of the share format.
+const VERSION: u8 = 0;
+
+/// Length of the header of a share: version, identifier, iteration exponent, index, threshold and secret length.
+const HEADER_LENGTH: usize = 7;
+
+/// Length of the checksum at the end of a share.
+const CHECKSUM_LENGTH: usize = 4;
+
+/// Length of the digest of the secret in the digest share.
+const DIGEST_LENGTH: usize = 4;
+
+/// Indexes of the digest and the secret in the polynomial.
+const DIGEST_INDEX: u8 = 254;
+const SECRET_INDEX: u8 = 255;
+
+/// Iterations of the PBKDF2 round function for the iteration exponent `0`.
+const BASE_ITERATION_COUNT: usize = 10000;
+const ROUND_COUNT: u8 = 4;
+
+/// Iteration exponent of new shares.
+const ITERATION_EXPONENT: u8 = 0;
+
+const WORD_BITS: usize = 11;
+
+#[derive(DeriveError, Debug, Clone, PartialEq, Eq)]
+pub enum ShamirError {
+    #[error("invalid threshold {threshold} for {shares} shares")]
+    InvalidThreshold { threshold: u8, shares: u8 },
+
+    #[error("secret has to be an even number of bytes between {min} and {max}, has {has}")]
+    InvalidSecretLength { has: usize, min: usize, max: usize },
+
+    #[error("no such word in wordlist: `{0}`")]
+    NoSuchWord(String),
+
+    #[error("invalid share: {0}")]
+    InvalidShare(&'static str),
+
+    #[error("shares do not belong to the same secret")]
+    MismatchedShares,
+
+    #[error("{threshold} shares are required, got {has}")]
+    InsufficientShares { threshold: u8, has: usize },
+
+    #[error("digest of the secret does not match")]
+    InvalidDigest,
+
+    #[error("{0}")]
+    Crypto(String),
+}
+
+impl From<crypto::Error> for ShamirError {
+    fn from(e: crypto::Error) -> Self {
+        ShamirError::Crypto(e.to_string())
+    }
+}
+
+/// A share of a secret.
+struct Share {
+    identifier: u16,
+    iteration_exponent: u8,
+    index: u8,
+    threshold: u8,
+    value: Vec<u8>,
+}
+
+impl Share {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LENGTH + self.value.len() + CHECKSUM_LENGTH);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.identifier.to_be_bytes());
+        bytes.push(self.iteration_exponent);
+        bytes.push(self.index);
+        bytes.push(self.threshold);
+        bytes.push(self.value.len() as u8);
+        bytes.extend_from_slice(&self.value);
+        bytes.extend_from_slice(&checksum(&bytes));
+        bytes
+    }
+
+    fn try_from_bytes(bytes: &[u8]) -> Result<Self, ShamirError> {
+        if bytes.len() < HEADER_LENGTH + CHECKSUM_LENGTH {
+            return Err(ShamirError::InvalidShare("too short"));
+        }
+        if bytes[0] != VERSION {
+            return Err(ShamirError::InvalidShare("unsupported version"));
+        }
+        let len = HEADER_LENGTH + bytes[6] as usize;
+        // the word encoding pads the share with less than one word of zero bits.
+        let (share, padding) = bytes.split_at(len.min(bytes.len()));
+        if padding.len() < CHECKSUM_LENGTH || padding[CHECKSUM_LENGTH..].iter().any(|b| *b != 0) {
+            return Err(ShamirError::InvalidShare("invalid length"));
+        }
+        if padding[..CHECKSUM_LENGTH] != checksum(share) {
+            return Err(ShamirError::InvalidShare("checksum mismatch"));
+        }
+        // a share only passes the checksum if it is well-formed, but crafted shares have to be rejected as well.
+        if share[5] == 0 || share[5] > SHAMIR_MAX_SHARES {
+            return Err(ShamirError::InvalidShare("invalid threshold"));
+        }
+        if share[4] >= SHAMIR_MAX_SHARES {
+            return Err(ShamirError::InvalidShare("invalid index"));
+        }
+        check_secret_length(share.len() - HEADER_LENGTH)?;
+        Ok(Share {
+            identifier: u16::from_be_bytes([share[1], share[2]]),
+            iteration_exponent: share[3],
+            index: share[4],
+            threshold: share[5],
+            value: share[HEADER_LENGTH..].to_vec(),
+        })
+    }
+
+    fn to_mnemonic(&self, wordlist: &Wordlist) -> String {
+        let mut bytes = self.to_bytes();
+        let words: Vec<&str> = bits_to_words(&bytes).into_iter().map(|i| wordlist.words[i]).collect();
+        bytes.zeroize();
+        words.join(wordlist.separator)
+    }
+
+    fn try_from_mnemonic(mnemonic: &str, wordlist: &Wordlist) -> Result<Self, ShamirError> {
+        let indexes = mnemonic
+            .split_whitespace()
+            .map(|w| {
+                wordlist
+                    .words
+                    .iter()
+                    .position(|v| *v == w)
+                    .ok_or_else(|| ShamirError::NoSuchWord(w.to_string()))
+            })
+            .collect::<Result<Vec<usize>, _>>()?;
+        let mut bytes = words_to_bits(&indexes);
+        let share = Self::try_from_bytes(&bytes);
+        bytes.zeroize();
+        share
+    }
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        self.value.zeroize()
+    }
+}
+
+/// Splits the secret into `shares` mnemonic sentences, of which `threshold` are required to recover the secret.
+pub fn split(
+    secret: &[u8],
+    threshold: u8,
+    shares: u8,
+    passphrase: &[u8],
+    wordlist: &Wordlist,
+) -> Result<Vec<String>, ShamirError> {
+    if threshold == 0 || threshold > shares || shares > SHAMIR_MAX_SHARES {
+        return Err(ShamirError::InvalidThreshold { threshold, shares });
+    }
+    check_secret_length(secret.len())?;
+
+    let mut identifier = [0; 2];
+    fill(&mut identifier)?;
+    let identifier = u16::from_be_bytes(identifier) & 0x7fff;
+
+    let mut encrypted = encrypt(secret, passphrase, ITERATION_EXPONENT, identifier)?;
+    let values = split_secret(threshold, shares, &encrypted);
+    encrypted.zeroize();
+
+    Ok(values?
+        .into_iter()
+        .map(|(index, value)| Share {
+            identifier,
+            iteration_exponent: ITERATION_EXPONENT,
+            index,
+            threshold,
+            value,
+        })
+        .map(|share| share.to_mnemonic(wordlist))
+        .collect())
+}
+
+/// Recovers the secret from the mnemonic sentences of its shares.
+pub fn combine<S: AsRef<str>>(mnemonics: &[S], passphrase: &[u8], wordlist: &Wordlist) -> Result<Vec<u8>, ShamirError> {
+    let shares = mnemonics
+        .iter()
+        .map(|m| Share::try_from_mnemonic(m.as_ref(), wordlist))
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = shares
+        .first()
+        .ok_or(ShamirError::InsufficientShares { threshold: 1, has: 0 })?;
+    for share in &shares {
+        if share.identifier != first.identifier
+            || share.iteration_exponent != first.iteration_exponent
+            || share.threshold != first.threshold
+            || share.value.len() != first.value.len()
+        {
+            return Err(ShamirError::MismatchedShares);
+        }
+        if shares.iter().filter(|s| s.index == share.index).count() > 1 {
+            return Err(ShamirError::InvalidShare("duplicate index"));
+        }
+    }
+    if shares.len() < first.threshold as usize {
+        return Err(ShamirError::InsufficientShares {
+            threshold: first.threshold,
+            has: shares.len(),
+        });
+    }
+
+    let points: Vec<(u8, &[u8])> = shares
+        .iter()
+        .take(first.threshold as usize)
+        .map(|s| (s.index, &s.value[..]))
+        .collect();
+    let mut encrypted = recover_secret(first.threshold, &points)?;
+    let secret = decrypt(&encrypted, passphrase, first.iteration_exponent, first.identifier);
+    encrypted.zeroize();
+    secret
+}
+
+fn check_secret_length(len: usize) -> Result<(), ShamirError> {
+    if !(SHAMIR_MIN_SECRET_LENGTH..=SHAMIR_MAX_SECRET_LENGTH).contains(&len) || !len.is_multiple_of(2) {
+        return Err(ShamirError::InvalidSecretLength {
+            has: len,
+            min: SHAMIR_MIN_SECRET_LENGTH,
+            max: SHAMIR_MAX_SECRET_LENGTH,
+        });
+    }
+    Ok(())
+}
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let mut hash = [0; SHA256_LEN];
+    SHA256(data, &mut hash);
+    let mut checksum = [0; CHECKSUM_LENGTH];
+    checksum.copy_from_slice(&hash[..CHECKSUM_LENGTH]);
+    checksum
+}
+
+fn digest(random: &[u8], secret: &[u8]) -> [u8; DIGEST_LENGTH] {
+    let mut mac = [0; SHA256_LEN];
+    HMAC_SHA256(secret, random, &mut mac);
+    let mut digest = [0; DIGEST_LENGTH];
+    digest.copy_from_slice(&mac[..DIGEST_LENGTH]);
+    digest
+}
+
+fn split_secret(threshold: u8, shares: u8, secret: &[u8]) -> Result<Vec<(u8, Vec<u8>)>, ShamirError> {
+    if threshold == 1 {
+        return Ok((0..shares).map(|i| (i, secret.to_vec())).collect());
+    }
+
+    let random_count = threshold as usize - 2;
+    let mut values: Vec<(u8, Vec<u8>)> = Vec::with_capacity(shares as usize);
+    for i in 0..random_count {
+        let mut value = vec![0; secret.len()];
+        fill(&mut value)?;
+        values.push((i as u8, value));
+    }
+
+    let mut digest_value = vec![0; secret.len()];
+    fill(&mut digest_value[DIGEST_LENGTH..])?;
+    let d = digest(&digest_value[DIGEST_LENGTH..], secret);
+    digest_value[..DIGEST_LENGTH].copy_from_slice(&d);
+
+    let mut points: Vec<(u8, &[u8])> = values.iter().map(|(i, v)| (*i, &v[..])).collect();
+    points.push((DIGEST_INDEX, &digest_value));
+    points.push((SECRET_INDEX, secret));
+    let interpolated = (random_count as u8..shares)
+        .map(|i| (i, interpolate(&points, i)))
+        .collect::<Vec<_>>();
+    digest_value.zeroize();
+
+    values.extend(interpolated);
+    Ok(values)
+}
+
+fn recover_secret(threshold: u8, points: &[(u8, &[u8])]) -> Result<Vec<u8>, ShamirError> {
+    if threshold == 1 {
+        return Ok(points[0].1.to_vec());
+    }
+    let secret = interpolate(points, SECRET_INDEX);
+    let mut digest_value = interpolate(points, DIGEST_INDEX);
+    let valid = digest(&digest_value[DIGEST_LENGTH..], &secret) == digest_value[..DIGEST_LENGTH];
+    digest_value.zeroize();
+    if !valid {
+        return Err(ShamirError::InvalidDigest);
+    }
+    Ok(secret)
+}
+
+/// Encrypts the secret with the four round Feistel network of SLIP39.
+fn encrypt(secret: &[u8], passphrase: &[u8], exponent: u8, identifier: u16) -> Result<Vec<u8>, ShamirError> {
+    feistel(secret, passphrase, exponent, identifier, 0..ROUND_COUNT)
+}
+
+fn decrypt(encrypted: &[u8], passphrase: &[u8], exponent: u8, identifier: u16) -> Result<Vec<u8>, ShamirError> {
+    feistel(encrypted, passphrase, exponent, identifier, (0..ROUND_COUNT).rev())
+}
+
+fn feistel<I: Iterator<Item = u8>>(
+    data: &[u8],
+    passphrase: &[u8],
+    exponent: u8,
+    identifier: u16,
+    rounds: I,
+) -> Result<Vec<u8>, ShamirError> {
+    let half = data.len() / 2;
+    let mut l = data[..half].to_vec();
+    let mut r = data[half..].to_vec();
+    let mut salt = b"shamir".to_vec();
+    salt.extend_from_slice(&identifier.to_be_bytes());
+    let iterations = (BASE_ITERATION_COUNT << exponent) / ROUND_COUNT as usize;
+
+    for i in rounds {
+        let mut password = vec![i];
+        password.extend_from_slice(passphrase);
+        let mut f = vec![0; half];
+        PBKDF2_HMAC_SHA256(&password, &[&salt[..], &r[..]].concat(), iterations, &mut f)?;
+        l.iter_mut().zip(f.iter()).for_each(|(l, f)| *l ^= f);
+        std::mem::swap(&mut l, &mut r);
+        password.zeroize();
+        f.zeroize();
+    }
+
+    let out = [&r[..], &l[..]].concat();
+    l.zeroize();
+    r.zeroize();
+    Ok(out)
+}
+
+/// Tables of the exponentials and logarithms in GF(256) with the polynomial `x^8 + x^4 + x^3 + x + 1`.
+fn tables() -> ([u8; 255], [u8; 256]) {
+    let mut exp = [0; 255];
+    let mut log = [0; 256];
+    let mut poly: u16 = 1;
+    for (i, e) in exp.iter_mut().enumerate() {
+        *e = poly as u8;
+        log[poly as usize] = i as u8;
+        poly = (poly << 1) ^ poly;
+        if poly & 0x100 != 0 {
+            poly ^= 0x11b;
+        }
+    }
+    (exp, log)
+}
+
+/// Evaluates the polynomial through the points at `x`.
+fn interpolate(points: &[(u8, &[u8])], x: u8) -> Vec<u8> {
+    if let Some((_, y)) = points.iter().find(|(i, _)| *i == x) {
+        return y.to_vec();
+    }
+    let (exp, log) = tables();
+    let log = |a: u8| log[a as usize] as i64;
+    let log_prod: i64 = points.iter().map(|(i, _)| log(i ^ x)).sum();
+
+    let mut result = vec![0; points[0].1.len()];
+    for (i, y) in points {
+        // the basis polynomial of the point is `prod_{j != i} (x - x_j) / (x_i - x_j)`; `log(0)` is never used as
+        // the term of `j == i` is excluded, but it is `0` in the table and does not affect the sum.
+        let log_denominator: i64 = points.iter().map(|(j, _)| log(i ^ j)).sum::<i64>() + log(i ^ x);
+        let log_basis = (log_prod - log_denominator).rem_euclid(255);
+        result.iter_mut().zip(y.iter()).for_each(|(r, y)| {
+            if *y != 0 {
+                *r ^= exp[((log(*y) + log_basis) % 255) as usize];
+            }
+        });
+    }
+    result
+}
+
+fn bits_to_words(bytes: &[u8]) -> Vec<usize> {
+    let word_count = (bytes.len() * 8).div_ceil(WORD_BITS);
+    (0..word_count)
+        .map(|w| {
+            (0..WORD_BITS).fold(0, |word, b| {
+                let bit = w * WORD_BITS + b;
+                let set = bytes.get(bit / 8).map_or(0, |byte| (byte >> (7 - bit % 8)) & 1);
+                (word << 1) | set as usize
+            })
+        })
+        .collect()
+}
+
+fn words_to_bits(words: &[usize]) -> Vec<u8> {
+    let mut bytes = vec![0; words.len() * WORD_BITS / 8];
+    for (w, word) in words.iter().enumerate() {
+        for b in 0..WORD_BITS {
+            let bit = w * WORD_BITS + b;
+            if bit / 8 < bytes.len() && (word >> (WORD_BITS - 1 - b)) & 1 == 1 {
+                bytes[bit / 8] |= 1 << (7 - bit % 8);
+            }
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crypto::keys::bip39::wordlist::ENGLISH;
+
+    fn crafted(threshold: u8, index: u8, len: usize) -> String {
+        Share {
+            identifier: 1,
+            iteration_exponent: ITERATION_EXPONENT,
+            index,
+            threshold,
+            value: vec![1; len],
+        }
+        .to_mnemonic(&ENGLISH)
+    }
+
+    #[test]
+    fn combine_rejects_crafted_shares() {
+        for (threshold, index, len) in [(0, 0, 16), (SHAMIR_MAX_SHARES + 1, 0, 16), (2, SHAMIR_MAX_SHARES, 16)] {
+            let shares = [crafted(threshold, index, len)];
+            assert!(matches!(
+                combine(&shares, &[], &ENGLISH),
+                Err(ShamirError::InvalidShare(_))
+            ));
+        }
+        for len in [0, 15, 17] {
+            let shares = [crafted(1, 0, len)];
+            assert!(matches!(
+                combine(&shares, &[], &ENGLISH),
+                Err(ShamirError::InvalidSecretLength { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,430 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! SLIP10 key derivation on the supported curves.
+//!
+//! Ed25519 derivation is provided by `iota-crypto`. The secp256k1 and NIST P-256 curves follow BIP32 and allow
+//! non-hardened derivation. Derived keys of all curves are stored in the same format: the 32 byte secret key
+//! followed by the 32 byte chain code.
+//!
+//! Chains are described by a [`DerivationPath`], which can be parsed from and printed as the BIP32 path notation,
+//! e.g. `m/44'/4218'/0'/0'/0'`.
+
+use crypto::{
+    hashes::sha::{SHA256, SHA256_LEN},
+    keys::slip10::{self, Chain, ChainCode, Segment},
+    macs::hmac::HMAC_SHA512,
+};
+use k256::elliptic_curve::{
+    ff::{Field, PrimeField},
+    point::PointCompression,
+    sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint},
+    AffinePoint, CurveArithmetic, FieldBytes, FieldBytesSize, Scalar, SecretKey,
+};
+use ripemd::{Digest, Ripemd160};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use thiserror::Error as DeriveError;
+use zeroize::Zeroize;
+
+/// Version bytes of a serialized BIP32 extended public key.
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+
+/// Elliptic curve on which a SLIP10 chain is derived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Curve {
+    /// Only supports hardened derivation.
+    Ed25519,
+    Secp256k1,
+    NistP256,
+}
+
+/// Ed25519 was the only curve before the curve of a derivation could be chosen.
+impl Default for Curve {
+    fn default() -> Self {
+        Curve::Ed25519
+    }
+}
+
+impl Curve {
+    fn seedkey(&self) -> &[u8] {
+        match self {
+            Curve::Ed25519 => b"ed25519 seed",
+            Curve::Secp256k1 => b"Bitcoin seed",
+            Curve::NistP256 => b"Nist256p1 seed",
+        }
+    }
+
+    fn is_valid_secret(&self, secret: &[u8]) -> bool {
+        match self {
+            Curve::Ed25519 => true,
+            Curve::Secp256k1 => SecretKey::<k256::Secp256k1>::from_slice(secret).is_ok(),
+            Curve::NistP256 => SecretKey::<p256::NistP256>::from_slice(secret).is_ok(),
+        }
+    }
+}
+
+/// BIP44 purpose of a [`DerivationPath`].
+const BIP44_PURPOSE: u32 = 44;
+
+#[derive(DeriveError, Debug, Clone, PartialEq, Eq)]
+pub enum DerivationPathError {
+    #[error("derivation path has to start with `m`")]
+    MissingMaster,
+
+    #[error("invalid segment `{0}` in derivation path")]
+    InvalidSegment(String),
+
+    #[error("index {0} of derivation path is out of range")]
+    IndexOutOfRange(u32),
+
+    #[error("curve {0:?} does not support non-hardened segment {1}")]
+    NonHardenedSegment(Curve, u32),
+}
+
+/// A SLIP10 derivation path, e.g. `m/44'/4218'/0'/0'/0'`.
+///
+/// The path is parsed from and displayed as the BIP32 notation: segments are separated by `/`, and hardened
+/// segments are marked with a trailing `'`, `h` or `H`. Each segment is stored as its child index, with
+/// [`DerivationPath::HARDEN_MASK`] set for hardened segments.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    /// Bit of a child index that marks a hardened segment.
+    pub const HARDEN_MASK: u32 = Segment::HARDEN_MASK;
+
+    /// Creates a new path from the child indexes of its segments.
+    pub fn new(indexes: Vec<u32>) -> Self {
+        Self(indexes)
+    }
+
+    /// Creates the BIP44 path `m/44'/coin_type'/account'` of an account.
+    pub fn bip44(coin_type: u32, account: u32) -> Result<Self, DerivationPathError> {
+        Self::default()
+            .hardened_child(BIP44_PURPOSE)?
+            .hardened_child(coin_type)?
+            .hardened_child(account)
+    }
+
+    /// Appends a non-hardened segment to the path.
+    pub fn child(mut self, index: u32) -> Result<Self, DerivationPathError> {
+        if index & Self::HARDEN_MASK != 0 {
+            return Err(DerivationPathError::IndexOutOfRange(index));
+        }
+        self.0.push(index);
+        Ok(self)
+    }
+
+    /// Appends a hardened segment to the path.
+    pub fn hardened_child(mut self, index: u32) -> Result<Self, DerivationPathError> {
+        if index & Self::HARDEN_MASK != 0 {
+            return Err(DerivationPathError::IndexOutOfRange(index));
+        }
+        self.0.push(index | Self::HARDEN_MASK);
+        Ok(self)
+    }
+
+    /// Appends the segments of `other` to the path.
+    pub fn join(&self, other: &DerivationPath) -> Self {
+        Self([&self.0[..], &other.0[..]].concat())
+    }
+
+    /// The child indexes of the segments of the path.
+    pub fn indexes(&self) -> &[u32] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if all segments of the path are hardened.
+    pub fn is_hardened(&self) -> bool {
+        self.0.iter().all(|i| i & Self::HARDEN_MASK != 0)
+    }
+
+    /// Check that the path can be derived on the curve.
+    pub fn validate(&self, curve: Curve) -> Result<(), DerivationPathError> {
+        match self.0.iter().find(|i| *i & Self::HARDEN_MASK == 0) {
+            Some(i) if curve == Curve::Ed25519 => Err(DerivationPathError::NonHardenedSegment(curve, *i)),
+            _ => Ok(()),
+        }
+    }
+
+    /// The BIP44 purpose of the path, if the first segment is hardened.
+    pub fn purpose(&self) -> Option<u32> {
+        self.hardened_index(0)
+    }
+
+    /// The BIP44 coin type of the path, if the path has the BIP44 purpose.
+    pub fn coin_type(&self) -> Option<u32> {
+        self.bip44_index(1)
+    }
+
+    /// The BIP44 account of the path, if the path has the BIP44 purpose.
+    pub fn account(&self) -> Option<u32> {
+        self.bip44_index(2)
+    }
+
+    fn bip44_index(&self, segment: usize) -> Option<u32> {
+        match self.purpose() {
+            Some(BIP44_PURPOSE) => self.hardened_index(segment),
+            _ => None,
+        }
+    }
+
+    fn hardened_index(&self, segment: usize) -> Option<u32> {
+        self.0
+            .get(segment)
+            .filter(|i| *i & Self::HARDEN_MASK != 0)
+            .map(|i| i & !Self::HARDEN_MASK)
+    }
+
+    fn parent(&self) -> Option<(Self, u32)> {
+        self.0.split_last().map(|(last, path)| (Self(path.to_vec()), *last))
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = DerivationPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = s.trim().split('/');
+        if !matches!(segments.next(), Some("m" | "M")) {
+            return Err(DerivationPathError::MissingMaster);
+        }
+        segments.try_fold(Self::default(), |path, segment| {
+            let invalid = || DerivationPathError::InvalidSegment(segment.to_string());
+            let (index, hardened) = match segment.strip_suffix(['\'', 'h', 'H']) {
+                Some(index) => (index, true),
+                None => (segment, false),
+            };
+            if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            let index = index.parse::<u32>().map_err(|_| invalid())?;
+            if hardened {
+                path.hardened_child(index)
+            } else {
+                path.child(index)
+            }
+        })
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m")?;
+        for i in &self.0 {
+            match i & Self::HARDEN_MASK {
+                0 => write!(f, "/{}", i)?,
+                _ => write!(f, "/{}'", i & !Self::HARDEN_MASK)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Chain> for DerivationPath {
+    fn from(chain: Chain) -> Self {
+        Self(chain.segments().iter().map(|s| u32::from_be_bytes(s.bs())).collect())
+    }
+}
+
+impl From<&DerivationPath> for Chain {
+    fn from(path: &DerivationPath) -> Self {
+        Chain::from_u32(path.0.iter().copied())
+    }
+}
+
+/// Length of an [`ExtendedKey`] in a record.
+pub const EXTENDED_KEY_LENGTH: usize = 64;
+
+/// An extended secret key: the secret key followed by the chain code.
+pub struct ExtendedKey([u8; EXTENDED_KEY_LENGTH]);
+
+impl ExtendedKey {
+    /// Parses an extended key from the content of a record.
+    pub fn try_from_slice(bs: &[u8]) -> Result<Self, crypto::Error> {
+        if bs.len() != EXTENDED_KEY_LENGTH {
+            return Err(crypto::Error::BufferSize {
+                name: "key",
+                has: bs.len(),
+                needs: EXTENDED_KEY_LENGTH,
+            });
+        }
+        let mut key = [0; EXTENDED_KEY_LENGTH];
+        key.copy_from_slice(bs);
+        Ok(Self(key))
+    }
+
+    /// Creates the master key of the curve from a seed.
+    pub fn from_seed(curve: Curve, seed: &[u8]) -> Self {
+        let mut i = [0; 64];
+        HMAC_SHA512(seed, curve.seedkey(), &mut i);
+        // SLIP10: retry with the output as input as long as the secret key is invalid for the curve.
+        while !curve.is_valid_secret(&i[..32]) {
+            let data = i;
+            HMAC_SHA512(&data, curve.seedkey(), &mut i);
+        }
+        Self(i)
+    }
+
+    pub fn secret_key(&self) -> [u8; 32] {
+        let mut sk = [0; 32];
+        sk.copy_from_slice(&self.0[..32]);
+        sk
+    }
+
+    pub fn chain_code(&self) -> ChainCode {
+        let mut cc = [0; 32];
+        cc.copy_from_slice(&self.0[32..]);
+        cc
+    }
+
+    /// Derives the child key at the end of the path.
+    pub fn derive(&self, curve: Curve, path: &DerivationPath) -> Result<Self, crypto::Error> {
+        path.indexes()
+            .iter()
+            .try_fold(Self(self.0), |key, index| key.child_key(curve, *index))
+    }
+
+    fn child_key(&self, curve: Curve, index: u32) -> Result<Self, crypto::Error> {
+        match curve {
+            Curve::Ed25519 => {
+                let key = slip10::Key::try_from(&self.0[..])?.child_key(&Segment::from_u32(index))?;
+                let key: Vec<u8> = key.into();
+                Self::try_from_slice(&key)
+            }
+            Curve::Secp256k1 => ecdsa_child_key::<k256::Secp256k1>(&self.0, index).map(Self),
+            Curve::NistP256 => ecdsa_child_key::<p256::NistP256>(&self.0, index).map(Self),
+        }
+    }
+
+    /// Gets the serialized public key of the curve: the compressed SEC1 point for the ECDSA curves, and the public
+    /// key prefixed with a zero byte for Ed25519.
+    pub fn public_key(&self, curve: Curve) -> Result<[u8; 33], crypto::Error> {
+        let mut pk = [0; 33];
+        match curve {
+            Curve::Ed25519 => {
+                let key = slip10::Key::try_from(&self.0[..])?;
+                pk[1..].copy_from_slice(&key.secret_key().public_key().to_bytes());
+            }
+            Curve::Secp256k1 => pk.copy_from_slice(&ecdsa_public_key::<k256::Secp256k1>(&self.0[..32])?),
+            Curve::NistP256 => pk.copy_from_slice(&ecdsa_public_key::<p256::NistP256>(&self.0[..32])?),
+        }
+        Ok(pk)
+    }
+}
+
+impl Zeroize for ExtendedKey {
+    fn zeroize(&mut self) {
+        self.0.zeroize()
+    }
+}
+
+impl Drop for ExtendedKey {
+    fn drop(&mut self) {
+        self.zeroize()
+    }
+}
+
+impl From<&ExtendedKey> for Vec<u8> {
+    fn from(key: &ExtendedKey) -> Self {
+        key.0.to_vec()
+    }
+}
+
+/// Serializes the BIP32 extended public key of the node at the end of `path`, derived from `parent`.
+pub fn xpub(curve: Curve, parent: &ExtendedKey, path: &DerivationPath) -> Result<String, crypto::Error> {
+    let (fingerprint, child_number) = match path.parent() {
+        Some((parent_path, index)) => {
+            let parent = parent.derive(curve, &parent_path)?;
+            let mut fingerprint = [0; 4];
+            fingerprint.copy_from_slice(&hash160(&parent.public_key(curve)?)[..4]);
+            (fingerprint, index.to_be_bytes())
+        }
+        None => ([0; 4], [0; 4]),
+    };
+    let node = parent.derive(curve, path)?;
+
+    let mut data = Vec::with_capacity(78);
+    data.extend_from_slice(&XPUB_VERSION);
+    data.push(path.len().min(u8::MAX as usize) as u8);
+    data.extend_from_slice(&fingerprint);
+    data.extend_from_slice(&child_number);
+    data.extend_from_slice(&node.chain_code());
+    data.extend_from_slice(&node.public_key(curve)?);
+
+    Ok(bs58::encode(data).with_check().into_string())
+}
+
+fn hash160(data: &[u8]) -> [u8; 20] {
+    let mut sha = [0; SHA256_LEN];
+    SHA256(data, &mut sha);
+    Ripemd160::digest(sha).into()
+}
+
+fn ecdsa_public_key<C>(secret: &[u8]) -> Result<Vec<u8>, crypto::Error>
+where
+    C: CurveArithmetic + PointCompression,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let sk = SecretKey::<C>::from_slice(secret).map_err(|_| invalid_key())?;
+    Ok(sk.public_key().to_encoded_point(true).as_bytes().to_vec())
+}
+
+/// BIP32 child key derivation, including the SLIP10 handling of invalid keys.
+fn ecdsa_child_key<C>(parent: &[u8; 64], index: u32) -> Result<[u8; 64], crypto::Error>
+where
+    C: CurveArithmetic + PointCompression,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let k_par = SecretKey::<C>::from_slice(&parent[..32]).map_err(|_| invalid_key())?;
+
+    let mut data = Vec::with_capacity(1 + 32 + 4);
+    if index & DerivationPath::HARDEN_MASK != 0 {
+        data.push(0);
+        data.extend_from_slice(&parent[..32]);
+    } else {
+        data.extend_from_slice(k_par.public_key().to_encoded_point(true).as_bytes());
+    }
+    data.extend_from_slice(&index.to_be_bytes());
+
+    loop {
+        let mut i = [0; 64];
+        HMAC_SHA512(&data, &parent[32..], &mut i);
+
+        let il: Option<Scalar<C>> = Scalar::<C>::from_repr(FieldBytes::<C>::clone_from_slice(&i[..32])).into();
+        if let Some(il) = il {
+            let child = il + *k_par.to_nonzero_scalar();
+            if !bool::from(child.is_zero()) {
+                i[..32].copy_from_slice(&child.to_repr());
+                data.zeroize();
+                return Ok(i);
+            }
+        }
+
+        // SLIP10: the key is invalid, continue with the right half of the output.
+        data.zeroize();
+        data.clear();
+        data.push(1);
+        data.extend_from_slice(&i[32..]);
+        data.extend_from_slice(&index.to_be_bytes());
+        i.zeroize();
+    }
+}
+
+fn invalid_key() -> crypto::Error {
+    crypto::Error::ConvertError {
+        from: "bytes",
+        to: "secret key",
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Assembly of signed tokens: JSON Web Signatures (RFC 7515) in compact serialization and PASETO v4.public tokens.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error as DeriveError;
+
+const PASETO_V4_PUBLIC_HEADER: &str = "v4.public.";
+
+/// Signature algorithm of a JWS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JwsAlgorithm {
+    /// Ed25519 (RFC 8037).
+    EdDSA,
+    /// ECDSA over secp256k1 with SHA-256 (RFC 8812).
+    ES256K,
+}
+
+impl JwsAlgorithm {
+    /// Value of the `alg` header parameter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            JwsAlgorithm::EdDSA => "EdDSA",
+            JwsAlgorithm::ES256K => "ES256K",
+        }
+    }
+}
+
+#[derive(DeriveError, Debug, Clone, PartialEq, Eq)]
+pub enum TokenError {
+    #[error("invalid JOSE header: {0}")]
+    InvalidHeader(String),
+
+    #[error("header parameter `alg` is `{has}`, but the key signs with `{needs}`")]
+    AlgorithmMismatch { has: String, needs: &'static str },
+}
+
+/// Encode with the URL safe base64 alphabet without padding.
+pub fn base64url(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+/// Get the JWS signing input `BASE64URL(header) || '.' || BASE64URL(payload)`.
+///
+/// The `alg` parameter and, if given, the `kid` parameter are set in the JSON object of the `header`. An empty
+/// header is treated as empty object.
+pub fn jws_signing_input(
+    header: &str,
+    alg: JwsAlgorithm,
+    kid: Option<&str>,
+    payload: &[u8],
+) -> Result<String, TokenError> {
+    let mut header: Map<String, Value> = match header.trim() {
+        "" => Map::new(),
+        header => serde_json::from_str(header).map_err(|e| TokenError::InvalidHeader(e.to_string()))?,
+    };
+    match header.get("alg") {
+        None => {}
+        Some(Value::String(has)) if has == alg.name() => {}
+        Some(has) => {
+            return Err(TokenError::AlgorithmMismatch {
+                has: has.to_string(),
+                needs: alg.name(),
+            })
+        }
+    }
+    header.insert("alg".into(), alg.name().into());
+    if let Some(kid) = kid {
+        header.insert("kid".into(), kid.into());
+    }
+    let header = serde_json::to_vec(&header).map_err(|e| TokenError::InvalidHeader(e.to_string()))?;
+    Ok(format!("{}.{}", base64url(&header), base64url(payload)))
+}
+
+/// Get the compact serialization of a JWS from its signing input and signature.
+pub fn compact_jws(signing_input: &str, signature: &[u8]) -> String {
+    format!("{}.{}", signing_input, base64url(signature))
+}
+
+/// Get the pre-authentication encoding of a PASETO v4.public token, which is signed with Ed25519.
+pub fn paseto_v4_public_pae(payload: &[u8], footer: &[u8], implicit_assertion: &[u8]) -> Vec<u8> {
+    let pieces: [&[u8]; 4] = [PASETO_V4_PUBLIC_HEADER.as_bytes(), payload, footer, implicit_assertion];
+    let mut pae = le64(pieces.len());
+    for piece in pieces {
+        pae.extend_from_slice(&le64(piece.len()));
+        pae.extend_from_slice(piece);
+    }
+    pae
+}
+
+/// Get a PASETO v4.public token from its payload, footer and signature.
+pub fn paseto_v4_public(payload: &[u8], footer: &[u8], signature: &[u8]) -> String {
+    let mut body = payload.to_vec();
+    body.extend_from_slice(signature);
+    let mut token = format!("{}{}", PASETO_V4_PUBLIC_HEADER, base64url(&body));
+    if !footer.is_empty() {
+        token.push('.');
+        token.push_str(&base64url(footer));
+    }
+    token
+}
+
+/// Little-endian encoding of a length, with the most significant bit cleared.
+fn le64(n: usize) -> Vec<u8> {
+    ((n as u64) & (u64::MAX >> 1)).to_le_bytes().to_vec()
+}
@@ -7,7 +7,7 @@ use crate::{
 };
 use engine::{
     runtime::GuardedVec,
-    vault::{RecordHint, VaultId},
+    vault::{RecordHint, RecordPolicy, VaultId},
 };
 use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, string::FromUtf8Error};
@@ -31,11 +31,23 @@ pub trait Runner {
     where
         F: FnOnce(GuardedVec<u8>) -> Result<Products<T>, FatalProcedureError>;
 
-    fn write_to_vault(&mut self, location1: &Location, hint: RecordHint, value: Vec<u8>) -> Result<(), RecordError>;
+    // Write `value` into `location1`. The `policy` is stored with the new record.
+    fn write_to_vault(
+        &mut self,
+        location1: &Location,
+        hint: RecordHint,
+        value: Vec<u8>,
+        policy: RecordPolicy,
+    ) -> Result<(), RecordError>;
+
+    // Get the hint and the policy of the record at `location`.
+    fn get_metadata(&mut self, location: &Location) -> Result<(RecordHint, RecordPolicy), RecordError>;
 
     fn revoke_data(&mut self, location: &Location) -> Result<(), RecordError>;
 
     fn garbage_collect(&mut self, vault_id: VaultId) -> bool;
+
+    fn rotate_vault_key(&mut self, vault_id: VaultId) -> Result<(), VaultError<FatalProcedureError>>;
 }
 
 /// Products of a procedure.
@@ -64,11 +76,17 @@ pub trait GenerateSecret: Sized {
 
     fn target(&self) -> (&Location, RecordHint);
 
+    // Policy of the record of the generated secret.
+    fn policy(&self) -> RecordPolicy {
+        RecordPolicy::default()
+    }
+
     fn exec<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
         let (target, hint) = self.target();
         let target = target.clone();
+        let policy = self.policy();
         let Products { output, secret } = self.generate()?;
-        runner.write_to_vault(&target, hint, secret)?;
+        runner.write_to_vault(&target, hint, secret, policy)?;
         Ok(output)
     }
 }
@@ -137,6 +155,13 @@ impl<const N: usize> From<[u8; N]> for ProcedureOutput {
     }
 }
 
+impl From<Vec<String>> for ProcedureOutput {
+    fn from(v: Vec<String>) -> Self {
+        // serializing a list of strings can not fail.
+        bincode::serialize(&v).expect("serialize strings").into()
+    }
+}
+
 impl From<ProcedureOutput> for () {
     fn from(_: ProcedureOutput) -> Self {}
 }
@@ -154,6 +179,13 @@ impl TryFrom<ProcedureOutput> for String {
     }
 }
 
+impl TryFrom<ProcedureOutput> for Vec<String> {
+    type Error = bincode::Error;
+    fn try_from(value: ProcedureOutput) -> Result<Self, Self::Error> {
+        bincode::deserialize(&value.0)
+    }
+}
+
 impl<const N: usize> TryFrom<ProcedureOutput> for [u8; N] {
     type Error = <[u8; N] as TryFrom<Vec<u8>>>::Error;
 
@@ -218,7 +250,7 @@ mod test {
     fn proc_io_vec() {
         let vec = random::bytestring(2048);
         let proc_io: ProcedureOutput = vec.clone().into();
-        let converted = Vec::try_from(proc_io).unwrap();
+        let converted = Vec::<u8>::try_from(proc_io).unwrap();
         assert_eq!(vec.len(), converted.len());
         assert_eq!(vec, converted);
     }
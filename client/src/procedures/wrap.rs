@@ -0,0 +1,156 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Wrapping of records for the transfer between vaults of independent Strongholds.
+//!
+//! A wrapped record contains the secret of the record together with its [`RecordHint`] and [`RecordPolicy`]. It is
+//! either wrapped with AES-256 key wrap (RFC 3394) under a symmetric key that both sides share, or sealed to the
+//! X25519 key of the recipient. The first byte of a wrapped record identifies the mode.
+
+use super::sealed_box;
+use crypto::{ciphers::aes_kw::Aes256Kw, keys::x25519};
+use engine::vault::{RecordHint, RecordPolicy};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, UNIX_EPOCH};
+use zeroize::Zeroize;
+
+const MODE_AES_KW: u8 = 1;
+const MODE_SEALED_BOX: u8 = 2;
+
+/// Content of a wrapped record.
+pub struct WrappedRecord {
+    pub hint: RecordHint,
+    pub policy: RecordPolicy,
+    pub secret: Vec<u8>,
+}
+
+/// Serialized form of a [`WrappedRecord`].
+#[derive(Serialize, Deserialize)]
+struct Payload {
+    hint: RecordHint,
+    denied: u64,
+    uses: Option<u64>,
+    // milliseconds since the unix epoch.
+    expires: Option<u64>,
+    secret: Vec<u8>,
+}
+
+impl Drop for Payload {
+    fn drop(&mut self) {
+        self.secret.zeroize()
+    }
+}
+
+impl Drop for WrappedRecord {
+    fn drop(&mut self) {
+        self.secret.zeroize()
+    }
+}
+
+impl WrappedRecord {
+    /// Serializes the record with a length prefix, zero padded to a multiple of the AES key wrap block size.
+    fn to_bytes(&self) -> Result<Vec<u8>, crypto::Error> {
+        let payload = Payload {
+            hint: self.hint,
+            denied: self.policy.denied,
+            uses: self.policy.uses,
+            expires: self
+                .policy
+                .expires
+                .map(|e| e.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)),
+            secret: self.secret.clone(),
+        };
+        let mut serialized = bincode::serialize(&payload).map_err(|_| serialize_error())?;
+        let mut bytes = Vec::with_capacity(4 + serialized.len() + Aes256Kw::BLOCK);
+        bytes.extend_from_slice(&(serialized.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&serialized);
+        serialized.zeroize();
+        let padded_len = (bytes.len().div_ceil(Aes256Kw::BLOCK) * Aes256Kw::BLOCK).max(2 * Aes256Kw::BLOCK);
+        bytes.resize(padded_len, 0);
+        Ok(bytes)
+    }
+
+    fn try_from_bytes(bytes: &[u8]) -> Result<Self, crypto::Error> {
+        let len = bytes
+            .get(..4)
+            .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+            .ok_or_else(serialize_error)?;
+        let serialized = bytes.get(4..4 + len).ok_or_else(serialize_error)?;
+        let mut payload: Payload = bincode::deserialize(serialized).map_err(|_| serialize_error())?;
+        Ok(WrappedRecord {
+            hint: payload.hint,
+            policy: RecordPolicy {
+                denied: payload.denied,
+                uses: payload.uses,
+                expires: payload.expires.map(|millis| UNIX_EPOCH + Duration::from_millis(millis)),
+            },
+            secret: std::mem::take(&mut payload.secret),
+        })
+    }
+
+    /// Wraps the record with an AES-256 key wrap key.
+    pub fn wrap_aes_kw(&self, kek: &[u8]) -> Result<Vec<u8>, crypto::Error> {
+        check_kek(kek)?;
+        let mut bytes = self.to_bytes()?;
+        let mut wrapped = vec![0; 1 + bytes.len() + Aes256Kw::BLOCK];
+        wrapped[0] = MODE_AES_KW;
+        let res = Aes256Kw::new(kek).wrap_key(&bytes, &mut wrapped[1..]);
+        bytes.zeroize();
+        res.map(|_| wrapped)
+    }
+
+    /// Seals the record to the X25519 public key of the recipient.
+    pub fn wrap_sealed_box(&self, recipient: &x25519::PublicKey) -> Result<Vec<u8>, crypto::Error> {
+        let mut bytes = self.to_bytes()?;
+        let sealed = sealed_box::seal(recipient, &bytes);
+        bytes.zeroize();
+        Ok([&[MODE_SEALED_BOX][..], &sealed?].concat())
+    }
+
+    /// Unwraps a wrapped record with the AES-256 key wrap key or the X25519 secret key that matches its mode.
+    pub fn unwrap(key: &[u8], wrapped: &[u8]) -> Result<Self, crypto::Error> {
+        let (mode, wrapped) = wrapped.split_first().ok_or_else(serialize_error)?;
+        let mut bytes = match *mode {
+            MODE_AES_KW => {
+                check_kek(key)?;
+                if wrapped.len() < 3 * Aes256Kw::BLOCK || !wrapped.len().is_multiple_of(Aes256Kw::BLOCK) {
+                    return Err(serialize_error());
+                }
+                let mut bytes = vec![0; wrapped.len() - Aes256Kw::BLOCK];
+                Aes256Kw::new(key).unwrap_key(wrapped, &mut bytes)?;
+                bytes
+            }
+            MODE_SEALED_BOX => {
+                let sk = x25519::SecretKey::try_from_slice(key)?;
+                sealed_box::open(&sk, wrapped)?
+            }
+            _ => {
+                return Err(crypto::Error::ConvertError {
+                    from: "wrapped record",
+                    to: "supported wrapping mode",
+                })
+            }
+        };
+        let record = Self::try_from_bytes(&bytes);
+        bytes.zeroize();
+        record
+    }
+}
+
+fn check_kek(kek: &[u8]) -> Result<(), crypto::Error> {
+    if kek.len() != Aes256Kw::KEY_LENGTH {
+        return Err(crypto::Error::BufferSize {
+            name: "key wrap key",
+            has: kek.len(),
+            needs: Aes256Kw::KEY_LENGTH,
+        });
+    }
+    Ok(())
+}
+
+fn serialize_error() -> crypto::Error {
+    crypto::Error::ConvertError {
+        from: "bytes",
+        to: "wrapped record",
+    }
+}
@@ -0,0 +1,474 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! X.509 v3 certificates (RFC 5280) and PKCS#10 certification requests (RFC 2986) with Ed25519 keys (RFC 8410).
+//!
+//! The structures are assembled here and signed by the caller, so that the secret key never leaves the vault.
+
+use crypto::{
+    hashes::{sha::Sha256, Digest},
+    signatures::ed25519,
+};
+use serde::{Deserialize, Serialize};
+use std::{net::IpAddr, str::FromStr, time::Duration};
+use thiserror::Error as DeriveError;
+use x509_cert::{
+    attr::Attribute,
+    certificate::{Certificate, TbsCertificate, Version},
+    der::{
+        asn1::{BitString, GeneralizedTime, Ia5String, OctetString, SetOfVec, UtcTime},
+        flagset::FlagSet,
+        oid::db::{rfc5280, rfc5912, rfc8410::ID_ED_25519},
+        pem::LineEnding,
+        DateTime, Decode, DecodePem, Encode, EncodePem,
+    },
+    ext::{
+        pkix::{
+            self,
+            name::{GeneralName, GeneralNames},
+            AuthorityKeyIdentifier, BasicConstraints, KeyUsages, SubjectKeyIdentifier,
+        },
+        AsExtension, Extension,
+    },
+    name::Name,
+    request::{CertReq, CertReqInfo, ExtensionReq},
+    serial_number::SerialNumber,
+    spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SubjectPublicKeyInfoOwned},
+    time::{Time, Validity},
+};
+
+/// Length of key identifiers, the leftmost 160 bits of the SHA-256 hash of the public key (RFC 7093).
+const KEY_IDENTIFIER_LENGTH: usize = 20;
+
+/// Alternative name of the subject of a certificate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubjectAltName {
+    Dns(String),
+    Ip(IpAddr),
+    Uri(String),
+    Email(String),
+}
+
+/// Purpose of the key of a certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyUsage {
+    DigitalSignature,
+    NonRepudiation,
+    KeyEncipherment,
+    DataEncipherment,
+    KeyAgreement,
+    KeyCertSign,
+    CrlSign,
+}
+
+/// Application of the key of a certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExtendedKeyUsage {
+    ServerAuth,
+    ClientAuth,
+    CodeSigning,
+    EmailProtection,
+    TimeStamping,
+    OcspSigning,
+}
+
+/// Encoding of certificates and certification requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CertificateEncoding {
+    Der,
+    Pem,
+}
+
+/// Validity and key usage of an issued certificate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CertificateProfile {
+    /// Start of the validity period in seconds since the unix epoch.
+    pub not_before: u64,
+
+    /// End of the validity period in seconds since the unix epoch.
+    pub not_after: u64,
+
+    pub key_usage: Vec<KeyUsage>,
+
+    pub extended_key_usage: Vec<ExtendedKeyUsage>,
+
+    /// The certificate is a CA certificate, whose key may sign other certificates.
+    pub is_ca: bool,
+}
+
+#[derive(DeriveError, Debug, Clone, PartialEq, Eq)]
+pub enum X509Error {
+    #[error("invalid distinguished name `{0}`")]
+    InvalidName(String),
+
+    #[error("invalid subject alternative name `{0}`")]
+    InvalidAltName(String),
+
+    #[error("validity period ends before it starts")]
+    InvalidValidity,
+
+    #[error("invalid certification request: {0}")]
+    InvalidRequest(String),
+
+    #[error("invalid CA certificate: {0}")]
+    InvalidCaCertificate(String),
+
+    #[error("unsupported signature algorithm {0}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("signature of the certification request is invalid")]
+    InvalidSignature,
+
+    #[error("CA certificate does not match the CA key")]
+    CaKeyMismatch,
+
+    #[error("encoding failed: {0}")]
+    Encoding(String),
+}
+
+/// Assemble a certification request for the Ed25519 `public_key` and sign it with `sign`.
+pub fn certification_request(
+    subject: &str,
+    subject_alt_names: &[SubjectAltName],
+    public_key: &[u8],
+    encoding: CertificateEncoding,
+    sign: impl FnOnce(&[u8]) -> Vec<u8>,
+) -> Result<Vec<u8>, X509Error> {
+    let subject = parse_name(subject)?;
+    let mut attributes = SetOfVec::new();
+    if !subject_alt_names.is_empty() {
+        let extension = alt_names(subject_alt_names)?
+            .to_extension(&subject, &[])
+            .map_err(encoding_error)?;
+        let attribute = Attribute::try_from(ExtensionReq(vec![extension])).map_err(encoding_error)?;
+        attributes.insert(attribute).map_err(encoding_error)?;
+    }
+    let info = CertReqInfo {
+        version: Default::default(),
+        subject,
+        public_key: ed25519_spki(public_key)?,
+        attributes,
+    };
+    let signature = sign(&info.to_der().map_err(encoding_error)?);
+    let request = CertReq {
+        info,
+        algorithm: ed25519_algorithm(),
+        signature: BitString::from_bytes(&signature).map_err(encoding_error)?,
+    };
+    encode(&request, encoding)
+}
+
+/// Assemble a self-signed certificate for the Ed25519 `public_key` and sign it with `sign`.
+pub fn self_signed_certificate(
+    subject: &str,
+    subject_alt_names: &[SubjectAltName],
+    profile: &CertificateProfile,
+    serial_number: &[u8],
+    public_key: &[u8],
+    encoding: CertificateEncoding,
+    sign: impl FnOnce(&[u8]) -> Vec<u8>,
+) -> Result<Vec<u8>, X509Error> {
+    let subject = parse_name(subject)?;
+    let mut extensions = Vec::new();
+    if !subject_alt_names.is_empty() {
+        let extension = alt_names(subject_alt_names)?
+            .to_extension(&subject, &[])
+            .map_err(encoding_error)?;
+        extensions.push(extension);
+    }
+    let issuer = Issuer {
+        name: subject.clone(),
+        key_identifier: key_identifier(public_key),
+    };
+    let tbs = tbs_certificate(
+        issuer,
+        subject,
+        ed25519_spki(public_key)?,
+        extensions,
+        profile,
+        serial_number,
+    )?;
+    sign_certificate(tbs, encoding, sign)
+}
+
+/// Issue a certificate for the subject, alternative names and public key of the certification `request`. The
+/// certificate is issued by the subject of `ca_certificate` and signed with `sign`, whose Ed25519 public key is
+/// `ca_public_key`.
+///
+/// Only requests of Ed25519 keys are accepted, because the signature of the request has to be verified.
+pub fn issue_certificate(
+    request: &[u8],
+    ca_certificate: &[u8],
+    ca_public_key: &[u8],
+    profile: &CertificateProfile,
+    serial_number: &[u8],
+    encoding: CertificateEncoding,
+    sign: impl FnOnce(&[u8]) -> Vec<u8>,
+) -> Result<Vec<u8>, X509Error> {
+    let request = decode::<CertReq>(request).map_err(|e| X509Error::InvalidRequest(e.to_string()))?;
+    verify_request(&request)?;
+    let ca_certificate =
+        decode::<Certificate>(ca_certificate).map_err(|e| X509Error::InvalidCaCertificate(e.to_string()))?;
+    let issuer = ca_issuer(ca_certificate.tbs_certificate, ca_public_key)?;
+
+    // only the alternative names of the requested extensions are taken over, the key usage is set by the CA.
+    let mut extensions = Vec::new();
+    for attribute in request.info.attributes.iter() {
+        if attribute.oid != rfc5912::ID_EXTENSION_REQ {
+            continue;
+        }
+        for value in attribute.values.iter() {
+            let requested: Vec<Extension> = value
+                .decode_as()
+                .map_err(|e| X509Error::InvalidRequest(e.to_string()))?;
+            extensions.extend(
+                requested
+                    .into_iter()
+                    .filter(|extension| extension.extn_id == rfc5280::ID_CE_SUBJECT_ALT_NAME),
+            );
+        }
+    }
+    let tbs = tbs_certificate(
+        issuer,
+        request.info.subject,
+        request.info.public_key,
+        extensions,
+        profile,
+        serial_number,
+    )?;
+    sign_certificate(tbs, encoding, sign)
+}
+
+struct Issuer {
+    name: Name,
+    key_identifier: Vec<u8>,
+}
+
+fn tbs_certificate(
+    issuer: Issuer,
+    subject: Name,
+    public_key: SubjectPublicKeyInfoOwned,
+    mut extensions: Vec<Extension>,
+    profile: &CertificateProfile,
+    serial_number: &[u8],
+) -> Result<TbsCertificate, X509Error> {
+    if profile.not_after < profile.not_before {
+        return Err(X509Error::InvalidValidity);
+    }
+    let validity = Validity {
+        not_before: time(profile.not_before)?,
+        not_after: time(profile.not_after)?,
+    };
+
+    let basic_constraints = BasicConstraints {
+        ca: profile.is_ca,
+        path_len_constraint: None,
+    };
+    extensions.push(to_extension(&basic_constraints, &subject)?);
+    if !profile.key_usage.is_empty() {
+        let flags = profile
+            .key_usage
+            .iter()
+            .fold(FlagSet::default(), |flags, usage| flags | key_usage_flag(*usage));
+        let key_usage = pkix::KeyUsage(flags);
+        extensions.push(to_extension(&key_usage, &subject)?);
+    }
+    if !profile.extended_key_usage.is_empty() {
+        let extended_key_usage = pkix::ExtendedKeyUsage(
+            profile
+                .extended_key_usage
+                .iter()
+                .map(|usage| extended_key_usage_oid(*usage))
+                .collect(),
+        );
+        extensions.push(to_extension(&extended_key_usage, &subject)?);
+    }
+    let subject_key_identifier = SubjectKeyIdentifier(
+        OctetString::new(key_identifier(public_key.subject_public_key.raw_bytes())).map_err(encoding_error)?,
+    );
+    extensions.push(to_extension(&subject_key_identifier, &subject)?);
+    let authority_key_identifier = AuthorityKeyIdentifier {
+        key_identifier: Some(OctetString::new(issuer.key_identifier).map_err(encoding_error)?),
+        ..Default::default()
+    };
+    extensions.push(to_extension(&authority_key_identifier, &subject)?);
+
+    Ok(TbsCertificate {
+        version: Version::V3,
+        serial_number: SerialNumber::new(serial_number).map_err(encoding_error)?,
+        signature: ed25519_algorithm(),
+        issuer: issuer.name,
+        validity,
+        subject,
+        subject_public_key_info: public_key,
+        issuer_unique_id: None,
+        subject_unique_id: None,
+        extensions: Some(extensions),
+    })
+}
+
+fn sign_certificate(
+    tbs_certificate: TbsCertificate,
+    encoding: CertificateEncoding,
+    sign: impl FnOnce(&[u8]) -> Vec<u8>,
+) -> Result<Vec<u8>, X509Error> {
+    let signature = sign(&tbs_certificate.to_der().map_err(encoding_error)?);
+    let certificate = Certificate {
+        tbs_certificate,
+        signature_algorithm: ed25519_algorithm(),
+        signature: BitString::from_bytes(&signature).map_err(encoding_error)?,
+    };
+    encode(&certificate, encoding)
+}
+
+fn verify_request(request: &CertReq) -> Result<(), X509Error> {
+    if request.algorithm.oid != ID_ED_25519 || request.info.public_key.algorithm.oid != ID_ED_25519 {
+        return Err(X509Error::UnsupportedAlgorithm(request.algorithm.oid.to_string()));
+    }
+    let public_key = request
+        .info
+        .public_key
+        .subject_public_key
+        .as_bytes()
+        .and_then(|bytes| ed25519::PublicKey::try_from_bytes(bytes.try_into().ok()?).ok())
+        .ok_or_else(|| X509Error::InvalidRequest("invalid Ed25519 public key".into()))?;
+    let signature = request
+        .signature
+        .as_bytes()
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(ed25519::Signature::from_bytes)
+        .ok_or(X509Error::InvalidSignature)?;
+    let info = request.info.to_der().map_err(encoding_error)?;
+    if public_key.verify(&signature, &info) {
+        Ok(())
+    } else {
+        Err(X509Error::InvalidSignature)
+    }
+}
+
+fn ca_issuer(ca_certificate: TbsCertificate, ca_public_key: &[u8]) -> Result<Issuer, X509Error> {
+    let invalid = |e: &dyn std::fmt::Display| X509Error::InvalidCaCertificate(e.to_string());
+    if ca_certificate.subject_public_key_info.algorithm.oid != ID_ED_25519
+        || ca_certificate.subject_public_key_info.subject_public_key.raw_bytes() != ca_public_key
+    {
+        return Err(X509Error::CaKeyMismatch);
+    }
+    match ca_certificate.get::<BasicConstraints>().map_err(|e| invalid(&e))? {
+        Some((_, constraints)) if constraints.ca => {}
+        _ => return Err(invalid(&"not a CA certificate")),
+    }
+    let key_identifier = match ca_certificate.get::<SubjectKeyIdentifier>().map_err(|e| invalid(&e))? {
+        Some((_, identifier)) => identifier.0.into_bytes(),
+        None => key_identifier(ca_public_key),
+    };
+    Ok(Issuer {
+        name: ca_certificate.subject,
+        key_identifier,
+    })
+}
+
+fn alt_names(names: &[SubjectAltName]) -> Result<pkix::SubjectAltName, X509Error> {
+    let names = names
+        .iter()
+        .map(|name| {
+            let invalid = |_| X509Error::InvalidAltName(format!("{:?}", name));
+            Ok(match name {
+                SubjectAltName::Dns(dns) => GeneralName::DnsName(Ia5String::new(dns).map_err(invalid)?),
+                SubjectAltName::Uri(uri) => {
+                    GeneralName::UniformResourceIdentifier(Ia5String::new(uri).map_err(invalid)?)
+                }
+                SubjectAltName::Email(email) => GeneralName::Rfc822Name(Ia5String::new(email).map_err(invalid)?),
+                SubjectAltName::Ip(IpAddr::V4(ip)) => {
+                    GeneralName::IpAddress(OctetString::new(ip.octets()).map_err(invalid)?)
+                }
+                SubjectAltName::Ip(IpAddr::V6(ip)) => {
+                    GeneralName::IpAddress(OctetString::new(ip.octets()).map_err(invalid)?)
+                }
+            })
+        })
+        .collect::<Result<GeneralNames, X509Error>>()?;
+    Ok(pkix::SubjectAltName(names))
+}
+
+fn key_usage_flag(usage: KeyUsage) -> KeyUsages {
+    match usage {
+        KeyUsage::DigitalSignature => KeyUsages::DigitalSignature,
+        KeyUsage::NonRepudiation => KeyUsages::NonRepudiation,
+        KeyUsage::KeyEncipherment => KeyUsages::KeyEncipherment,
+        KeyUsage::DataEncipherment => KeyUsages::DataEncipherment,
+        KeyUsage::KeyAgreement => KeyUsages::KeyAgreement,
+        KeyUsage::KeyCertSign => KeyUsages::KeyCertSign,
+        KeyUsage::CrlSign => KeyUsages::CRLSign,
+    }
+}
+
+fn extended_key_usage_oid(usage: ExtendedKeyUsage) -> ObjectIdentifier {
+    match usage {
+        ExtendedKeyUsage::ServerAuth => rfc5280::ID_KP_SERVER_AUTH,
+        ExtendedKeyUsage::ClientAuth => rfc5280::ID_KP_CLIENT_AUTH,
+        ExtendedKeyUsage::CodeSigning => rfc5280::ID_KP_CODE_SIGNING,
+        ExtendedKeyUsage::EmailProtection => rfc5280::ID_KP_EMAIL_PROTECTION,
+        ExtendedKeyUsage::TimeStamping => rfc5280::ID_KP_TIME_STAMPING,
+        ExtendedKeyUsage::OcspSigning => rfc5280::ID_KP_OCSP_SIGNING,
+    }
+}
+
+/// Times until 2049 are encoded as UTCTime, later ones as GeneralizedTime (RFC 5280 4.1.2.5).
+fn time(timestamp: u64) -> Result<Time, X509Error> {
+    let date_time = DateTime::from_unix_duration(Duration::from_secs(timestamp)).map_err(encoding_error)?;
+    if date_time.year() <= UtcTime::MAX_YEAR {
+        UtcTime::from_date_time(date_time).map(Time::UtcTime)
+    } else {
+        Ok(Time::GeneralTime(GeneralizedTime::from_date_time(date_time)))
+    }
+    .map_err(encoding_error)
+}
+
+fn parse_name(name: &str) -> Result<Name, X509Error> {
+    if name.is_empty() {
+        return Ok(Name::default());
+    }
+    Name::from_str(name).map_err(|_| X509Error::InvalidName(name.to_string()))
+}
+
+fn key_identifier(public_key: &[u8]) -> Vec<u8> {
+    Sha256::digest(public_key)[..KEY_IDENTIFIER_LENGTH].to_vec()
+}
+
+fn ed25519_algorithm() -> AlgorithmIdentifierOwned {
+    AlgorithmIdentifierOwned {
+        oid: ID_ED_25519,
+        parameters: None,
+    }
+}
+
+fn ed25519_spki(public_key: &[u8]) -> Result<SubjectPublicKeyInfoOwned, X509Error> {
+    Ok(SubjectPublicKeyInfoOwned {
+        algorithm: ed25519_algorithm(),
+        subject_public_key: BitString::from_bytes(public_key).map_err(encoding_error)?,
+    })
+}
+
+fn to_extension(extension: &impl AsExtension, subject: &Name) -> Result<Extension, X509Error> {
+    extension.to_extension(subject, &[]).map_err(encoding_error)
+}
+
+fn encode<T: Encode + EncodePem>(value: &T, encoding: CertificateEncoding) -> Result<Vec<u8>, X509Error> {
+    match encoding {
+        CertificateEncoding::Der => value.to_der(),
+        CertificateEncoding::Pem => value.to_pem(LineEnding::LF).map(String::into_bytes),
+    }
+    .map_err(encoding_error)
+}
+
+/// Decode PEM or DER.
+fn decode<T: for<'a> Decode<'a> + DecodePem>(data: &[u8]) -> x509_cert::der::Result<T> {
+    match std::str::from_utf8(data) {
+        Ok(pem) if pem.trim_start().starts_with("-----BEGIN ") => T::from_pem(pem),
+        _ => T::from_der(data),
+    }
+}
+
+fn encoding_error(e: impl std::fmt::Display) -> X509Error {
+    X509Error::Encoding(e.to_string())
+}
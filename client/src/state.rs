@@ -1,8 +1,12 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod counter_index;
 pub mod key_store;
 #[cfg(feature = "p2p")]
 pub mod p2p;
 pub mod secure;
 pub mod snapshot;
+#[cfg(all(unix, feature = "ssh-agent"))]
+pub mod ssh_agent;
+pub mod webauthn;
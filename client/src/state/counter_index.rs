@@ -0,0 +1,101 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use engine::vault::RecordId;
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Index of the counter records in a vault.  Maps each [`RecordId`] to its counter value and keeps track of the head
+/// of the version chain, so that lookups and "next counter" queries do not have to re-derive the record ids.  The
+/// versions of the chain are linked through the [`RecordAttributes`] of their records.
+///
+/// The index is stored as a record inside of its vault, so that it is encrypted with the key of the vault and
+/// persisted with the snapshot.
+///
+/// [`RecordAttributes`]: engine::vault::RecordAttributes
+#[derive(Debug, Clone, Default)]
+pub struct CounterIndex {
+    records: BTreeMap<usize, RecordId>,
+    counters: HashMap<RecordId, usize>,
+    // latest version of the chain.
+    head: Option<usize>,
+    // next unused counter. Counters are not reused, even if their record was revoked.
+    next: usize,
+}
+
+impl CounterIndex {
+    /// Creates a new [`CounterIndex`] from the counter records of a vault. The head of the chain is the last of the
+    /// consecutive counters starting at 0.
+    pub fn from_records(records: BTreeMap<usize, RecordId>) -> Self {
+        let mut index = Self::default();
+        records.into_iter().for_each(|(ctr, rid)| {
+            index.insert(ctr, rid);
+        });
+        let head = (0..).take_while(|ctr| index.records.contains_key(ctr)).last();
+        index.head = head;
+        index
+    }
+
+    /// Deserializes the index from the content of its record.
+    pub fn from_bytes(bytes: &[u8]) -> bincode::Result<Self> {
+        let (records, head, next): (BTreeMap<usize, RecordId>, Option<usize>, usize) = bincode::deserialize(bytes)?;
+        let counters = records.iter().map(|(ctr, rid)| (*rid, *ctr)).collect();
+        Ok(Self {
+            records,
+            counters,
+            head,
+            next,
+        })
+    }
+
+    /// Serializes the index into the content of its record.
+    pub fn to_bytes(&self) -> bincode::Result<Vec<u8>> {
+        bincode::serialize(&(&self.records, self.head, self.next))
+    }
+
+    /// Adds a counter record to the index, without changing the head of the chain.  Returns `false` if the record
+    /// was already indexed.
+    pub fn insert(&mut self, ctr: usize, rid: RecordId) -> bool {
+        if self.records.insert(ctr, rid) == Some(rid) {
+            return false;
+        }
+        self.counters.insert(rid, ctr);
+        // records are never written at the last counter, see `SecureClient::resolve_write`.
+        if let Some(next) = ctr.checked_add(1) {
+            self.next = self.next.max(next);
+        }
+        true
+    }
+
+    /// Removes a counter record from the index.  Returns the counter of the record, or [`None`] if it was not indexed.
+    pub fn remove(&mut self, rid: RecordId) -> Option<usize> {
+        let ctr = self.counters.remove(&rid)?;
+        self.records.remove(&ctr);
+        Some(ctr)
+    }
+
+    /// Gets the counter of a record.
+    pub fn counter(&self, rid: RecordId) -> Option<usize> {
+        self.counters.get(&rid).copied()
+    }
+
+    /// Gets the record of a counter.
+    pub fn record(&self, ctr: usize) -> Option<RecordId> {
+        self.records.get(&ctr).copied()
+    }
+
+    /// Gets the counter of the latest version in the chain, or [`None`] if the chain is empty.
+    pub fn latest(&self) -> Option<usize> {
+        self.head
+    }
+
+    /// Sets the latest version of the chain.
+    pub fn set_latest(&mut self, head: Option<usize>) {
+        self.head = head;
+    }
+
+    /// Gets the counter of the next version in the chain.
+    pub fn next(&self) -> usize {
+        self.next
+    }
+}
@@ -38,6 +38,24 @@ impl KeyStore {
         self.store.entry(id).or_insert(key)
     }
 
+    /// Replaces the key of the vault with a freshly generated one. `f` receives the old and the new key and has to
+    /// re-encrypt the vault. The new key is only stored if `f` succeeds, otherwise the old key is kept. Returns [`None`]
+    /// if there is no key for the vault.
+    pub fn rotate_key<E, F>(&mut self, id: VaultId, f: F) -> Option<Result<(), E>>
+    where
+        F: FnOnce(&Key<Provider>, &Key<Provider>) -> Result<(), E>,
+    {
+        let old_key = self.store.get_mut(&id)?;
+        let new_key = Key::random();
+
+        let res = f(old_key, &new_key);
+        if res.is_ok() {
+            *old_key = new_key;
+        }
+
+        Some(res)
+    }
+
     /// Rebuilds the [`KeyStore`] while throwing out any existing [`VaultId`], [`Key<Provider>`] pairs.  Accepts a
     /// [`Vec<Key<Provider>>`] and returns then a [`Vec<VaultId>`]; primarily used to repopulate the state from a
     /// snapshot.
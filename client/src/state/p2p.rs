@@ -11,7 +11,7 @@ use crate::{
     },
     enum_from_inner,
     procedures::{self, ProcedureError, ProcedureOutput, StrongholdProcedure},
-    Location, RecordHint, RecordId,
+    Location, RecordHint, RecordId, VaultFlags,
 };
 use actix::prelude::*;
 use futures::{
@@ -708,18 +708,20 @@ impl FwRequest<ShRequest> for AccessRequest {
                     StrongholdProcedure::RevokeData(procedures::RevokeData { location, .. }) => vec![Access::Write {
                         vault_path: location.vault_path().to_vec(),
                     }],
-                    StrongholdProcedure::GarbageCollect(procedures::GarbageCollect { vault_path }) => {
+                    StrongholdProcedure::GarbageCollect(procedures::GarbageCollect { vault_path })
+                    | StrongholdProcedure::RotateVaultKey(procedures::RotateVaultKey { vault_path }) => {
                         vec![Access::Write {
                             vault_path: vault_path.clone(),
                         }]
                     }
                     proc => {
-                        let mut access = Vec::new();
-                        if let Some(input) = proc.input() {
-                            access.push(Access::Use {
+                        let mut access: Vec<Access> = proc
+                            .inputs()
+                            .into_iter()
+                            .map(|input| Access::Use {
                                 vault_path: input.vault_path().to_vec(),
-                            });
-                        }
+                            })
+                            .collect();
                         if let Some(output) = proc.output() {
                             access.push(Access::Write {
                                 vault_path: output.vault_path().to_vec(),
@@ -743,6 +745,7 @@ pub struct WriteToRemoteVault {
     pub location: Location,
     pub payload: Vec<u8>,
     pub hint: RecordHint,
+    pub flags: Vec<VaultFlags>,
 }
 
 impl From<WriteToRemoteVault> for WriteToVault {
@@ -751,11 +754,13 @@ impl From<WriteToRemoteVault> for WriteToVault {
             location,
             payload,
             hint,
+            flags,
         } = t;
         WriteToVault {
             location,
             payload,
             hint,
+            flags,
         }
     }
 }
@@ -766,11 +771,13 @@ impl From<WriteToVault> for WriteToRemoteVault {
             location,
             payload,
             hint,
+            flags,
         } = t;
         WriteToRemoteVault {
             location,
             payload,
             hint,
+            flags,
         }
     }
 }
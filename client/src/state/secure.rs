@@ -6,17 +6,20 @@
 use crate::{
     actors::{RecordError, VaultError},
     internals,
-    procedures::{FatalProcedureError, Products, Runner},
-    state::key_store::KeyStore,
+    procedures::{FatalProcedureError, ProcedureKind, Products, Runner},
+    state::{counter_index::CounterIndex, key_store::KeyStore},
     utils::LoadFromPath,
     Location,
 };
 use engine::{
     runtime::GuardedVec,
     store::Cache,
-    vault::{ClientId, DbView, RecordHint, RecordId, VaultId},
+    vault::{ClientId, DbView, Key, RecordAttributes, RecordHint, RecordId, RecordPolicy, VaultId},
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    time::Duration,
 };
-use std::time::Duration;
 
 /// Cache type definition
 pub type Store = Cache<Vec<u8>, Vec<u8>>;
@@ -30,8 +33,18 @@ pub struct SecureClient {
     pub client_id: ClientId,
     // Contains the Record Ids for the most recent Record in each vault.
     pub store: Store,
+    // Cached counter indexes of the vaults.
+    counters: HashMap<VaultId, CounterIndex>,
+    // The kind of the procedure that is currently executed.
+    pub(crate) procedure: Option<ProcedureKind>,
 }
 
+/// Hint of the record that holds the counter index of a vault.
+const COUNTER_INDEX_HINT: &[u8] = b"counter_index";
+
+/// Highest counter that is derived when indexing a vault that was written before the index existed.
+const MAX_LEGACY_COUNTER: usize = 32_000_000;
+
 impl SecureClient {
     /// Creates a new Client given a `ClientID` and `ChannelRef<SHResults>`
     pub fn new(client_id: ClientId) -> Self {
@@ -42,6 +55,8 @@ impl SecureClient {
             store,
             keystore: KeyStore::new(),
             db: DbView::new(),
+            counters: HashMap::new(),
+            procedure: None,
         }
     }
 
@@ -78,7 +93,9 @@ impl SecureClient {
         self.store = store;
     }
 
-    /// Resolves a location to a `VaultId` and a `RecordId`
+    /// Resolves a location to a `VaultId` and a `RecordId`. A counter location without an index is resolved to the
+    /// first record of the version chain, use [`SecureClient::resolve_read`] or [`SecureClient::resolve_write`] to
+    /// resolve the head of the chain.
     pub fn resolve_location<L: AsRef<Location>>(l: L) -> (VaultId, RecordId) {
         match l.as_ref() {
             Location::Generic {
@@ -91,13 +108,358 @@ impl SecureClient {
             }
             Location::Counter { vault_path, counter } => {
                 let vid = Self::derive_vault_id(vault_path);
-                let rid = Self::derive_record_id(vault_path, *counter);
+                let rid = Self::derive_record_id(vault_path, counter.unwrap_or(0));
 
                 (vid, rid)
             }
         }
     }
 
+    /// Resolves a location that is read from. A counter location without an index refers to the latest version in the
+    /// chain.
+    pub fn resolve_read<L: AsRef<Location>>(&mut self, l: L) -> Result<(VaultId, RecordId), RecordError> {
+        match l.as_ref() {
+            Location::Counter {
+                vault_path,
+                counter: None,
+            } => {
+                let ctr = self.latest_version(vault_path)?.unwrap_or(0);
+                Ok((
+                    Self::derive_vault_id(vault_path),
+                    Self::derive_record_id(vault_path, ctr),
+                ))
+            }
+            l => Ok(Self::resolve_location(l)),
+        }
+    }
+
+    /// Resolves a location that is written to. A counter location without an index refers to the next version in the
+    /// chain. Fails if the counter is the last one, since no version could follow it.
+    pub fn resolve_write<L: AsRef<Location>>(&mut self, l: L) -> Result<(VaultId, RecordId), RecordError> {
+        match l.as_ref() {
+            Location::Counter { vault_path, counter } => {
+                let ctr = match counter {
+                    Some(ctr) => *ctr,
+                    None => self.next_version(vault_path)?,
+                };
+                ctr.checked_add(1).ok_or(RecordError::CounterOverflow(ctr))?;
+                Ok((
+                    Self::derive_vault_id(vault_path),
+                    Self::derive_record_id(vault_path, ctr),
+                ))
+            }
+            l => Ok(Self::resolve_location(l)),
+        }
+    }
+
+    /// Lists the versions of the counter chain in a vault, ordered from the first to the latest version. The chain
+    /// starts at the head of the vault and follows the link of each version to the previous version.
+    pub fn list_versions<P: AsRef<Vec<u8>>>(
+        &mut self,
+        vault_path: P,
+    ) -> Result<Vec<(usize, RecordId, RecordHint)>, RecordError> {
+        let vault_path = vault_path.as_ref();
+        let versions = self.versions(vault_path)?;
+        let vault_id = Self::derive_vault_id(vault_path);
+        let key = match self.keystore.take_key(vault_id) {
+            Some(key) => key,
+            None => return Ok(Vec::new()),
+        };
+
+        let list = versions
+            .into_iter()
+            .filter_map(|(ctr, rid)| self.db.get_hint(&key, vault_id, rid).ok().map(|hint| (ctr, rid, hint)))
+            .collect();
+        self.keystore.insert_key(vault_id, key);
+        Ok(list)
+    }
+
+    /// Collects the versions of the counter chain in a vault, ordered from the first to the latest version. Only the
+    /// transactions of the versions are decrypted. Records that were written before versions were linked are linked
+    /// to the record of the previous counter.
+    fn versions(&mut self, vault_path: &Vec<u8>) -> Result<Vec<(usize, RecordId)>, RecordError> {
+        let vault_id = Self::derive_vault_id(vault_path);
+        let index = self.counter_index(vault_path)?.clone();
+        let key = match self.keystore.take_key(vault_id) {
+            Some(key) => key,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut versions = Vec::new();
+        let mut next = index.latest().and_then(|ctr| Some((ctr, index.record(ctr)?)));
+        while let Some((ctr, rid)) = next {
+            let prev = match self.db.get_attributes(&key, vault_id, rid) {
+                Ok(Some(attributes)) => attributes.prev.and_then(|prev| index.counter(prev)),
+                Ok(None) => ctr.checked_sub(1),
+                Err(_) => break,
+            };
+            versions.push((ctr, rid));
+            // links always point to an earlier counter, which ensures that the walk terminates.
+            next = prev
+                .filter(|prev| *prev < ctr)
+                .and_then(|prev| Some((prev, index.record(prev)?)));
+        }
+        self.keystore.insert_key(vault_id, key);
+
+        versions.reverse();
+        Ok(versions)
+    }
+
+    /// Lists the [`RecordId`] and [`RecordHint`] values of a vault together with the counter of each counter record.
+    pub fn list_hints_ids_and_counters<P: AsRef<Vec<u8>>>(
+        &mut self,
+        vault_path: P,
+    ) -> Result<Vec<(RecordId, RecordHint, Option<usize>)>, RecordError> {
+        let vault_path = vault_path.as_ref();
+        let list = self.list_hints_and_ids(vault_path);
+        let index = self.counter_index(vault_path)?;
+        Ok(list
+            .into_iter()
+            .map(|(rid, hint)| (rid, hint, index.counter(rid)))
+            .collect())
+    }
+
+    /// Lists the [`RecordId`] and [`RecordHint`] values of a vault, without the record of the counter index. The
+    /// record is hidden, but is filtered here as well in case it was written before it was hidden.
+    pub fn list_hints_and_ids<P: AsRef<Vec<u8>>>(&mut self, vault_path: P) -> Vec<(RecordId, RecordHint)> {
+        let vault_id = Self::derive_vault_id(&vault_path);
+        let index_id = Self::derive_index_id(&vault_path);
+        let key = match self.keystore.take_key(vault_id) {
+            Some(key) => key,
+            None => return Vec::new(),
+        };
+
+        let mut list = self.db.list_hints_and_ids(&key, vault_id);
+        self.keystore.insert_key(vault_id, key);
+        list.retain(|(rid, _)| *rid != index_id);
+        list
+    }
+
+    /// Gets the index of the latest version in the counter chain of a vault, or [`None`] if the chain is empty.
+    pub fn latest_version<P: AsRef<Vec<u8>>>(&mut self, vault_path: P) -> Result<Option<usize>, RecordError> {
+        Ok(self.counter_index(vault_path.as_ref())?.latest())
+    }
+
+    /// Gets the index of the next version in the counter chain of a vault.
+    pub fn next_version<P: AsRef<Vec<u8>>>(&mut self, vault_path: P) -> Result<usize, RecordError> {
+        Ok(self.counter_index(vault_path.as_ref())?.next())
+    }
+
+    /// Rolls the counter chain of a vault back to the specified version by revoking all later versions. The next
+    /// write to the head of the chain is linked to the specified version.
+    pub fn rollback_to<P: AsRef<Vec<u8>>>(&mut self, vault_path: P, version: usize) -> Result<(), RecordError> {
+        let vault_path = vault_path.as_ref();
+        let versions = self.versions(vault_path)?;
+        let not_found = || RecordError::RecordNotFound(Self::derive_record_id(vault_path, version).into());
+        let pos = versions
+            .iter()
+            .position(|(ctr, _)| *ctr == version)
+            .ok_or_else(not_found)?;
+
+        let vault_id = Self::derive_vault_id(vault_path);
+        let key = self.keystore.take_key(vault_id).ok_or_else(not_found)?;
+
+        // revoke from the head, so that the chain stays consistent if revoking fails.
+        let mut res = Ok(());
+        for i in (pos + 1..versions.len()).rev() {
+            let (_, rid) = versions[i];
+            res = self.db.revoke_record(&key, vault_id, rid);
+            if res.is_err() {
+                break;
+            }
+            let index = self.counters.get_mut(&vault_id).expect("counter index was loaded");
+            index.remove(rid);
+            index.set_latest(Some(versions[i - 1].0));
+        }
+        self.keystore.insert_key(vault_id, key);
+        res?;
+
+        self.store_counter_index(vault_path)
+    }
+
+    /// Gets the counter index of a vault. The index is loaded from its record if it is not cached yet.
+    fn counter_index(&mut self, vault_path: &Vec<u8>) -> Result<&mut CounterIndex, RecordError> {
+        let vault_id = Self::derive_vault_id(vault_path);
+        if !self.counters.contains_key(&vault_id) {
+            let index = self.load_counter_index(vault_path)?;
+            self.counters.insert(vault_id, index);
+        }
+        Ok(self.counters.get_mut(&vault_id).expect("counter index was loaded"))
+    }
+
+    /// Reads the counter index of a vault from its record. Every vault starts with an index record, only vaults that
+    /// were written before the index existed are indexed once by deriving the ids of the counters, and the index is
+    /// stored in the vault.
+    fn load_counter_index(&mut self, vault_path: &Vec<u8>) -> Result<CounterIndex, RecordError> {
+        let vault_id = Self::derive_vault_id(vault_path);
+        let index_id = Self::derive_index_id(vault_path);
+        let key = match self.keystore.take_key(vault_id) {
+            Some(key) => key,
+            None => return Ok(CounterIndex::default()),
+        };
+
+        if !self.db.contains_record(&key, vault_id, index_id) {
+            let ids: HashSet<RecordId> = self
+                .db
+                .list_hints_and_ids(&key, vault_id)
+                .into_iter()
+                .map(|(rid, _)| rid)
+                .collect();
+            self.keystore.insert_key(vault_id, key);
+            if ids.is_empty() {
+                return Ok(CounterIndex::default());
+            }
+
+            let index = CounterIndex::from_records(Self::find_counters(vault_path, ids));
+            self.write_counter_index(vault_path, &index)?;
+            return Ok(index);
+        }
+
+        let mut index = None;
+        let res = self
+            .db
+            .get_guard::<bincode::Error, _>(&key, vault_id, index_id, |guard| {
+                index = Some(CounterIndex::from_bytes(&guard.borrow())?);
+                Ok(())
+            });
+        self.keystore.insert_key(vault_id, key);
+
+        match res {
+            Ok(()) => Ok(index.expect("counter index was read")),
+            Err(VaultError::Record(e)) => Err(e),
+            Err(VaultError::Procedure(e)) => Err(RecordError::CorruptedContent(format!(
+                "Deserializing counter index failed: {}",
+                e
+            ))),
+            Err(VaultError::VaultNotFound(_)) => Err(RecordError::RecordNotFound(index_id.into())),
+        }
+    }
+
+    /// Finds the counters of the records of a vault by deriving the ids of the counters up to
+    /// [`MAX_LEGACY_COUNTER`]. Stops as soon as all records were found.
+    fn find_counters(vault_path: &Vec<u8>, mut ids: HashSet<RecordId>) -> BTreeMap<usize, RecordId> {
+        let mut records = BTreeMap::new();
+        for ctr in 0..=MAX_LEGACY_COUNTER {
+            if ids.is_empty() {
+                break;
+            }
+            let rid = Self::derive_record_id(vault_path, ctr);
+            if ids.remove(&rid) {
+                records.insert(ctr, rid);
+            }
+        }
+        records
+    }
+
+    /// Creates the vault of a location if it does not exist yet. The vault starts with an empty counter index, so that
+    /// it is never mistaken for a vault that was written before the index existed.
+    fn init_vault(&mut self, location: &Location) -> Result<(), RecordError> {
+        let vault_path = location.vault_path().to_vec();
+        let vault_id = Self::derive_vault_id(&vault_path);
+        if self.keystore.vault_exists(vault_id) {
+            return Ok(());
+        }
+
+        let key = self.keystore.create_key(vault_id);
+        self.db.init_vault(key, vault_id);
+        self.counters.insert(vault_id, CounterIndex::default());
+        self.write_counter_index(&vault_path, &CounterIndex::default())
+    }
+
+    /// Writes the cached counter index of a vault into its record.
+    fn store_counter_index(&mut self, vault_path: &Vec<u8>) -> Result<(), RecordError> {
+        let index = self.counter_index(vault_path)?.clone();
+        self.write_counter_index(vault_path, &index)
+    }
+
+    /// Writes a counter index into the hidden record of its vault.
+    fn write_counter_index(&mut self, vault_path: &Vec<u8>, index: &CounterIndex) -> Result<(), RecordError> {
+        let vault_id = Self::derive_vault_id(vault_path);
+        let index_id = Self::derive_index_id(vault_path);
+        let bytes = index
+            .to_bytes()
+            .map_err(|e| RecordError::CorruptedContent(format!("Serializing counter index failed: {}", e)))?;
+
+        let key = match self.keystore.take_key(vault_id) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let hint = RecordHint::new(COUNTER_INDEX_HINT).expect("hint has valid length");
+        let attributes = RecordAttributes {
+            prev: None,
+            hidden: true,
+        };
+        let res = self.db.write_with_attributes(
+            &key,
+            vault_id,
+            index_id,
+            &bytes,
+            hint,
+            RecordPolicy::default(),
+            attributes,
+        );
+        self.keystore.insert_key(vault_id, key);
+        res
+    }
+
+    /// Gets the [`RecordAttributes`] of a new record at a location. A new record at the head of a counter chain is
+    /// linked to the latest version of the chain.
+    fn record_attributes(&mut self, location: &Location) -> Result<RecordAttributes, RecordError> {
+        match location {
+            Location::Counter { vault_path, counter } => {
+                let index = self.counter_index(vault_path)?;
+                let prev = index.latest().and_then(|head| index.record(head));
+                match counter {
+                    Some(ctr) if *ctr < index.next() => Ok(RecordAttributes::default()),
+                    _ => Ok(RecordAttributes { prev, hidden: false }),
+                }
+            }
+            Location::Generic { .. } => Ok(RecordAttributes::default()),
+        }
+    }
+
+    /// Adds the record that was written to a counter location to the counter index. A new record at the head of the
+    /// chain becomes the latest version of the chain.
+    fn index_counter_record(&mut self, location: &Location, record_id: RecordId) -> Result<(), RecordError> {
+        if let Location::Counter { vault_path, counter } = location {
+            let index = self.counter_index(vault_path)?;
+            let next = index.next();
+            let ctr = counter.unwrap_or(next);
+            if index.insert(ctr, record_id) {
+                if ctr >= next {
+                    index.set_latest(Some(ctr));
+                }
+                self.store_counter_index(vault_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the record of a counter location from the counter index. If the record is a version of the chain, the
+    /// chain ends before it.
+    fn unindex_counter_record(
+        &mut self,
+        location: &Location,
+        record_id: RecordId,
+        versions: Vec<(usize, RecordId)>,
+    ) -> Result<(), RecordError> {
+        if let Location::Counter { vault_path, .. } = location {
+            let index = self.counter_index(vault_path)?;
+            if index.remove(record_id).is_some() {
+                if let Some(pos) = versions.iter().position(|(_, rid)| *rid == record_id) {
+                    index.set_latest(pos.checked_sub(1).map(|prev| versions[prev].0));
+                }
+                self.store_counter_index(vault_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Clears the cached counter indexes, e.g. after the vaults were replaced.
+    pub fn clear_counter_indexes(&mut self) {
+        self.counters.clear();
+    }
+
     /// Gets the [`VaultId`] from a specified path.
     pub fn derive_vault_id<P: AsRef<Vec<u8>>>(path: P) -> VaultId {
         VaultId::load_from_path(path.as_ref(), path.as_ref())
@@ -116,25 +478,54 @@ impl SecureClient {
         RecordId::load_from_path(path.as_bytes(), path.as_bytes())
     }
 
+    /// Derives the [`RecordId`] of the record that holds the counter index of the vault.
+    pub fn derive_index_id<P: AsRef<Vec<u8>>>(vault_path: P) -> RecordId {
+        let path = format!("{:?}{}", vault_path.as_ref(), "counter_index");
+
+        RecordId::load_from_path(path.as_bytes(), path.as_bytes())
+    }
+
     /// Gets the client string.
     pub fn get_client_str(&self) -> String {
         self.client_id.into()
     }
 
-    /// Gets the current index of a record if its a counter.
-    pub fn get_index_from_record_id<P: AsRef<Vec<u8>>>(&self, vault_path: P, record_id: RecordId) -> usize {
-        let mut ctr = 0;
-        let vault_path = vault_path.as_ref();
-
-        while ctr <= 32_000_000 {
-            let rid = Self::derive_record_id(vault_path, ctr);
-            if record_id == rid {
-                break;
+    /// Rolls back the active transaction on the vaults and restores the keys of the changed vaults.
+    pub fn rollback_transaction(&mut self) {
+        for (vault_id, key) in self.db.rollback_transaction() {
+            self.keystore.take_key(vault_id);
+            if let Some(key) = key {
+                self.keystore.insert_key(vault_id, key);
             }
-            ctr += 1;
         }
+        // the cached counter indexes may refer to records that were rolled back.
+        self.clear_counter_indexes();
+    }
 
-        ctr
+    /// Checks the policy of a record against the procedure that is currently executed. Records with a restricted
+    /// policy can only be used from within a permitted procedure. Returns the policy of the record.
+    fn check_policy(
+        &self,
+        key: &Key<internals::Provider>,
+        vault_id: VaultId,
+        record_id: RecordId,
+    ) -> Result<RecordPolicy, RecordError> {
+        let policy = self.db.get_policy(key, vault_id, record_id)?;
+        let usage = self.procedure.map_or(u64::MAX, ProcedureKind::usage);
+        if policy.permits(usage) {
+            Ok(policy)
+        } else {
+            Err(RecordError::PolicyViolation(record_id.into()))
+        }
+    }
+
+    /// Gets the current index of a record if its a counter.
+    pub fn get_index_from_record_id<P: AsRef<Vec<u8>>>(
+        &mut self,
+        vault_path: P,
+        record_id: RecordId,
+    ) -> Result<Option<usize>, RecordError> {
+        Ok(self.counter_index(vault_path.as_ref())?.counter(record_id))
     }
 }
 
@@ -143,7 +534,7 @@ impl Runner for SecureClient {
     where
         F: FnOnce(GuardedVec<u8>) -> Result<T, FatalProcedureError>,
     {
-        let (vault_id, record_id) = Self::resolve_location(location);
+        let (vault_id, record_id) = self.resolve_read(location)?;
         let key = self
             .keystore
             .take_key(vault_id)
@@ -154,7 +545,10 @@ impl Runner for SecureClient {
             ret = Some(f(guard)?);
             Ok(())
         };
-        let res = self.db.get_guard(&key, vault_id, record_id, execute_procedure);
+        let res = match self.check_policy(&key, vault_id, record_id) {
+            Ok(_) => self.db.get_guard(&key, vault_id, record_id, execute_procedure),
+            Err(e) => Err(VaultError::Record(e)),
+        };
         self.keystore.insert_key(vault_id, key);
 
         match res {
@@ -173,10 +567,23 @@ impl Runner for SecureClient {
     where
         F: FnOnce(GuardedVec<u8>) -> Result<Products<T>, FatalProcedureError>,
     {
-        let (vid0, rid0) = Self::resolve_location(location0);
-        let (vid1, rid1) = Self::resolve_location(location1);
+        let (vid0, rid0) = self.resolve_read(location0)?;
+        let (vid1, rid1) = self.resolve_write(location1)?;
+        let attributes = self.record_attributes(location1)?;
 
         let key0 = self.keystore.take_key(vid0).ok_or(VaultError::VaultNotFound(vid0))?;
+        let policy = match self.check_policy(&key0, vid0, rid0) {
+            Ok(policy) => policy,
+            Err(e) => {
+                self.keystore.insert_key(vid0, key0);
+                return Err(VaultError::Record(e));
+            }
+        };
+        // the derived secret may not be exported if its source may not be exported.
+        let policy = RecordPolicy {
+            denied: policy.denied & ProcedureKind::export_usage(),
+            ..RecordPolicy::default()
+        };
 
         let mut ret = None;
         let execute_procedure = |guard: GuardedVec<u8>| {
@@ -187,47 +594,96 @@ impl Runner for SecureClient {
 
         let res;
         if vid0 == vid1 {
-            res = self
-                .db
-                .exec_proc(&key0, vid0, rid0, &key0, vid1, rid1, hint, execute_procedure);
+            res = self.db.exec_proc_with_attributes(
+                &key0,
+                vid0,
+                rid0,
+                &key0,
+                vid1,
+                rid1,
+                hint,
+                policy,
+                attributes,
+                execute_procedure,
+            );
         } else {
-            if !self.keystore.vault_exists(vid1) {
-                let key1 = self.keystore.create_key(vid1);
-                self.db.init_vault(key1, vid1);
+            if let Err(e) = self.init_vault(location1) {
+                self.keystore.insert_key(vid0, key0);
+                return Err(VaultError::Record(e));
             }
             let key1 = self.keystore.take_key(vid1).unwrap();
-            res = self
-                .db
-                .exec_proc(&key0, vid0, rid0, &key1, vid1, rid1, hint, execute_procedure);
+            res = self.db.exec_proc_with_attributes(
+                &key0,
+                vid0,
+                rid0,
+                &key1,
+                vid1,
+                rid1,
+                hint,
+                policy,
+                attributes,
+                execute_procedure,
+            );
             self.keystore.insert_key(vid1, key1);
         }
 
         self.keystore.insert_key(vid0, key0);
 
         match res {
-            Ok(()) => Ok(ret.unwrap()),
+            Ok(()) => {
+                self.index_counter_record(location1, rid1)?;
+                Ok(ret.unwrap())
+            }
             Err(e) => Err(e),
         }
     }
 
-    fn write_to_vault(&mut self, location: &Location, hint: RecordHint, value: Vec<u8>) -> Result<(), RecordError> {
-        let (vault_id, record_id) = Self::resolve_location(location);
-        if !self.keystore.vault_exists(vault_id) {
-            let key = self.keystore.create_key(vault_id);
-            self.db.init_vault(key, vault_id);
-        }
+    fn write_to_vault(
+        &mut self,
+        location: &Location,
+        hint: RecordHint,
+        value: Vec<u8>,
+        policy: RecordPolicy,
+    ) -> Result<(), RecordError> {
+        let (vault_id, record_id) = self.resolve_write(location)?;
+        let attributes = self.record_attributes(location)?;
+        self.init_vault(location)?;
         let key = self.keystore.take_key(vault_id).unwrap();
-        let res = self.db.write(&key, vault_id, record_id, &value, hint);
+        let res = self
+            .db
+            .write_with_attributes(&key, vault_id, record_id, &value, hint, policy, attributes);
+        self.keystore.insert_key(vault_id, key);
+        res?;
+
+        self.index_counter_record(location, record_id)
+    }
+
+    fn get_metadata(&mut self, location: &Location) -> Result<(RecordHint, RecordPolicy), RecordError> {
+        let (vault_id, record_id) = self.resolve_read(location)?;
+        let key = self
+            .keystore
+            .take_key(vault_id)
+            .ok_or(RecordError::RecordNotFound(record_id.into()))?;
+        let res = self.db.get_policy(&key, vault_id, record_id).and_then(|policy| {
+            let hint = self.db.get_hint(&key, vault_id, record_id)?;
+            Ok((hint, policy))
+        });
         self.keystore.insert_key(vault_id, key);
         res
     }
 
     fn revoke_data(&mut self, location: &Location) -> Result<(), RecordError> {
-        let (vault_id, record_id) = Self::resolve_location(location);
+        let (vault_id, record_id) = self.resolve_read(location)?;
+        // the versions are collected first, the link of a revoked record can not be read anymore.
+        let versions = match location {
+            Location::Counter { vault_path, .. } => self.versions(vault_path)?,
+            Location::Generic { .. } => Vec::new(),
+        };
         if let Some(key) = self.keystore.take_key(vault_id) {
             let res = self.db.revoke_record(&key, vault_id, record_id);
             self.keystore.insert_key(vault_id, key);
             res?;
+            self.unindex_counter_record(location, record_id, versions)?;
         }
         Ok(())
     }
@@ -241,6 +697,22 @@ impl Runner for SecureClient {
         self.keystore.insert_key(vault_id, key);
         true
     }
+
+    fn rotate_vault_key(&mut self, vault_id: VaultId) -> Result<(), VaultError<FatalProcedureError>> {
+        let db = &mut self.db;
+        let res = self
+            .keystore
+            .rotate_key(vault_id, |old_key, new_key| {
+                db.rotate_vault_key(old_key, new_key, vault_id)
+            })
+            .ok_or(VaultError::VaultNotFound(vault_id))?;
+
+        res.map_err(|e| match e {
+            VaultError::VaultNotFound(vid) => VaultError::VaultNotFound(vid),
+            VaultError::Record(e) => VaultError::Record(e),
+            VaultError::Procedure(e) => match e {},
+        })
+    }
 }
 
 #[cfg(test)]
@@ -255,7 +727,7 @@ mod tests {
 
         let vault_path = b"some_vault".to_vec();
 
-        let client: SecureClient = SecureClient::new(clientid);
+        let mut client: SecureClient = SecureClient::new(clientid);
         let mut ctr = 0;
         let mut ctr2 = 0;
 
@@ -273,10 +745,175 @@ mod tests {
         let rid = SecureClient::derive_record_id(vault_path.clone(), ctr);
 
         let test_rid = SecureClient::derive_record_id(vault_path.clone(), 2);
-        let ctr = client.get_index_from_record_id(vault_path, rid);
-
         assert_eq!(test_rid, rid);
-        assert_eq!(2, ctr);
+
+        // counters are only indexed once a record was written.
+        assert_eq!(client.get_index_from_record_id(&vault_path, rid).unwrap(), None);
+
+        for i in 0..3 {
+            let location = Location::counter::<_, usize>(vault_path.clone(), i);
+            client
+                .write_to_vault(
+                    &location,
+                    RecordHint::new(b"").unwrap(),
+                    vec![i as u8],
+                    RecordPolicy::default(),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(client.get_index_from_record_id(&vault_path, rid).unwrap(), Some(2));
+        assert_eq!(client.next_version(&vault_path).unwrap(), 3);
+
+        // the index is restored from its record.
+        client.clear_counter_indexes();
+        assert_eq!(client.get_index_from_record_id(&vault_path, rid).unwrap(), Some(2));
+        assert_eq!(client.list_hints_and_ids(&vault_path).len(), 3);
+
+        client
+            .revoke_data(&Location::counter::<_, usize>(vault_path.clone(), 1))
+            .unwrap();
+        assert_eq!(client.latest_version(&vault_path).unwrap(), Some(0));
+        assert_eq!(client.get_index_from_record_id(&vault_path, rid).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_counter_index_of_existing_vault() {
+        let clientid = ClientId::random::<Provider>().unwrap();
+        let vault_path = b"some_vault".to_vec();
+        let mut client: SecureClient = SecureClient::new(clientid);
+
+        // records that were written without the index are indexed on first access.
+        let vault_id = SecureClient::derive_vault_id(&vault_path);
+        let key = client.keystore.create_key(vault_id).clone();
+        for i in [0, 1, 2, 5] {
+            let rid = SecureClient::derive_record_id(&vault_path, i);
+            client
+                .db
+                .write(&key, vault_id, rid, b"test", RecordHint::new(b"").unwrap())
+                .unwrap();
+        }
+
+        assert_eq!(client.latest_version(&vault_path).unwrap(), Some(2));
+        let rid = SecureClient::derive_record_id(&vault_path, 1);
+        assert_eq!(client.get_index_from_record_id(&vault_path, rid).unwrap(), Some(1));
+
+        // counters after a gap are indexed as well, and are not reused.
+        let rid = SecureClient::derive_record_id(&vault_path, 5);
+        assert_eq!(client.get_index_from_record_id(&vault_path, rid).unwrap(), Some(5));
+        assert_eq!(client.next_version(&vault_path).unwrap(), 6);
+
+        // the index is stored in a hidden record.
+        let index_id = SecureClient::derive_index_id(&vault_path);
+        assert!(client.db.contains_record(&key, vault_id, index_id));
+        assert_eq!(client.db.list_hints_and_ids(&key, vault_id).len(), 4);
+        client.clear_counter_indexes();
+        assert_eq!(client.get_index_from_record_id(&vault_path, rid).unwrap(), Some(5));
+    }
+
+    #[test]
+    fn test_counter_index_of_new_vault() {
+        let clientid = ClientId::random::<Provider>().unwrap();
+        let vault_path = b"some_vault".to_vec();
+        let mut client: SecureClient = SecureClient::new(clientid);
+
+        // a new vault is created with an empty index, even if it only holds generic records.
+        client
+            .write_to_vault(
+                &Location::generic(vault_path.clone(), b"record".to_vec()),
+                RecordHint::new(b"").unwrap(),
+                b"test".to_vec(),
+                RecordPolicy::default(),
+            )
+            .unwrap();
+
+        let vault_id = SecureClient::derive_vault_id(&vault_path);
+        let index_id = SecureClient::derive_index_id(&vault_path);
+        let key = client.keystore.take_key(vault_id).unwrap();
+        assert!(client.db.contains_record(&key, vault_id, index_id));
+        client.keystore.insert_key(vault_id, key);
+
+        // the index is read from its record instead of scanning the vault for counters.
+        client.clear_counter_indexes();
+        assert_eq!(client.latest_version(&vault_path).unwrap(), None);
+        assert_eq!(client.next_version(&vault_path).unwrap(), 0);
+        assert_eq!(client.list_hints_and_ids(&vault_path).len(), 1);
+    }
+
+    #[test]
+    fn test_counter_overflow() {
+        let clientid = ClientId::random::<Provider>().unwrap();
+        let vault_path = b"some_vault".to_vec();
+        let mut client: SecureClient = SecureClient::new(clientid);
+
+        let location = Location::counter::<_, usize>(vault_path.clone(), usize::MAX);
+        assert!(matches!(
+            client.write_to_vault(
+                &location,
+                RecordHint::new(b"").unwrap(),
+                b"test".to_vec(),
+                RecordPolicy::default(),
+            ),
+            Err(RecordError::CounterOverflow(usize::MAX))
+        ));
+
+        // the version after the second to last counter would be the last one.
+        let location = Location::counter::<_, usize>(vault_path.clone(), usize::MAX - 1);
+        client
+            .write_to_vault(
+                &location,
+                RecordHint::new(b"").unwrap(),
+                b"test".to_vec(),
+                RecordPolicy::default(),
+            )
+            .unwrap();
+        assert_eq!(client.next_version(&vault_path).unwrap(), usize::MAX);
+        assert!(client
+            .write_to_vault(
+                &Location::counter_head(vault_path.clone()),
+                RecordHint::new(b"").unwrap(),
+                b"test".to_vec(),
+                RecordPolicy::default(),
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_corrupted_counter_index() {
+        let clientid = ClientId::random::<Provider>().unwrap();
+        let vault_path = b"some_vault".to_vec();
+        let mut client: SecureClient = SecureClient::new(clientid);
+
+        let location = Location::counter::<_, usize>(vault_path.clone(), 0);
+        client
+            .write_to_vault(
+                &location,
+                RecordHint::new(b"").unwrap(),
+                b"test".to_vec(),
+                RecordPolicy::default(),
+            )
+            .unwrap();
+
+        let vault_id = SecureClient::derive_vault_id(&vault_path);
+        let index_id = SecureClient::derive_index_id(&vault_path);
+        let key = client.keystore.take_key(vault_id).unwrap();
+        client
+            .db
+            .write(&key, vault_id, index_id, b"corrupted", RecordHint::new(b"").unwrap())
+            .unwrap();
+        client.keystore.insert_key(vault_id, key);
+        client.clear_counter_indexes();
+
+        // the index is not replaced by an empty index.
+        assert!(client.latest_version(&vault_path).is_err());
+        assert!(client
+            .write_to_vault(
+                &Location::counter_head(vault_path.clone()),
+                RecordHint::new(b"").unwrap(),
+                b"test".to_vec(),
+                RecordPolicy::default(),
+            )
+            .is_err());
     }
 
     #[test]
@@ -6,13 +6,38 @@
 use crate::{state::secure::Store, Provider};
 
 use engine::{
-    snapshot::{self, read_from, write_to, Key, ReadError as EngineReadError, WriteError as EngineWriteError},
+    snapshot::{
+        self, read_from_stream, read_from_stream_with_password, write_to_stream, write_to_stream_with_password, Key,
+        ReadError as EngineReadError, WriteError as EngineWriteError,
+    },
     vault::{ClientId, DbView, Key as PKey, VaultId},
 };
 
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, io, path::Path};
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::Path,
+};
 use thiserror::Error as DeriveError;
+use zeroize::Zeroize;
+
+/// Secret that protects a snapshot file.
+pub enum SnapshotKey {
+    /// Raw key that is used to encrypt the snapshot.
+    Key(Key),
+    /// Password from which the key is derived. The key derivation parameters are stored in the snapshot header.
+    Password(Vec<u8>),
+}
+
+impl Drop for SnapshotKey {
+    fn drop(&mut self) {
+        match self {
+            SnapshotKey::Key(key) => key.zeroize(),
+            SnapshotKey::Password(password) => password.zeroize(),
+        }
+    }
+}
 
 /// Wrapper for the [`SnapshotState`] data structure.
 #[derive(Default)]
@@ -45,30 +70,46 @@ impl Snapshot {
 
     /// Reads state from the specified named snapshot or the specified path
     /// TODO: Add associated data.
-    pub fn read_from_snapshot(name: Option<&str>, path: Option<&Path>, key: Key) -> Result<Self, ReadError> {
-        let state = match path {
-            Some(p) => read_from(p, &key, &[])?,
-            None => read_from(&snapshot::files::get_path(name)?, &key, &[])?,
+    pub fn read_from_snapshot(name: Option<&str>, path: Option<&Path>, key: SnapshotKey) -> Result<Self, ReadError> {
+        let path = match path {
+            Some(p) => p.to_path_buf(),
+            None => snapshot::files::get_path(name)?,
+        };
+        let deserialize = |r: &mut dyn Read| {
+            SnapshotState::deserialize_from(r)
+                .map_err(|_| EngineReadError::CorruptedContent("Deserialization failed.".into()))
+        };
+        let data = match &key {
+            SnapshotKey::Key(key) => read_from_stream(&path, key, &[], deserialize)?,
+            SnapshotKey::Password(password) => read_from_stream_with_password(&path, password, &[], deserialize)?,
         };
-
-        let data =
-            SnapshotState::deserialize(state).map_err(|_| ReadError::CorruptedContent("Decryption failed.".into()))?;
 
         Ok(Self::new(data))
     }
 
     /// Writes state to the specified named snapshot or the specified path
     /// TODO: Add associated data.
-    pub fn write_to_snapshot(&self, name: Option<&str>, path: Option<&Path>, key: Key) -> Result<(), WriteError> {
-        let data = self
-            .state
-            .serialize()
-            .map_err(|_| WriteError::CorruptedData("Serialization failed.".into()))?;
+    pub fn write_to_snapshot(
+        &self,
+        name: Option<&str>,
+        path: Option<&Path>,
+        key: SnapshotKey,
+    ) -> Result<(), WriteError> {
+        let serialize = |w: &mut dyn Write| {
+            self.state
+                .serialize_into(w)
+                .map_err(|_| EngineWriteError::CorruptedData("Serialization failed.".into()))
+        };
+
+        let write = |p: &Path| match &key {
+            SnapshotKey::Key(key) => write_to_stream(p, key, &[], serialize),
+            SnapshotKey::Password(password) => write_to_stream_with_password(p, password, &[], serialize),
+        };
 
         // TODO: This is a hack and probably should be removed when we add proper error handling.
         let f = move || match path {
-            Some(p) => write_to(&data, p, &key, &[]),
-            None => write_to(&data, &snapshot::files::get_path(name)?, &key, &[]),
+            Some(p) => write(p),
+            None => write(&snapshot::files::get_path(name)?),
         };
 
         match f() {
@@ -78,6 +119,38 @@ impl Snapshot {
     }
 }
 
+impl Snapshot {
+    /// Reads the snapshot at the specified path with the old key, upgrading it from any former version of the
+    /// snapshot format, and writes it back in the current format with the new key.
+    pub fn migrate_snapshot(path: &Path, old_key: SnapshotKey, new_key: SnapshotKey) -> Result<(), MigrationError> {
+        let snapshot = Self::read_from_snapshot(None, Some(path), old_key)?;
+        snapshot.write_to_snapshot(None, Some(path), new_key)?;
+
+        Ok(())
+    }
+
+    /// Decrypts the state of all clients in the specified named snapshot or the specified path with the old key and
+    /// re-encrypts it with the new key. The file is replaced atomically, it is left untouched if either step fails.
+    pub fn rekey_snapshot(
+        name: Option<&str>,
+        path: Option<&Path>,
+        old_key: SnapshotKey,
+        new_key: SnapshotKey,
+    ) -> Result<(), RekeyError> {
+        let path = match path {
+            Some(p) => p.to_path_buf(),
+            None => snapshot::files::get_path(name).map_err(|e| RekeyError::Read(e.into()))?,
+        };
+
+        let snapshot = Self::read_from_snapshot(None, Some(&path), old_key).map_err(RekeyError::Read)?;
+        snapshot
+            .write_to_snapshot(None, Some(&path), new_key)
+            .map_err(RekeyError::Write)?;
+
+        Ok(())
+    }
+}
+
 impl SnapshotState {
     /// Creates a new snapshot state.
     pub fn new(id: ClientId, data: (HashMap<VaultId, PKey<Provider>>, DbView<Provider>, Store)) -> Self {
@@ -92,14 +165,14 @@ impl SnapshotState {
         self.0.insert(id, data);
     }
 
-    /// Serializes the snapshot state into bytes.
-    pub fn serialize(&self) -> bincode::Result<Vec<u8>> {
-        bincode::serialize(&self)
+    /// Serializes the snapshot state into the writer.
+    pub fn serialize_into<W: Write>(&self, writer: W) -> bincode::Result<()> {
+        bincode::serialize_into(writer, &self)
     }
 
-    /// Deserializes the snapshot state from bytes.
-    pub fn deserialize(data: Vec<u8>) -> bincode::Result<Self> {
-        bincode::deserialize(&data)
+    /// Deserializes the snapshot state from the reader.
+    pub fn deserialize_from<R: Read>(reader: R) -> bincode::Result<Self> {
+        bincode::deserialize_from(reader)
     }
 }
 
@@ -147,3 +220,22 @@ impl From<EngineWriteError> for WriteError {
         }
     }
 }
+
+#[derive(Debug, DeriveError)]
+pub enum MigrationError {
+    #[error("reading the snapshot failed: {0}")]
+    Read(#[from] ReadError),
+
+    #[error("writing the snapshot failed: {0}")]
+    Write(#[from] WriteError),
+}
+
+/// Error of changing the key of a snapshot file.
+#[derive(Debug, DeriveError)]
+pub enum RekeyError {
+    #[error("decrypting the snapshot with the old key failed: {0}")]
+    Read(ReadError),
+
+    #[error("encrypting the snapshot with the new key failed: {0}")]
+    Write(WriteError),
+}
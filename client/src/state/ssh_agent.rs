@@ -0,0 +1,363 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! SSH agent protocol (draft-miller-ssh-agent) on a Unix socket.
+//!
+//! The agent offers the Ed25519 keys of the configured vault locations as identities. The public keys are computed
+//! once with the [`PublicKey`] procedure when the agent is spawned, signatures are computed with the [`Ed25519Sign`]
+//! procedure through the [`Stronghold`] handle, so every signature is subject to the usage policies of the records.
+//! Secret keys never leave the vault.
+
+use crate::{
+    procedures::{Ed25519Sign, KeyType, PublicKey},
+    utils::ssh::{ed25519_key_blob, put_string, Reader, SSH_ED25519},
+    Location, RecordHint, Stronghold,
+};
+use crypto::{signatures::ed25519, utils::rand::fill};
+use std::{
+    cell::RefCell,
+    ffi::OsString,
+    fs,
+    io::{self, ErrorKind},
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{UnixListener, UnixStream},
+    task::JoinHandle,
+};
+use zeroize::Zeroize;
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENT_SUCCESS: u8 = 6;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+const SSH_AGENTC_ADD_IDENTITY: u8 = 17;
+const SSH_AGENTC_REMOVE_IDENTITY: u8 = 18;
+const SSH_AGENTC_REMOVE_ALL_IDENTITIES: u8 = 19;
+
+/// Maximal length of a message, as in OpenSSH.
+const MAX_MESSAGE_LENGTH: usize = 256 * 1024;
+
+const ADDED_IDENTITY_HINT: &str = "ssh-agent";
+
+/// Configuration of the identities of the [`SshAgent`] and the requests that it accepts.
+///
+/// By default requests to add or remove identities are rejected.
+#[derive(Debug, Clone, Default)]
+pub struct SshAgentConfig {
+    identities: Vec<(Location, Option<[u8; 32]>, String)>,
+    add_identity_vault: Option<Vec<u8>>,
+    remove_identity: bool,
+}
+
+impl SshAgentConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Offer the Ed25519 key at `location` as identity with the given comment. The public key is computed once when
+    /// the agent is spawned, the identity is skipped if the policy of the record denies [`PublicKey`].
+    pub fn with_identity(mut self, location: Location, comment: impl Into<String>) -> Self {
+        self.identities.push((location, None, comment.into()));
+        self
+    }
+
+    /// Offer the Ed25519 key at `location`, whose public key is already known, as identity with the given comment.
+    /// The record is only used for signatures, so it may be restricted to [`Ed25519Sign`].
+    pub fn with_identity_key(mut self, location: Location, public_key: [u8; 32], comment: impl Into<String>) -> Self {
+        self.identities.push((location, Some(public_key), comment.into()));
+        self
+    }
+
+    /// Accept requests to add Ed25519 identities. The secret keys are written to the vault at `vault_path`, with
+    /// the public key as record path.
+    pub fn with_add_identity(mut self, vault_path: impl Into<Vec<u8>>) -> Self {
+        self.add_identity_vault = Some(vault_path.into());
+        self
+    }
+
+    /// Accept / Reject requests to remove identities. Removed identities are no longer offered by the agent, the
+    /// records of identities that were added through the agent are deleted from the vault.
+    pub fn with_remove_identity(mut self, is_enabled: bool) -> Self {
+        self.remove_identity = is_enabled;
+        self
+    }
+}
+
+/// Handle of a running SSH agent.
+///
+/// The agent stops accepting connections and removes its socket when the handle is dropped. Connections that are
+/// already open are served until the client closes them.
+pub struct SshAgent {
+    path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl SshAgent {
+    /// Bind the agent to the Unix socket at `path` and serve the identities of `config`.
+    pub(crate) async fn spawn(stronghold: Stronghold, path: &Path, config: SshAgentConfig) -> io::Result<Self> {
+        let agent = Rc::new(Agent::new(stronghold, config).await);
+        let listener = bind_private(path)?;
+        let task = actix::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let agent = agent.clone();
+                actix::spawn(async move {
+                    // errors only affect the connection of the failing client.
+                    let _ = agent.serve(stream).await;
+                });
+            }
+        });
+        Ok(SshAgent {
+            path: path.to_path_buf(),
+            task,
+        })
+    }
+
+    /// Path of the socket of the agent, e.g. to set the `SSH_AUTH_SOCK` environment variable.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stop the agent.
+    pub fn stop(self) {}
+}
+
+impl Drop for SshAgent {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Bind a socket at `path` that only the current user can connect to.
+///
+/// The socket is bound inside of a fresh directory that is only accessible by the user and is restricted before it
+/// is linked to `path`, so other users never get the chance to connect to it. Linking fails if `path` exists.
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "socket path has no file name"))?;
+    let mut suffix = [0u8; 8];
+    fill(&mut suffix).map_err(|e| io::Error::other(e.to_string()))?;
+    let mut dir_name = OsString::from(".");
+    dir_name.push(file_name);
+    dir_name.push(format!(".{:016x}", u64::from_le_bytes(suffix)));
+    let dir = path.with_file_name(dir_name);
+
+    fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let socket = dir.join("socket");
+    let listener = UnixListener::bind(&socket).and_then(|listener| {
+        fs::set_permissions(&socket, fs::Permissions::from_mode(0o600))?;
+        fs::hard_link(&socket, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&socket);
+    let _ = fs::remove_dir(&dir);
+    listener
+}
+
+#[derive(Clone)]
+struct Identity {
+    location: Location,
+    public_key: [u8; 32],
+    comment: String,
+    // the key was added through the agent.
+    added: bool,
+}
+
+struct Agent {
+    stronghold: Stronghold,
+    identities: RefCell<Vec<Identity>>,
+    add_identity_vault: Option<Vec<u8>>,
+    remove_identity: bool,
+}
+
+impl Agent {
+    async fn new(stronghold: Stronghold, config: SshAgentConfig) -> Self {
+        let mut identities = Vec::with_capacity(config.identities.len());
+        for (location, public_key, comment) in config.identities {
+            let public_key = match public_key {
+                Some(public_key) => public_key,
+                // identities whose key can not be used are skipped.
+                None => match public_key_of(&stronghold, &location).await {
+                    Some(public_key) => public_key,
+                    None => continue,
+                },
+            };
+            identities.push(Identity {
+                location,
+                public_key,
+                comment,
+                added: false,
+            });
+        }
+        Agent {
+            stronghold,
+            identities: RefCell::new(identities),
+            add_identity_vault: config.add_identity_vault,
+            remove_identity: config.remove_identity,
+        }
+    }
+
+    async fn serve(&self, mut stream: UnixStream) -> io::Result<()> {
+        loop {
+            let mut len = [0; 4];
+            match stream.read_exact(&mut len).await {
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                res => res?,
+            };
+            let len = u32::from_be_bytes(len) as usize;
+            if len == 0 || len > MAX_MESSAGE_LENGTH {
+                return Err(ErrorKind::InvalidData.into());
+            }
+            let mut message = vec![0; len];
+            stream.read_exact(&mut message).await?;
+            let response = self
+                .handle(message[0], Reader(&message[1..]))
+                .await
+                .unwrap_or_else(|| vec![SSH_AGENT_FAILURE]);
+            // the message may contain the secret key of an added identity.
+            message.zeroize();
+
+            let mut frame = (response.len() as u32).to_be_bytes().to_vec();
+            frame.extend_from_slice(&response);
+            stream.write_all(&frame).await?;
+        }
+    }
+
+    async fn handle(&self, ty: u8, reader: Reader<'_>) -> Option<Vec<u8>> {
+        match ty {
+            SSH_AGENTC_REQUEST_IDENTITIES => Some(self.request_identities()),
+            SSH_AGENTC_SIGN_REQUEST => self.sign(reader).await,
+            SSH_AGENTC_ADD_IDENTITY => self.add_identity(reader).await,
+            SSH_AGENTC_REMOVE_IDENTITY => self.remove_identity(reader).await,
+            SSH_AGENTC_REMOVE_ALL_IDENTITIES => self.remove_all_identities().await,
+            // key constraints, locking and extensions are not supported.
+            _ => None,
+        }
+    }
+
+    fn request_identities(&self) -> Vec<u8> {
+        let identities = self.identities.borrow();
+        let mut response = vec![SSH_AGENT_IDENTITIES_ANSWER];
+        response.extend_from_slice(&(identities.len() as u32).to_be_bytes());
+        for identity in identities.iter() {
+            put_string(&mut response, &ed25519_key_blob(&identity.public_key));
+            put_string(&mut response, identity.comment.as_bytes());
+        }
+        response
+    }
+
+    async fn sign(&self, mut reader: Reader<'_>) -> Option<Vec<u8>> {
+        let blob = reader.string()?;
+        let data = reader.string()?;
+        // the flags only select the hash of RSA signatures.
+        let _flags = reader.u32()?;
+        let identity = self.find(blob)?;
+
+        let sign = Ed25519Sign {
+            msg: data.to_vec(),
+            private_key: identity.location,
+        };
+        let signature = self.stronghold.runtime_exec(sign).await.ok()?.ok()?;
+
+        let mut sig_blob = Vec::new();
+        put_string(&mut sig_blob, SSH_ED25519);
+        put_string(&mut sig_blob, &signature);
+        let mut response = vec![SSH_AGENT_SIGN_RESPONSE];
+        put_string(&mut response, &sig_blob);
+        Some(response)
+    }
+
+    async fn add_identity(&self, mut reader: Reader<'_>) -> Option<Vec<u8>> {
+        let vault_path = self.add_identity_vault.clone()?;
+        if reader.string()? != SSH_ED25519 {
+            return None;
+        }
+        let public_key = reader.string()?;
+        // the secret key is the seed followed by the public key.
+        let secret_key = reader.string()?;
+        let comment = String::from_utf8_lossy(reader.string()?).into_owned();
+        if secret_key.len() != 64 || &secret_key[32..] != public_key {
+            return None;
+        }
+        // the public key is checked against the seed before the key is accepted.
+        let mut seed = [0; 32];
+        seed.copy_from_slice(&secret_key[..32]);
+        let derived = ed25519::SecretKey::from_bytes(seed).public_key().to_bytes();
+        seed.zeroize();
+        if derived[..] != *public_key {
+            return None;
+        }
+
+        let location = Location::generic(vault_path, public_key.to_vec());
+        let hint = RecordHint::new(ADDED_IDENTITY_HINT).expect("hint is not too long");
+        self.stronghold
+            .write_to_vault(location.clone(), secret_key[..32].to_vec(), hint, vec![])
+            .await
+            .ok()?
+            .ok()?;
+
+        let mut identities = self.identities.borrow_mut();
+        identities.retain(|identity| identity.location != location);
+        identities.push(Identity {
+            location,
+            public_key: derived,
+            comment,
+            added: true,
+        });
+        Some(vec![SSH_AGENT_SUCCESS])
+    }
+
+    async fn remove_identity(&self, mut reader: Reader<'_>) -> Option<Vec<u8>> {
+        if !self.remove_identity {
+            return None;
+        }
+        let blob = reader.string()?;
+        let identity = self.find(blob)?;
+        self.identities
+            .borrow_mut()
+            .retain(|other| other.location != identity.location);
+        self.delete_added(vec![identity]).await;
+        Some(vec![SSH_AGENT_SUCCESS])
+    }
+
+    async fn remove_all_identities(&self) -> Option<Vec<u8>> {
+        if !self.remove_identity {
+            return None;
+        }
+        let identities = self.identities.take();
+        self.delete_added(identities).await;
+        Some(vec![SSH_AGENT_SUCCESS])
+    }
+
+    async fn delete_added(&self, identities: Vec<Identity>) {
+        for identity in identities.into_iter().filter(|identity| identity.added) {
+            let _ = self.stronghold.delete_data(identity.location, true).await;
+        }
+    }
+
+    /// Find the identity with the given key blob.
+    fn find(&self, blob: &[u8]) -> Option<Identity> {
+        self.identities
+            .borrow()
+            .iter()
+            .find(|identity| ed25519_key_blob(&identity.public_key) == blob)
+            .cloned()
+    }
+}
+
+async fn public_key_of(stronghold: &Stronghold, location: &Location) -> Option<[u8; 32]> {
+    let public_key = PublicKey {
+        ty: KeyType::Ed25519,
+        private_key: location.clone(),
+        compressed: false,
+    };
+    let public_key: Vec<u8> = stronghold.runtime_exec(public_key).await.ok()?.ok()?;
+    public_key.try_into().ok()
+}
@@ -0,0 +1,485 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Software authenticator of WebAuthn, implementing the `authenticatorMakeCredential` and `authenticatorGetAssertion`
+//! commands of CTAP2.
+//!
+//! Credentials are Ed25519 keys that are generated with the [`GenerateKey`] procedure in a vault of the current
+//! target client, with the credential ID as record path. The relying party and user of each credential and the
+//! signature counters are kept in the store of the client. Attestation objects have the `packed` format with self
+//! attestation, so they can be verified without an attestation CA.
+//!
+//! The authenticator has no user interface: the presence of the user is always asserted, user verification only if
+//! it is enabled in the [`AuthenticatorConfig`].
+
+use crate::{
+    procedures::{Ed25519Sign, GenerateKey, KeyType, ProcedureError, PublicKey},
+    utils::cbor,
+    ActorError, Location, RecordHint, Stronghold,
+};
+use crypto::{
+    hashes::{sha::Sha256, Digest},
+    utils::rand::fill,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error as DeriveError;
+
+/// COSE algorithm identifier of EdDSA, the only algorithm of the credentials.
+pub const COSE_ALGORITHM_EDDSA: i64 = -8;
+
+const COSE_KEY_TYPE_OKP: i64 = 1;
+const COSE_CURVE_ED25519: i64 = 6;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+const CREDENTIAL_ID_LENGTH: usize = 32;
+
+const CREDENTIAL_STORE_KEY: &[u8] = b"webauthn/credential/";
+const COUNTER_STORE_KEY: &[u8] = b"webauthn/counter/";
+const RELYING_PARTY_STORE_KEY: &[u8] = b"webauthn/rp/";
+
+const CREDENTIAL_HINT: &str = "webauthn";
+
+#[derive(DeriveError, Debug, Clone)]
+pub enum AuthenticatorError {
+    #[error("none of the requested algorithms is supported")]
+    UnsupportedAlgorithm,
+
+    #[error("user verification is not enabled")]
+    UnsupportedOption,
+
+    #[error("a credential of the exclude list exists")]
+    CredentialExcluded,
+
+    #[error("no credential of the relying party is available")]
+    NoCredentials,
+
+    #[error("actor error: {0}")]
+    Actor(#[from] ActorError),
+
+    #[error("procedure error: {0}")]
+    Procedure(#[from] ProcedureError),
+
+    #[error("invalid credential data in the store: {0}")]
+    InvalidStoreData(String),
+
+    #[error("generating the credential ID failed: {0}")]
+    CredentialId(String),
+}
+
+impl AuthenticatorError {
+    /// Status code of the error in CTAP2.
+    pub fn status_code(&self) -> u8 {
+        match self {
+            AuthenticatorError::CredentialExcluded => 0x19,
+            AuthenticatorError::UnsupportedAlgorithm => 0x26,
+            AuthenticatorError::UnsupportedOption => 0x2b,
+            AuthenticatorError::NoCredentials => 0x2e,
+            // CTAP1_ERR_OTHER
+            _ => 0x7f,
+        }
+    }
+}
+
+/// Configuration of the [`Authenticator`].
+///
+/// By default the AAGUID is zero and user verification is rejected.
+#[derive(Debug, Clone, Default)]
+pub struct AuthenticatorConfig {
+    aaguid: [u8; 16],
+    user_verification: bool,
+}
+
+impl AuthenticatorConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the AAGUID, that identifies the model of the authenticator in the attested credential data.
+    pub fn with_aaguid(mut self, aaguid: [u8; 16]) -> Self {
+        self.aaguid = aaguid;
+        self
+    }
+
+    /// Accept / Reject requests with user verification. If enabled, the caller is responsible to verify the user,
+    /// the authenticator only sets the flag.
+    pub fn with_user_verification(mut self, is_enabled: bool) -> Self {
+        self.user_verification = is_enabled;
+        self
+    }
+}
+
+/// Relying party of a credential.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelyingParty {
+    /// RP ID, usually the domain of the relying party.
+    pub id: String,
+
+    pub name: Option<String>,
+}
+
+/// User account of a credential.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    /// User handle of the relying party.
+    pub id: Vec<u8>,
+
+    pub name: Option<String>,
+
+    pub display_name: Option<String>,
+}
+
+/// Parameters of the `authenticatorMakeCredential` command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MakeCredentialRequest {
+    /// Hash of the serialized client data.
+    pub client_data_hash: [u8; 32],
+
+    pub rp: RelyingParty,
+
+    pub user: User,
+
+    /// COSE algorithm identifiers that the relying party accepts.
+    pub pub_key_cred_params: Vec<i64>,
+
+    /// IDs of credentials that must not exist on the authenticator, usually the existing credentials of the user.
+    pub exclude_list: Vec<Vec<u8>>,
+
+    /// Create a discoverable credential, which can be used without its credential ID.
+    pub resident_key: bool,
+
+    pub user_verification: bool,
+}
+
+/// Result of the `authenticatorMakeCredential` command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MakeCredentialResponse {
+    pub credential_id: Vec<u8>,
+
+    /// Authenticator data with the attested credential data.
+    pub auth_data: Vec<u8>,
+
+    /// CBOR encoded attestation object, as it is passed to the relying party.
+    pub attestation_object: Vec<u8>,
+}
+
+/// Parameters of the `authenticatorGetAssertion` command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GetAssertionRequest {
+    pub rp_id: String,
+
+    /// Hash of the serialized client data.
+    pub client_data_hash: [u8; 32],
+
+    /// IDs of the credentials that the relying party accepts. If it is empty, a discoverable credential of the
+    /// relying party is used.
+    pub allow_list: Vec<Vec<u8>>,
+
+    pub user_verification: bool,
+}
+
+/// Result of the `authenticatorGetAssertion` command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GetAssertionResponse {
+    pub credential_id: Vec<u8>,
+
+    pub auth_data: Vec<u8>,
+
+    /// Ed25519 signature over the authenticator data and the client data hash.
+    pub signature: Vec<u8>,
+
+    /// User of the credential, if it was discovered without allow list.
+    pub user: Option<User>,
+}
+
+/// Metadata of a credential in the store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Credential {
+    rp_id: String,
+    user: User,
+    discoverable: bool,
+}
+
+/// Authenticator with the credentials in a vault of the [`Stronghold`].
+pub struct Authenticator {
+    stronghold: Stronghold,
+    vault_path: Vec<u8>,
+    config: AuthenticatorConfig,
+}
+
+impl Authenticator {
+    pub(crate) fn new(stronghold: Stronghold, vault_path: Vec<u8>, config: AuthenticatorConfig) -> Self {
+        Authenticator {
+            stronghold,
+            vault_path,
+            config,
+        }
+    }
+
+    /// Create a new credential for the relying party and user of the request.
+    pub async fn make_credential(
+        &self,
+        request: MakeCredentialRequest,
+    ) -> Result<MakeCredentialResponse, AuthenticatorError> {
+        if !request.pub_key_cred_params.contains(&COSE_ALGORITHM_EDDSA) {
+            return Err(AuthenticatorError::UnsupportedAlgorithm);
+        }
+        let flags = self.flags(request.user_verification)?;
+        for credential_id in &request.exclude_list {
+            if let Some(credential) = self.credential(credential_id).await? {
+                if credential.rp_id == request.rp.id {
+                    return Err(AuthenticatorError::CredentialExcluded);
+                }
+            }
+        }
+
+        let mut credential_id = vec![0; CREDENTIAL_ID_LENGTH];
+        fill(&mut credential_id).map_err(|e| AuthenticatorError::CredentialId(e.to_string()))?;
+        let location = self.location(&credential_id);
+        let generate_key = GenerateKey {
+            ty: KeyType::Ed25519,
+            output: location.clone(),
+            hint: RecordHint::new(CREDENTIAL_HINT).expect("hint is not too long"),
+        };
+        self.stronghold.runtime_exec(generate_key).await??;
+
+        let rp_id = request.rp.id.clone();
+        match self.register(&credential_id, location.clone(), request, flags).await {
+            Ok(response) => Ok(response),
+            Err(e) => {
+                // do not leave a key or store entries of a credential that was not returned to the caller.
+                self.remove(&credential_id, &rp_id, location).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Store the metadata of a newly generated credential and create its attestation.
+    async fn register(
+        &self,
+        credential_id: &[u8],
+        location: Location,
+        request: MakeCredentialRequest,
+        flags: u8,
+    ) -> Result<MakeCredentialResponse, AuthenticatorError> {
+        let public_key = PublicKey {
+            ty: KeyType::Ed25519,
+            private_key: location.clone(),
+            compressed: false,
+        };
+        let public_key: Vec<u8> = self.stronghold.runtime_exec(public_key).await??;
+
+        let credential = Credential {
+            rp_id: request.rp.id,
+            user: request.user,
+            discoverable: request.resident_key,
+        };
+        self.write(store_key(COUNTER_STORE_KEY, credential_id), &0u32).await?;
+        self.write(store_key(CREDENTIAL_STORE_KEY, credential_id), &credential)
+            .await?;
+        self.stronghold
+            .append_to_store_list(rp_store_key(&credential.rp_id), credential_id.to_vec())
+            .await?
+            .ok_or_else(|| AuthenticatorError::InvalidStoreData("invalid credential list".into()))?;
+
+        let mut attested_credential_data = self.config.aaguid.to_vec();
+        attested_credential_data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+        attested_credential_data.extend_from_slice(credential_id);
+        attested_credential_data.extend_from_slice(&cose_key(&public_key));
+        let auth_data = auth_data(
+            &credential.rp_id,
+            flags | FLAG_ATTESTED_CREDENTIAL_DATA,
+            0,
+            &attested_credential_data,
+        );
+        let signature = self.sign(location, &auth_data, &request.client_data_hash).await?;
+
+        Ok(MakeCredentialResponse {
+            credential_id: credential_id.to_vec(),
+            attestation_object: attestation_object(&auth_data, &signature),
+            auth_data,
+        })
+    }
+
+    /// Remove the key and the store entries of a credential. Errors are ignored, the credential is unusable without
+    /// its metadata anyway.
+    async fn remove(&self, credential_id: &[u8], rp_id: &str, location: Location) {
+        let _ = self.stronghold.delete_data(location, true).await;
+        let _ = self
+            .stronghold
+            .remove_from_store_list(rp_store_key(rp_id), credential_id.to_vec())
+            .await;
+        let _ = self
+            .stronghold
+            .delete_from_store(store_key(CREDENTIAL_STORE_KEY, credential_id))
+            .await;
+        let _ = self
+            .stronghold
+            .delete_from_store(store_key(COUNTER_STORE_KEY, credential_id))
+            .await;
+    }
+
+    /// Sign an assertion with a credential of the relying party. The signature counter of the credential is
+    /// incremented with each assertion.
+    ///
+    /// If several credentials are eligible, the first one of the allow list, or without allow list the most recently
+    /// created discoverable credential is used.
+    pub async fn get_assertion(
+        &self,
+        request: GetAssertionRequest,
+    ) -> Result<GetAssertionResponse, AuthenticatorError> {
+        let flags = self.flags(request.user_verification)?;
+        let (credential_id, credential) = self
+            .find(&request.rp_id, &request.allow_list)
+            .await?
+            .ok_or(AuthenticatorError::NoCredentials)?;
+
+        let counter_key = store_key(COUNTER_STORE_KEY, &credential_id);
+        let counter = self
+            .stronghold
+            .increment_store_counter(counter_key)
+            .await?
+            .ok_or_else(|| AuthenticatorError::InvalidStoreData("invalid signature counter".into()))?;
+
+        let auth_data = auth_data(&request.rp_id, flags, counter, &[]);
+        let location = self.location(&credential_id);
+        let signature = self.sign(location, &auth_data, &request.client_data_hash).await?;
+
+        Ok(GetAssertionResponse {
+            credential_id,
+            auth_data,
+            signature,
+            user: request.allow_list.is_empty().then_some(credential.user),
+        })
+    }
+
+    async fn find(
+        &self,
+        rp_id: &str,
+        allow_list: &[Vec<u8>],
+    ) -> Result<Option<(Vec<u8>, Credential)>, AuthenticatorError> {
+        let discover = allow_list.is_empty();
+        let credential_ids = if discover {
+            let mut credential_ids: Vec<Vec<u8>> = self.read(rp_store_key(rp_id)).await?.unwrap_or_default();
+            credential_ids.reverse();
+            credential_ids
+        } else {
+            allow_list.to_vec()
+        };
+        for credential_id in credential_ids {
+            match self.credential(&credential_id).await? {
+                Some(credential) if credential.rp_id == rp_id && (credential.discoverable || !discover) => {
+                    return Ok(Some((credential_id, credential)))
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    fn flags(&self, user_verification: bool) -> Result<u8, AuthenticatorError> {
+        match (user_verification, self.config.user_verification) {
+            (false, _) => Ok(FLAG_USER_PRESENT),
+            (true, true) => Ok(FLAG_USER_PRESENT | FLAG_USER_VERIFIED),
+            (true, false) => Err(AuthenticatorError::UnsupportedOption),
+        }
+    }
+
+    async fn sign(
+        &self,
+        location: Location,
+        auth_data: &[u8],
+        client_data_hash: &[u8],
+    ) -> Result<Vec<u8>, AuthenticatorError> {
+        let mut msg = auth_data.to_vec();
+        msg.extend_from_slice(client_data_hash);
+        let sign = Ed25519Sign {
+            msg,
+            private_key: location,
+        };
+        let signature = self.stronghold.runtime_exec(sign).await??;
+        Ok(signature.to_vec())
+    }
+
+    fn location(&self, credential_id: &[u8]) -> Location {
+        Location::generic(self.vault_path.clone(), credential_id.to_vec())
+    }
+
+    async fn credential(&self, credential_id: &[u8]) -> Result<Option<Credential>, AuthenticatorError> {
+        self.read(store_key(CREDENTIAL_STORE_KEY, credential_id)).await
+    }
+
+    async fn read<T: for<'de> Deserialize<'de>>(&self, key: Vec<u8>) -> Result<Option<T>, AuthenticatorError> {
+        match self.stronghold.read_from_store(key).await? {
+            Some(data) => bincode::deserialize(&data)
+                .map(Some)
+                .map_err(|e| AuthenticatorError::InvalidStoreData(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    async fn write<T: Serialize>(&self, key: Vec<u8>, value: &T) -> Result<(), AuthenticatorError> {
+        // serializing the metadata can not fail.
+        let data = bincode::serialize(value).expect("serialize credential data");
+        self.stronghold.write_to_store(key, data, None).await?;
+        Ok(())
+    }
+}
+
+fn store_key(prefix: &[u8], id: &[u8]) -> Vec<u8> {
+    let mut key = prefix.to_vec();
+    key.extend_from_slice(id);
+    key
+}
+
+/// Key of the list of credential IDs of a relying party.
+fn rp_store_key(rp_id: &str) -> Vec<u8> {
+    store_key(RELYING_PARTY_STORE_KEY, &Sha256::digest(rp_id.as_bytes()))
+}
+
+/// Authenticator data: `rpIdHash || flags || signCount || attestedCredentialData`.
+fn auth_data(rp_id: &str, flags: u8, counter: u32, attested_credential_data: &[u8]) -> Vec<u8> {
+    let mut auth_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+    auth_data.push(flags);
+    auth_data.extend_from_slice(&counter.to_be_bytes());
+    auth_data.extend_from_slice(attested_credential_data);
+    auth_data
+}
+
+/// COSE_Key of an Ed25519 public key (RFC 8152).
+fn cose_key(public_key: &[u8]) -> Vec<u8> {
+    let mut key = Vec::new();
+    cbor::put_map(&mut key, 4);
+    // kty
+    cbor::put_int(&mut key, 1);
+    cbor::put_int(&mut key, COSE_KEY_TYPE_OKP);
+    // alg
+    cbor::put_int(&mut key, 3);
+    cbor::put_int(&mut key, COSE_ALGORITHM_EDDSA);
+    // crv
+    cbor::put_int(&mut key, -1);
+    cbor::put_int(&mut key, COSE_CURVE_ED25519);
+    // x
+    cbor::put_int(&mut key, -2);
+    cbor::put_bytes(&mut key, public_key);
+    key
+}
+
+/// Attestation object in the `packed` format with self attestation.
+fn attestation_object(auth_data: &[u8], signature: &[u8]) -> Vec<u8> {
+    let mut object = Vec::new();
+    cbor::put_map(&mut object, 3);
+    cbor::put_text(&mut object, "fmt");
+    cbor::put_text(&mut object, "packed");
+    cbor::put_text(&mut object, "attStmt");
+    cbor::put_map(&mut object, 2);
+    cbor::put_text(&mut object, "alg");
+    cbor::put_int(&mut object, COSE_ALGORITHM_EDDSA);
+    cbor::put_text(&mut object, "sig");
+    cbor::put_bytes(&mut object, signature);
+    cbor::put_text(&mut object, "authData");
+    cbor::put_bytes(&mut object, auth_data);
+    object
+}
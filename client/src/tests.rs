@@ -10,3 +10,6 @@ mod interface_tests;
 #[cfg(feature = "p2p")]
 mod p2p_tests;
 mod procedures_tests;
+#[cfg(all(unix, feature = "ssh-agent"))]
+mod ssh_agent_tests;
+mod webauthn_tests;
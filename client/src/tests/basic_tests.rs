@@ -124,6 +124,84 @@ async fn test_multi_write_read_counter_head() {
     assert_eq!(Some(b"test 15".to_vec()), p);
 }
 
+// test the version chain of counter locations.
+#[actix::test]
+async fn test_counter_versions() {
+    let stronghold = setup_stronghold().await;
+    let client_path = b"test".to_vec();
+    let lochead = Location::counter_head("path");
+
+    for i in 0..3 {
+        let data = format!("version {:?}", i);
+        stronghold
+            .write_to_vault(
+                lochead.clone(),
+                data.as_bytes().to_vec(),
+                RecordHint::new(data).unwrap(),
+                vec![],
+            )
+            .await
+            .unwrap_or_else(|e| panic!("Actor error: {}", e))
+            .unwrap_or_else(|e| panic!("Write vault error: {}", e));
+    }
+
+    let p = stronghold
+        .read_secret(client_path.clone(), lochead.clone())
+        .await
+        .unwrap();
+    assert_eq!(Some(b"version 2".to_vec()), p);
+
+    let versions = stronghold.list_versions("path").await.unwrap().unwrap();
+    assert_eq!(versions.len(), 3);
+    for (i, (ctr, id, hint)) in versions.into_iter().enumerate() {
+        assert_eq!(ctr, i);
+        assert_eq!(
+            id,
+            SecureClient::resolve_location(Location::counter::<_, usize>("path", i)).1
+        );
+        assert_eq!(hint, RecordHint::new(format!("version {:?}", i)).unwrap());
+    }
+
+    // roll back to the first version.
+    stronghold.rollback_to("path", 0).await.unwrap().unwrap();
+    assert_eq!(stronghold.list_versions("path").await.unwrap().unwrap().len(), 1);
+
+    let p = stronghold
+        .read_secret(client_path.clone(), lochead.clone())
+        .await
+        .unwrap();
+    assert_eq!(Some(b"version 0".to_vec()), p);
+
+    // the next write is linked to the version that was rolled back to, revoked counters are not reused.
+    stronghold
+        .write_to_vault(
+            lochead.clone(),
+            b"new version".to_vec(),
+            RecordHint::new(b"new version").unwrap(),
+            vec![],
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    let versions = stronghold.list_versions("path").await.unwrap().unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].0, 0);
+    assert_eq!(versions[1].0, 3);
+
+    let p = stronghold.read_secret(client_path.clone(), lochead).await.unwrap();
+    assert_eq!(Some(b"new version".to_vec()), p);
+
+    let p = stronghold
+        .read_secret(client_path, Location::counter::<_, usize>("path", 2))
+        .await
+        .unwrap();
+    assert!(p.is_none());
+
+    assert!(stronghold.rollback_to("path", 5).await.unwrap().is_err());
+    assert!(stronghold.rollback_to("other path", 0).await.unwrap().is_err());
+}
+
 // test delete_data.
 #[actix::test]
 async fn test_revoke_with_gc() {
@@ -223,6 +301,20 @@ async fn test_write_read_snapshot() {
 
         assert_eq!(std::str::from_utf8(&p.unwrap()), Ok(res.as_str()));
     }
+
+    // the counter index is restored with the snapshot.
+    let mut list = stronghold.list_hints_ids_and_counters("path").await.unwrap().unwrap();
+    list.sort_by_key(|(_, _, ctr)| *ctr);
+    assert_eq!(list.len(), 20);
+    for (i, (id, hint, ctr)) in list.into_iter().enumerate() {
+        assert_eq!(ctr, Some(i));
+        assert_eq!(
+            id,
+            SecureClient::resolve_location(Location::counter::<_, usize>("path", i)).1
+        );
+        assert_eq!(hint, RecordHint::new(format!("test {:?}", i)).unwrap());
+    }
+    assert_eq!(stronghold.list_versions("path").await.unwrap().unwrap().len(), 20);
 }
 
 /// Spawn a number of actors and write one record into each of the child actors.  Writes the data from all of the actors
@@ -296,6 +388,278 @@ async fn test_write_read_multi_snapshot() {
     }
 }
 
+/// Test writing to a password protected snapshot and reading back.
+#[actix::test]
+async fn test_write_read_snapshot_with_password() {
+    let mut stronghold = setup_stronghold().await;
+
+    let password = b"some user password".to_vec();
+    let client_path = b"test".to_vec();
+    let loc = Location::generic("path", "record");
+
+    stronghold
+        .write_to_vault(loc.clone(), b"test".to_vec(), RecordHint::new("hint").unwrap(), vec![])
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write vault error: {}", e));
+
+    stronghold
+        .write_all_to_snapshot_with_password(&password, Some("test_password".into()), None)
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write snapshot error: {}", e));
+
+    stronghold.kill_stronghold(client_path.clone(), false).await.unwrap();
+
+    let wrong_password = b"wrong password".to_vec();
+    assert!(stronghold
+        .read_snapshot_with_password(
+            client_path.clone(),
+            None,
+            &wrong_password,
+            Some("test_password".into()),
+            None
+        )
+        .await
+        .unwrap()
+        .is_err());
+
+    stronghold
+        .read_snapshot_with_password(client_path.clone(), None, &password, Some("test_password".into()), None)
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Read snapshot error: {}", e));
+
+    let p = stronghold.read_secret(client_path, loc).await.unwrap();
+    assert_eq!(std::str::from_utf8(&p.unwrap()), Ok("test"));
+}
+
+/// Test migrating a snapshot to the current format with a new key.
+#[actix::test]
+async fn test_migrate_snapshot() {
+    let mut stronghold = setup_stronghold().await;
+
+    let old_key = b"abcdefghijklmnopqrstuvwxyz123456".to_vec();
+    let new_key = b"123456abcdefghijklmnopqrstuvwxyz".to_vec();
+    let client_path = b"test".to_vec();
+    let loc = Location::generic("path", "record");
+    let path = engine::snapshot::files::get_path(Some("test_migrate")).unwrap();
+
+    stronghold
+        .write_to_vault(loc.clone(), b"test".to_vec(), RecordHint::new("hint").unwrap(), vec![])
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write vault error: {}", e));
+
+    stronghold
+        .write_all_to_snapshot(&old_key, None, Some(path.clone()))
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write snapshot error: {}", e));
+
+    stronghold
+        .migrate_snapshot(path.clone(), &old_key, &new_key)
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Migrate snapshot error: {}", e));
+
+    stronghold.kill_stronghold(client_path.clone(), false).await.unwrap();
+
+    assert!(stronghold
+        .read_snapshot(client_path.clone(), None, &old_key, None, Some(path.clone()))
+        .await
+        .unwrap()
+        .is_err());
+
+    stronghold
+        .read_snapshot(client_path.clone(), None, &new_key, None, Some(path))
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Read snapshot error: {}", e));
+
+    let p = stronghold.read_secret(client_path, loc).await.unwrap();
+    assert_eq!(std::str::from_utf8(&p.unwrap()), Ok("test"));
+}
+
+/// Test migrating a password protected snapshot with a new password.
+#[actix::test]
+async fn test_migrate_snapshot_with_password() {
+    let mut stronghold = setup_stronghold().await;
+
+    let old_password = b"old password".to_vec();
+    let new_password = b"new password".to_vec();
+    let client_path = b"test".to_vec();
+    let loc = Location::generic("path", "record");
+    let path = engine::snapshot::files::get_path(Some("test_migrate_password")).unwrap();
+
+    stronghold
+        .write_to_vault(loc.clone(), b"test".to_vec(), RecordHint::new("hint").unwrap(), vec![])
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write vault error: {}", e));
+
+    stronghold
+        .write_all_to_snapshot_with_password(&old_password, None, Some(path.clone()))
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write snapshot error: {}", e));
+
+    stronghold
+        .migrate_snapshot_with_password(path.clone(), &old_password, &new_password)
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Migrate snapshot error: {}", e));
+
+    stronghold.kill_stronghold(client_path.clone(), false).await.unwrap();
+
+    assert!(stronghold
+        .read_snapshot_with_password(client_path.clone(), None, &old_password, None, Some(path.clone()))
+        .await
+        .unwrap()
+        .is_err());
+
+    stronghold
+        .read_snapshot_with_password(client_path.clone(), None, &new_password, None, Some(path))
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Read snapshot error: {}", e));
+
+    let p = stronghold.read_secret(client_path, loc).await.unwrap();
+    assert_eq!(std::str::from_utf8(&p.unwrap()), Ok("test"));
+}
+
+/// Test re-keying a snapshot that contains multiple clients, without loading them first.
+#[actix::test]
+async fn test_rekey_snapshot() {
+    let old_key = b"abcdefghijklmnopqrstuvwxyz012345".to_vec();
+    let new_key = b"012345abcdefghijklmnopqrstuvwxyz".to_vec();
+    let client_paths: Vec<Vec<u8>> = (0..2).map(|i| format!("rekey {}", i).into_bytes()).collect();
+    let loc = Location::generic("path", "record");
+
+    let mut stronghold = Stronghold::init_stronghold_system(client_paths[0].clone(), vec![])
+        .await
+        .unwrap();
+    stronghold
+        .spawn_stronghold_actor(client_paths[1].clone(), vec![])
+        .await
+        .unwrap();
+
+    for client_path in &client_paths {
+        stronghold.switch_actor_target(client_path.clone()).await.unwrap();
+        stronghold
+            .write_to_vault(
+                loc.clone(),
+                client_path.clone(),
+                RecordHint::new("hint").unwrap(),
+                vec![],
+            )
+            .await
+            .unwrap_or_else(|e| panic!("Actor error: {}", e))
+            .unwrap_or_else(|e| panic!("Write vault error: {}", e));
+    }
+
+    stronghold
+        .write_all_to_snapshot(&old_key, Some("test_rekey".into()), None)
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write snapshot error: {}", e));
+
+    // a new instance, that never loaded any of the clients.
+    let mut stronghold = Stronghold::init_stronghold_system(client_paths[0].clone(), vec![])
+        .await
+        .unwrap();
+
+    // re-keying with the wrong key fails and leaves the snapshot untouched.
+    assert!(stronghold
+        .rekey_snapshot(&new_key, &old_key, Some("test_rekey".into()), None)
+        .await
+        .unwrap()
+        .is_err());
+
+    stronghold
+        .rekey_snapshot(&old_key, &new_key, Some("test_rekey".into()), None)
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Rekey snapshot error: {}", e));
+
+    assert!(stronghold
+        .read_snapshot(client_paths[0].clone(), None, &old_key, Some("test_rekey".into()), None)
+        .await
+        .unwrap()
+        .is_err());
+
+    stronghold
+        .spawn_stronghold_actor(client_paths[1].clone(), vec![])
+        .await
+        .unwrap();
+
+    for client_path in &client_paths {
+        stronghold.switch_actor_target(client_path.clone()).await.unwrap();
+        stronghold
+            .read_snapshot(client_path.clone(), None, &new_key, Some("test_rekey".into()), None)
+            .await
+            .unwrap_or_else(|e| panic!("Actor error: {}", e))
+            .unwrap_or_else(|e| panic!("Read snapshot error: {}", e));
+
+        let p = stronghold.read_secret(client_path.clone(), loc.clone()).await.unwrap();
+        assert_eq!(p.as_ref(), Some(client_path));
+    }
+}
+
+/// Test changing the password of a password protected snapshot.
+#[actix::test]
+async fn test_rekey_snapshot_with_password() {
+    let mut stronghold = setup_stronghold().await;
+
+    let old_password = b"old password".to_vec();
+    let new_password = b"new password".to_vec();
+    let client_path = b"test".to_vec();
+    let loc = Location::generic("path", "record");
+    let path = engine::snapshot::files::get_path(Some("test_rekey_password")).unwrap();
+
+    stronghold
+        .write_to_vault(loc.clone(), b"test".to_vec(), RecordHint::new("hint").unwrap(), vec![])
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write vault error: {}", e));
+
+    stronghold
+        .write_all_to_snapshot_with_password(&old_password, None, Some(path.clone()))
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write snapshot error: {}", e));
+
+    // re-keying with the wrong password fails and leaves the snapshot untouched.
+    assert!(stronghold
+        .rekey_snapshot_with_password(&new_password, &old_password, None, Some(path.clone()))
+        .await
+        .unwrap()
+        .is_err());
+
+    stronghold
+        .rekey_snapshot_with_password(&old_password, &new_password, None, Some(path.clone()))
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Rekey snapshot error: {}", e));
+
+    stronghold.kill_stronghold(client_path.clone(), false).await.unwrap();
+
+    assert!(stronghold
+        .read_snapshot_with_password(client_path.clone(), None, &old_password, None, Some(path.clone()))
+        .await
+        .unwrap()
+        .is_err());
+
+    stronghold
+        .read_snapshot_with_password(client_path.clone(), None, &new_password, None, Some(path))
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Read snapshot error: {}", e));
+
+    let p = stronghold.read_secret(client_path, loc).await.unwrap();
+    assert_eq!(std::str::from_utf8(&p.unwrap()), Ok("test"));
+}
+
 #[actix::test]
 async fn test_store() {
     let client_path = b"test".to_vec();
@@ -1,10 +1,10 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use crypto::{keys::slip10::Chain, utils::rand::fill};
+use crypto::utils::rand::fill;
 pub use stronghold_utils::{random::*, test_utils};
 
-use crate::{Location, RecordHint};
+use crate::{procedures::DerivationPath, Location, RecordHint};
 
 /// Creates a random [`RecordHint`]
 pub fn record_hint() -> RecordHint {
@@ -28,7 +28,7 @@ pub fn passphrase() -> Option<String> {
 }
 
 /// Creates a random hd_path.
-pub fn hd_path() -> (String, Chain) {
+pub fn hd_path() -> (String, DerivationPath) {
     let mut s = "m".to_string();
     let mut is = vec![];
     while coinflip() {
@@ -36,5 +36,8 @@ pub fn hd_path() -> (String, Chain) {
         s.push_str(&format!("/{}'", i));
         is.push(i);
     }
-    (s, Chain::from_u32_hardened(is))
+    (
+        s,
+        DerivationPath::new(is.into_iter().map(|i| i | DerivationPath::HARDEN_MASK).collect()),
+    )
 }
@@ -2,13 +2,15 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    actors::secure_messages::Procedures,
     p2p::{identity::Keypair, NetworkConfig, OutboundFailure, P2pError, PeerId, Permissions, SwarmInfo},
-    procedures::{Slip10Derive, Slip10DeriveInput, Slip10Generate},
-    state::p2p::{ClientAccess, FirewallChannel, FirewallChannelSender},
+    procedures::{Curve, ExportWrapped, Slip10Derive, Slip10DeriveInput, Slip10Generate, WrappingKey},
+    state::p2p::{AccessRequest, ClientAccess, FirewallChannel, FirewallChannelSender, Request, ShRequest},
     tests::fresh,
     Location, Stronghold,
 };
 use futures::StreamExt;
+use p2p::firewall::FwRequest;
 use stronghold_utils::random::bytestring;
 use tokio::sync::{mpsc, oneshot};
 
@@ -198,6 +200,7 @@ async fn test_stronghold_p2p() {
                 remote_id,
                 remote_client_clone,
                 Slip10Derive {
+                    curve: Curve::Ed25519,
                     output: fresh::location(),
                     chain,
                     hint: fresh::record_hint(),
@@ -422,6 +425,7 @@ async fn test_p2p_firewall() {
 
         let loc4 = Location::generic(allowed_vault_path.clone(), fresh::bytestring(1024));
         let proc_derive = Slip10Derive {
+            curve: Curve::Ed25519,
             input: Slip10DeriveInput::Seed(loc3),
             chain: fresh::hd_path().1,
             output: loc4,
@@ -459,3 +463,27 @@ async fn test_p2p_firewall() {
 
     done_rx.await.unwrap();
 }
+
+#[test]
+fn test_p2p_firewall_export_wrapped() {
+    let source_vault_path = fresh::bytestring(1024);
+    let kek = fresh::location();
+    let export = ExportWrapped {
+        source: Location::generic(source_vault_path.clone(), fresh::bytestring(1024)),
+        wrapping_key: WrappingKey::Symmetric(kek.clone()),
+    };
+    let request = ShRequest {
+        client_path: fresh::bytestring(1024),
+        request: Request::Procedures(Procedures {
+            procedures: vec![export.into()],
+        }),
+    };
+    let access = AccessRequest::from_request(&request);
+
+    // The key encryption key is used as well, so access to the vault of the exported record does not suffice.
+    let permissions = ClientAccess::allow_none().with_vault_access(source_vault_path, true, false, false);
+    assert!(!permissions.is_permitted(&access));
+
+    let permissions = permissions.with_vault_access(kek.vault_path().to_vec(), true, false, false);
+    assert!(permissions.is_permitted(&access));
+}
@@ -0,0 +1,243 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::signatures::ed25519;
+use std::os::unix::fs::PermissionsExt;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+use super::fresh;
+use crate::{
+    procedures::{GenerateKey, KeyType, ProcedureKind, PublicKey},
+    ssh_agent::SshAgentConfig,
+    Location, Stronghold, VaultFlags,
+};
+
+fn socket_path() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("stronghold-ssh-agent-{}.sock", fresh::string(16)))
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &[u8]) {
+    buf.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    buf.extend_from_slice(s);
+}
+
+fn get_string<'a>(buf: &mut &'a [u8]) -> &'a [u8] {
+    let len = u32::from_be_bytes(buf[..4].try_into().unwrap()) as usize;
+    let (s, rest) = buf[4..].split_at(len);
+    *buf = rest;
+    s
+}
+
+fn key_blob(public_key: &[u8]) -> Vec<u8> {
+    let mut blob = Vec::new();
+    put_string(&mut blob, b"ssh-ed25519");
+    put_string(&mut blob, public_key);
+    blob
+}
+
+async fn request(stream: &mut UnixStream, message: &[u8]) -> Vec<u8> {
+    let mut frame = (message.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(message);
+    stream.write_all(&frame).await.unwrap();
+    let mut len = [0; 4];
+    stream.read_exact(&mut len).await.unwrap();
+    let mut response = vec![0; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response).await.unwrap();
+    response
+}
+
+async fn identities(stream: &mut UnixStream) -> Vec<(Vec<u8>, String)> {
+    let response = request(stream, &[11]).await;
+    assert_eq!(response[0], 12);
+    let count = u32::from_be_bytes(response[1..5].try_into().unwrap());
+    let mut buf = &response[5..];
+    (0..count)
+        .map(|_| {
+            let blob = get_string(&mut buf).to_vec();
+            let comment = String::from_utf8(get_string(&mut buf).to_vec()).unwrap();
+            (blob, comment)
+        })
+        .collect()
+}
+
+async fn sign(stream: &mut UnixStream, blob: &[u8], data: &[u8]) -> Option<ed25519::Signature> {
+    let mut message = vec![13];
+    put_string(&mut message, blob);
+    put_string(&mut message, data);
+    message.extend_from_slice(&0u32.to_be_bytes());
+    let response = request(stream, &message).await;
+    if response[0] != 14 {
+        return None;
+    }
+    let mut buf = &response[1..];
+    let mut sig_blob = get_string(&mut buf);
+    assert_eq!(get_string(&mut sig_blob), b"ssh-ed25519");
+    Some(ed25519::Signature::from_bytes(
+        get_string(&mut sig_blob).try_into().unwrap(),
+    ))
+}
+
+fn add_identity_message(sk: &ed25519::SecretKey, comment: &str) -> Vec<u8> {
+    let pk = sk.public_key().to_bytes();
+    let mut secret = sk.to_bytes().to_vec();
+    secret.extend_from_slice(&pk);
+    let mut message = vec![17];
+    put_string(&mut message, b"ssh-ed25519");
+    put_string(&mut message, &pk);
+    put_string(&mut message, &secret);
+    put_string(&mut message, comment.as_bytes());
+    message
+}
+
+async fn generate_key(sh: &Stronghold) -> Result<(Location, [u8; 32]), Box<dyn std::error::Error>> {
+    let location = fresh::location();
+    sh.runtime_exec(GenerateKey {
+        ty: KeyType::Ed25519,
+        output: location.clone(),
+        hint: fresh::record_hint(),
+    })
+    .await??;
+    let pk: Vec<u8> = sh
+        .runtime_exec(PublicKey {
+            ty: KeyType::Ed25519,
+            private_key: location.clone(),
+            compressed: false,
+        })
+        .await??;
+    Ok((location, pk.try_into().unwrap()))
+}
+
+#[actix::test]
+async fn usecase_ssh_agent() -> Result<(), Box<dyn std::error::Error>> {
+    let cp = fresh::bytestring(u8::MAX.into());
+    let sh = Stronghold::init_stronghold_system(cp, vec![]).await?;
+    let (location, pk) = generate_key(&sh).await?;
+    let (_, unused_pk) = generate_key(&sh).await?;
+
+    // by default identities can neither be added nor removed.
+    let config = SshAgentConfig::new().with_identity(location.clone(), "user@host");
+    let agent = sh.spawn_ssh_agent(socket_path(), config).await?;
+    let mut stream = UnixStream::connect(agent.path()).await?;
+
+    // only the user can connect to the socket, and it is not replaced by another agent.
+    let mode = std::fs::metadata(agent.path())?.permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(sh.spawn_ssh_agent(agent.path(), SshAgentConfig::new()).await.is_err());
+
+    let ids = identities(&mut stream).await;
+    assert_eq!(ids, vec![(key_blob(&pk), "user@host".to_string())]);
+
+    let sig = sign(&mut stream, &key_blob(&pk), b"session").await.unwrap();
+    assert!(ed25519::PublicKey::try_from_bytes(pk)?.verify(&sig, b"session"));
+    assert!(sign(&mut stream, &key_blob(&unused_pk), b"session").await.is_none());
+
+    let sk = ed25519::SecretKey::generate()?;
+    assert_eq!(request(&mut stream, &add_identity_message(&sk, "added")).await, vec![5]);
+    let mut remove = vec![18];
+    put_string(&mut remove, &key_blob(&pk));
+    assert_eq!(request(&mut stream, &remove).await, vec![5]);
+    assert_eq!(request(&mut stream, &[19]).await, vec![5]);
+    assert_eq!(identities(&mut stream).await.len(), 1);
+
+    let path = agent.path().to_path_buf();
+    agent.stop();
+    assert!(!path.exists());
+
+    // identities are added to and removed from the vault.
+    let vault_path = fresh::bytestring(1024);
+    let config = SshAgentConfig::new()
+        .with_identity(location.clone(), "user@host")
+        .with_add_identity(vault_path.clone())
+        .with_remove_identity(true);
+    let agent = sh.spawn_ssh_agent(socket_path(), config).await?;
+    let mut stream = UnixStream::connect(agent.path()).await?;
+
+    let added_pk = sk.public_key().to_bytes();
+    assert_eq!(request(&mut stream, &add_identity_message(&sk, "added")).await, vec![6]);
+    let ids = identities(&mut stream).await;
+    assert_eq!(ids.len(), 2);
+    assert!(ids.contains(&(key_blob(&added_pk), "added".to_string())));
+    let sig = sign(&mut stream, &key_blob(&added_pk), b"session").await.unwrap();
+    assert!(sk.public_key().verify(&sig, b"session"));
+    let added = Location::generic(vault_path, added_pk.to_vec());
+    assert!(sh.record_exists(added.clone()).await?);
+
+    let mut remove = vec![18];
+    put_string(&mut remove, &key_blob(&added_pk));
+    assert_eq!(request(&mut stream, &remove).await, vec![6]);
+    assert_eq!(
+        identities(&mut stream).await,
+        vec![(key_blob(&pk), "user@host".to_string())]
+    );
+    assert!(!sh.record_exists(added).await?);
+
+    // configured identities are only removed from the agent.
+    assert_eq!(request(&mut stream, &[19]).await, vec![6]);
+    assert!(identities(&mut stream).await.is_empty());
+    assert!(sh.record_exists(location).await?);
+
+    Ok(())
+}
+
+#[actix::test]
+async fn usecase_ssh_agent_restricted_keys() -> Result<(), Box<dyn std::error::Error>> {
+    let cp = fresh::bytestring(u8::MAX.into());
+    let sh = Stronghold::init_stronghold_system(cp, vec![]).await?;
+
+    // the key may only sign, so its public key has to be passed with the identity.
+    let sign_only_sk = ed25519::SecretKey::generate()?;
+    let sign_only_pk = sign_only_sk.public_key().to_bytes();
+    let sign_only = fresh::location();
+    let flags = vec![VaultFlags::AllowOnly(vec![ProcedureKind::Ed25519Sign])];
+    sh.write_to_vault(
+        sign_only.clone(),
+        sign_only_sk.to_bytes().to_vec(),
+        fresh::record_hint(),
+        flags,
+    )
+    .await??;
+
+    // the public key of the key is computed once with the first of its two uses.
+    let limited_sk = ed25519::SecretKey::generate()?;
+    let limited_pk = limited_sk.public_key().to_bytes();
+    let limited = fresh::location();
+    let flags = vec![VaultFlags::MaxUses(2)];
+    sh.write_to_vault(
+        limited.clone(),
+        limited_sk.to_bytes().to_vec(),
+        fresh::record_hint(),
+        flags,
+    )
+    .await??;
+
+    let config = SshAgentConfig::new()
+        .with_identity(sign_only.clone(), "skipped")
+        .with_identity_key(sign_only, sign_only_pk, "sign only")
+        .with_identity(limited, "limited");
+    let agent = sh.spawn_ssh_agent(socket_path(), config).await?;
+    let mut stream = UnixStream::connect(agent.path()).await?;
+
+    for _ in 0..3 {
+        assert_eq!(
+            identities(&mut stream).await,
+            vec![
+                (key_blob(&sign_only_pk), "sign only".to_string()),
+                (key_blob(&limited_pk), "limited".to_string())
+            ]
+        );
+    }
+
+    for _ in 0..2 {
+        let sig = sign(&mut stream, &key_blob(&sign_only_pk), b"session").await.unwrap();
+        assert!(sign_only_sk.public_key().verify(&sig, b"session"));
+    }
+
+    let sig = sign(&mut stream, &key_blob(&limited_pk), b"session").await.unwrap();
+    assert!(limited_sk.public_key().verify(&sig, b"session"));
+    assert!(sign(&mut stream, &key_blob(&limited_pk), b"session").await.is_none());
+
+    Ok(())
+}
@@ -0,0 +1,256 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::{
+    hashes::{sha::Sha256, Digest},
+    signatures::ed25519,
+};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+use super::fresh;
+use crate::{
+    webauthn::{
+        AuthenticatorConfig, GetAssertionRequest, MakeCredentialRequest, RelyingParty, User, COSE_ALGORITHM_EDDSA,
+    },
+    Location, Stronghold,
+};
+
+const RP_ID: &str = "example.com";
+
+/// Authenticator data of a credential, as it is checked by a relying party.
+struct AuthData {
+    flags: u8,
+    counter: u32,
+    credential: Option<(Vec<u8>, ed25519::PublicKey)>,
+}
+
+fn text(s: &str) -> Value {
+    Value::Text(s.to_string())
+}
+
+fn parse_auth_data(auth_data: &[u8]) -> AuthData {
+    assert_eq!(auth_data[..32], Sha256::digest(RP_ID.as_bytes())[..]);
+    let flags = auth_data[32];
+    let counter = u32::from_be_bytes(auth_data[33..37].try_into().unwrap());
+    let credential = (flags & 0x40 != 0).then(|| {
+        let id_len = u16::from_be_bytes(auth_data[53..55].try_into().unwrap()) as usize;
+        let credential_id = auth_data[55..55 + id_len].to_vec();
+        let cose_key: BTreeMap<Value, Value> = serde_cbor::from_slice(&auth_data[55 + id_len..]).unwrap();
+        assert_eq!(cose_key[&Value::Integer(1)], Value::Integer(1));
+        assert_eq!(
+            cose_key[&Value::Integer(3)],
+            Value::Integer(COSE_ALGORITHM_EDDSA.into())
+        );
+        assert_eq!(cose_key[&Value::Integer(-1)], Value::Integer(6));
+        let x = match &cose_key[&Value::Integer(-2)] {
+            Value::Bytes(x) => x.clone(),
+            _ => panic!("invalid COSE key"),
+        };
+        let public_key = ed25519::PublicKey::try_from_bytes(x.try_into().unwrap()).unwrap();
+        (credential_id, public_key)
+    });
+    AuthData {
+        flags,
+        counter,
+        credential,
+    }
+}
+
+fn verify(public_key: &ed25519::PublicKey, auth_data: &[u8], client_data_hash: &[u8], signature: &[u8]) -> bool {
+    let mut msg = auth_data.to_vec();
+    msg.extend_from_slice(client_data_hash);
+    public_key.verify(&ed25519::Signature::from_bytes(signature.try_into().unwrap()), &msg)
+}
+
+/// Verify a `packed` self attestation and return the credential.
+fn verify_attestation(attestation_object: &[u8], client_data_hash: &[u8]) -> (AuthData, Vec<u8>) {
+    let object: BTreeMap<Value, Value> = serde_cbor::from_slice(attestation_object).unwrap();
+    assert_eq!(object[&text("fmt")], text("packed"));
+    let auth_data = match &object[&text("authData")] {
+        Value::Bytes(auth_data) => auth_data.clone(),
+        _ => panic!("invalid authenticator data"),
+    };
+    let statement = match &object[&text("attStmt")] {
+        Value::Map(statement) => statement,
+        _ => panic!("invalid attestation statement"),
+    };
+    assert_eq!(statement[&text("alg")], Value::Integer(COSE_ALGORITHM_EDDSA.into()));
+    assert!(!statement.contains_key(&text("x5c")));
+    let signature = match &statement[&text("sig")] {
+        Value::Bytes(signature) => signature,
+        _ => panic!("invalid signature"),
+    };
+    let parsed = parse_auth_data(&auth_data);
+    let (_, public_key) = parsed.credential.as_ref().unwrap();
+    assert!(verify(public_key, &auth_data, client_data_hash, signature));
+    (parsed, auth_data)
+}
+
+fn make_credential_request(user_id: &[u8], resident_key: bool) -> MakeCredentialRequest {
+    MakeCredentialRequest {
+        client_data_hash: Sha256::digest(&fresh::bytestring(64)).into(),
+        rp: RelyingParty {
+            id: RP_ID.into(),
+            name: Some("Example".into()),
+        },
+        user: User {
+            id: user_id.to_vec(),
+            name: Some("alice@example.com".into()),
+            display_name: Some("Alice".into()),
+        },
+        pub_key_cred_params: vec![-7, COSE_ALGORITHM_EDDSA],
+        exclude_list: vec![],
+        resident_key,
+        user_verification: false,
+    }
+}
+
+fn get_assertion_request(allow_list: Vec<Vec<u8>>) -> GetAssertionRequest {
+    GetAssertionRequest {
+        rp_id: RP_ID.into(),
+        client_data_hash: Sha256::digest(&fresh::bytestring(64)).into(),
+        allow_list,
+        user_verification: false,
+    }
+}
+
+#[actix::test]
+async fn usecase_webauthn() -> Result<(), Box<dyn std::error::Error>> {
+    let cp = fresh::bytestring(u8::MAX.into());
+    let sh = Stronghold::init_stronghold_system(cp, vec![]).await?;
+    let vault_path = fresh::bytestring(1024);
+    let authenticator = sh.webauthn_authenticator(vault_path.clone(), AuthenticatorConfig::new());
+
+    // registration of a discoverable credential.
+    let request = make_credential_request(b"alice", true);
+    let response = authenticator.make_credential(request.clone()).await?;
+    let (auth_data, signed_auth_data) = verify_attestation(&response.attestation_object, &request.client_data_hash);
+    assert_eq!(signed_auth_data, response.auth_data);
+    assert_eq!(auth_data.flags, 0x41);
+    assert_eq!(auth_data.counter, 0);
+    let (credential_id, public_key) = auth_data.credential.unwrap();
+    assert_eq!(credential_id, response.credential_id);
+    assert!(
+        sh.record_exists(Location::generic(vault_path.clone(), credential_id.clone()))
+            .await?
+    );
+
+    // authentication with the credential ID.
+    let request = get_assertion_request(vec![fresh::bytestring(32), credential_id.clone()]);
+    let assertion = authenticator.get_assertion(request.clone()).await?;
+    assert_eq!(assertion.credential_id, credential_id);
+    assert_eq!(assertion.user, None);
+    assert!(verify(
+        &public_key,
+        &assertion.auth_data,
+        &request.client_data_hash,
+        &assertion.signature
+    ));
+    let auth_data = parse_auth_data(&assertion.auth_data);
+    assert_eq!(auth_data.flags, 0x01);
+    assert_eq!(auth_data.counter, 1);
+    assert!(auth_data.credential.is_none());
+
+    // authentication with the discoverable credential; the counter increases with each assertion.
+    let request = get_assertion_request(vec![]);
+    let assertion = authenticator.get_assertion(request.clone()).await?;
+    assert_eq!(assertion.credential_id, credential_id);
+    assert_eq!(assertion.user.unwrap().id, b"alice".to_vec());
+    assert!(verify(
+        &public_key,
+        &assertion.auth_data,
+        &request.client_data_hash,
+        &assertion.signature
+    ));
+    assert_eq!(parse_auth_data(&assertion.auth_data).counter, 2);
+
+    // concurrent assertions never use the same counter.
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let authenticator = sh.webauthn_authenticator(vault_path.clone(), AuthenticatorConfig::new());
+            let request = get_assertion_request(vec![credential_id.clone()]);
+            actix::spawn(async move { authenticator.get_assertion(request).await })
+        })
+        .collect();
+    let mut counters = Vec::new();
+    for task in tasks {
+        counters.push(parse_auth_data(&task.await??.auth_data).counter);
+    }
+    counters.sort_unstable();
+    assert_eq!(counters, (3..11).collect::<Vec<u32>>());
+
+    // credentials of the exclude list are not created twice.
+    let mut request = make_credential_request(b"alice", true);
+    request.exclude_list = vec![credential_id.clone()];
+    let error = authenticator.make_credential(request).await.unwrap_err();
+    assert_eq!(error.status_code(), 0x19);
+
+    // non-discoverable credentials are only used with their ID.
+    let request = make_credential_request(b"bob", false);
+    let bob = authenticator.make_credential(request).await?;
+    let assertion = authenticator.get_assertion(get_assertion_request(vec![])).await?;
+    assert_eq!(assertion.credential_id, credential_id);
+    let assertion = authenticator
+        .get_assertion(get_assertion_request(vec![bob.credential_id.clone()]))
+        .await?;
+    assert_eq!(assertion.credential_id, bob.credential_id);
+    assert_eq!(parse_auth_data(&assertion.auth_data).counter, 1);
+
+    // credentials are bound to their relying party.
+    let mut request = get_assertion_request(vec![credential_id]);
+    request.rp_id = "example.org".into();
+    let error = authenticator.get_assertion(request).await.unwrap_err();
+    assert_eq!(error.status_code(), 0x2e);
+
+    // only EdDSA is supported.
+    let mut request = make_credential_request(b"carol", false);
+    request.pub_key_cred_params = vec![-7];
+    let error = authenticator.make_credential(request).await.unwrap_err();
+    assert_eq!(error.status_code(), 0x26);
+
+    // user verification has to be enabled.
+    let mut request = make_credential_request(b"carol", false);
+    request.user_verification = true;
+    let error = authenticator.make_credential(request.clone()).await.unwrap_err();
+    assert_eq!(error.status_code(), 0x2b);
+    let authenticator = sh.webauthn_authenticator(
+        fresh::bytestring(1024),
+        AuthenticatorConfig::new()
+            .with_aaguid([7; 16])
+            .with_user_verification(true),
+    );
+    let response = authenticator.make_credential(request.clone()).await?;
+    let (auth_data, signed_auth_data) = verify_attestation(&response.attestation_object, &request.client_data_hash);
+    assert_eq!(auth_data.flags, 0x45);
+    assert_eq!(signed_auth_data[37..53], [7; 16]);
+
+    Ok(())
+}
+
+#[actix::test]
+async fn usecase_webauthn_concurrent_registration() -> Result<(), Box<dyn std::error::Error>> {
+    let cp = fresh::bytestring(u8::MAX.into());
+    let sh = Stronghold::init_stronghold_system(cp, vec![]).await?;
+    let vault_path = fresh::bytestring(1024);
+
+    let tasks: Vec<_> = (0..8u8)
+        .map(|i| {
+            let authenticator = sh.webauthn_authenticator(vault_path.clone(), AuthenticatorConfig::new());
+            actix::spawn(async move { authenticator.make_credential(make_credential_request(&[i], true)).await })
+        })
+        .collect();
+    let mut credential_ids = Vec::new();
+    for task in tasks {
+        credential_ids.push(task.await??.credential_id);
+    }
+
+    // none of the concurrent registrations is lost from the credential list of the relying party.
+    let rp_key = [b"webauthn/rp/".as_slice(), &Sha256::digest(RP_ID.as_bytes())].concat();
+    let mut stored: Vec<Vec<u8>> = bincode::deserialize(&sh.read_from_store(rp_key).await?.unwrap())?;
+    stored.sort();
+    credential_ids.sort();
+    assert_eq!(stored, credential_ids);
+
+    Ok(())
+}
@@ -1,7 +1,9 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod cbor;
 mod ids;
+pub mod ssh;
 mod types;
 
 pub use self::{
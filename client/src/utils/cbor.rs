@@ -0,0 +1,61 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Encoding of CBOR data items (RFC 8949).
+//!
+//! Only the types that are needed by WebAuthn are supported. The items of maps are written in the order of the
+//! caller, which has to follow the canonical order of CTAP2 (shorter keys first, then bytewise lexical order).
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_MAP: u8 = 5;
+
+/// Append an integer.
+pub fn put_int(buf: &mut Vec<u8>, value: i64) {
+    if value < 0 {
+        // -1 - n is encoded as n.
+        put_header(buf, MAJOR_NEGATIVE, !value as u64);
+    } else {
+        put_header(buf, MAJOR_UNSIGNED, value as u64);
+    }
+}
+
+/// Append a byte string.
+pub fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_header(buf, MAJOR_BYTES, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Append a text string.
+pub fn put_text(buf: &mut Vec<u8>, text: &str) {
+    put_header(buf, MAJOR_TEXT, text.len() as u64);
+    buf.extend_from_slice(text.as_bytes());
+}
+
+/// Append the header of a map with `len` key-value pairs, which have to follow.
+pub fn put_map(buf: &mut Vec<u8>, len: usize) {
+    put_header(buf, MAJOR_MAP, len as u64);
+}
+
+/// Append the initial byte and the argument in the shortest form.
+fn put_header(buf: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    match argument {
+        0..=23 => buf.push(major | argument as u8),
+        24..=0xff => buf.extend_from_slice(&[major | 24, argument as u8]),
+        0x100..=0xffff => {
+            buf.push(major | 25);
+            buf.extend_from_slice(&(argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            buf.push(major | 26);
+            buf.extend_from_slice(&(argument as u32).to_be_bytes());
+        }
+        _ => {
+            buf.push(major | 27);
+            buf.extend_from_slice(&argument.to_be_bytes());
+        }
+    }
+}
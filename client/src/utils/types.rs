@@ -1,7 +1,10 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::procedures::ProcedureKind;
+use engine::vault::RecordPolicy;
 use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
 
 /// A `Location` type used to specify where in the `Stronghold` a piece of data should be stored. A generic location
 /// specifies a non-versioned location while a counter location specifies a versioned location. The Counter location can
@@ -9,15 +12,27 @@ use serde::{Deserialize, Serialize};
 /// are referenced through their associated index.  On Read, the `None` location is the latest record in the version
 /// chain while on Write, the `None` location is the next record in the version chain.
 ///
+/// Each vault keeps the head of its version chain. A new record that is written to the head of the chain, or to a
+/// counter after it, is linked to the previous head and becomes the new head. Revoking a version ends the chain before
+/// that version, see [`Stronghold::rollback_to`]. Counters of revoked records are not reused.
+///
+/// [`Stronghold::rollback_to`]: crate::Stronghold::rollback_to
+///
 /// **Note: For each used vault an encryption key is created and protected through the [libsodium](https://doc.libsodium.org/memory_management)
 /// memory protection API. Many systems place limits on the amount of memory that may be locked by a process, which may
 /// result in the system panicking if the upper bound is reached!
 /// For users that write a large number of secrets into Stronghold, we strongly advise against writing each record in a
 /// separate vault, but instead group them into a limited number of different vaults.**
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Location {
-    Generic { vault_path: Vec<u8>, record_path: Vec<u8> },
-    Counter { vault_path: Vec<u8>, counter: usize },
+    Generic {
+        vault_path: Vec<u8>,
+        record_path: Vec<u8>,
+    },
+    Counter {
+        vault_path: Vec<u8>,
+        counter: Option<usize>,
+    },
 }
 
 impl Location {
@@ -38,14 +53,22 @@ impl Location {
     }
 
     /// Creates a counter location from a type that implements [`Into<Vec<u8>>`] and a counter type that implements
-    /// [`Into<usize>`]
-    pub fn counter<V: Into<Vec<u8>>, C: Into<usize>>(vault_path: V, counter: C) -> Self {
+    /// [`Into<Option<usize>>`]
+    pub fn counter<V: Into<Vec<u8>>, C: Into<Option<usize>>>(vault_path: V, counter: C) -> Self {
         Self::Counter {
             vault_path: vault_path.into(),
             counter: counter.into(),
         }
     }
 
+    /// Creates a counter location that refers to the head of the version chain in the vault.
+    pub fn counter_head<V: Into<Vec<u8>>>(vault_path: V) -> Self {
+        Self::Counter {
+            vault_path: vault_path.into(),
+            counter: None,
+        }
+    }
+
     /// Used to generate a constant generic location.
     pub const fn const_generic(vault_path: Vec<u8>, record_path: Vec<u8>) -> Self {
         Self::Generic {
@@ -56,7 +79,10 @@ impl Location {
 
     /// used to generate a constant counter location.
     pub const fn const_counter(vault_path: Vec<u8>, counter: usize) -> Self {
-        Self::Counter { vault_path, counter }
+        Self::Counter {
+            vault_path,
+            counter: Some(counter),
+        }
     }
 }
 
@@ -75,6 +101,48 @@ pub enum StrongholdFlags {
     IsReadable(bool),
 }
 
-/// Policy options for for a specific vault.  Must be specified on creation.
-#[derive(Clone, Debug)]
-pub enum VaultFlags {}
+/// Policy options for a specific record.  Must be specified on creation of the record.
+///
+/// The options are combined into the policy of the record, which is stored and persisted with the record. Before the
+/// secret of the record is used by a [`StrongholdProcedure`], the policy is checked against the [`ProcedureKind`] of
+/// the procedure. Outside of procedures, restricted records can not be accessed at all.
+///
+/// Records that are derived or copied from a record only inherit the denied [`ProcedureKind::EXPORTING`] kinds of its
+/// policy, so a record that is only meant for specific procedures should deny [`ProcedureKind::CopyRecord`], which
+/// is already the case for [`VaultFlags::AllowOnly`].
+///
+/// [`StrongholdProcedure`]: crate::procedures::StrongholdProcedure
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum VaultFlags {
+    /// The record may only be used by the listed procedures.
+    AllowOnly(Vec<ProcedureKind>),
+    /// The record may not be used by the procedure.
+    Deny(ProcedureKind),
+    /// The record may be used at most the given number of times.  It is revoked after its last use.
+    MaxUses(u64),
+    /// The record may be used until the given time.  Afterwards it is treated as revoked.
+    UsableUntil(SystemTime),
+    /// The secret of the record may not be copied or exported, see [`ProcedureKind::EXPORTING`].
+    NonExportable,
+}
+
+impl VaultFlags {
+    /// Combines the flags into the [`RecordPolicy`] of a record.  The most restrictive option wins.
+    pub(crate) fn policy(flags: &[VaultFlags]) -> RecordPolicy {
+        flags.iter().fold(RecordPolicy::default(), |mut policy, flag| {
+            match flag {
+                VaultFlags::AllowOnly(kinds) => {
+                    let allowed = kinds.iter().fold(0, |allowed, kind| allowed | kind.usage());
+                    policy.denied |= !allowed;
+                }
+                VaultFlags::Deny(kind) => policy.denied |= kind.usage(),
+                VaultFlags::NonExportable => policy.denied |= ProcedureKind::export_usage(),
+                VaultFlags::MaxUses(uses) => policy.uses = Some(policy.uses.map_or(*uses, |u| u.min(*uses))),
+                VaultFlags::UsableUntil(time) => {
+                    policy.expires = Some(policy.expires.map_or(*time, |t| t.min(*time)));
+                }
+            }
+            policy
+        })
+    }
+}
@@ -15,18 +15,29 @@
 //! bytestring to further protect the offline snapshot files (one might consider
 //! using a secondary user password strengthened by an HSM).
 //!
+//! If the key is derived from a password, the salt and the Argon2id cost parameters
+//! are stored in the header of the snapshot, so that the key can be re-derived from
+//! the password alone.
+//!
 //! The current version of the format is using X25519 together with an ephemeral
 //! key to derive a shared key for the symmetric XChaCha20 cipher and uses the
 //! Poly1305 message authentication algorithm.
-
-//! Future versions, when the demands for larger snapshot sizes and/or random
-//! access is desired, might consider encrypting smaller chunks (B-trees?) or
-//! similar using per chunk derived ephemeral keys.
+//!
+//! The plaintext is compressed and encrypted in segments of 64 KiB following the
+//! STREAM construction, so that snapshots can be written and read through the
+//! [`SnapshotWriter`] and [`SnapshotReader`] adapters without holding the whole
+//! plaintext in a single buffer. Snapshots of the former versions, that encrypt
+//! the plaintext as a whole, are read and upgraded to the current version with
+//! the migrations that are registered for each former version.
 
 mod compression;
 pub mod files;
 pub mod kdf;
 
 mod logic;
+mod migration;
+mod stream;
 pub use compression::{compress, decompress, Lz4DecodeError};
 pub use logic::*;
+pub use migration::{supported_versions, VERSION_2, VERSION_3};
+pub use stream::{SnapshotReader, SnapshotWriter, SEGMENT_SIZE};
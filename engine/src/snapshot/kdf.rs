@@ -1,8 +1,252 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+use argon2::{Algorithm, Argon2, Params, Version};
+use crypto::utils::rand;
+use thiserror::Error as DeriveError;
+
+use crate::snapshot::Key;
+
+/// Length of the random salt used for the password based key derivation.
+pub const SALT_LENGTH: usize = 32;
+
+/// Length of the serialized [`KdfParams`] inside of the snapshot header.
+pub const KDF_HEADER_LENGTH: usize = 1 + 3 * 4 + SALT_LENGTH;
+
+/// Default Argon2id memory cost in KiB (19 MiB).
+pub const DEFAULT_MEM_COST: u32 = 19 * 1024;
+
+/// Default number of Argon2id passes over the memory.
+pub const DEFAULT_TIME_COST: u32 = 2;
+
+/// Default Argon2id degree of parallelism.
+pub const DEFAULT_PARALLELISM: u32 = 1;
+
+/// Upper bound for the memory cost accepted from a snapshot header (1 GiB). The header is not authenticated before
+/// the key is derived, so a crafted file can make the reader allocate up to this amount of memory, but not more.
+pub const MAX_MEM_COST: u32 = 1024 * 1024;
+
+/// Upper bound for the number of passes accepted from a snapshot header, so that a crafted file can not stall the
+/// reader before the key can be checked.
+pub const MAX_TIME_COST: u32 = 64;
+
+/// Upper bound for the degree of parallelism accepted from a snapshot header.
+pub const MAX_PARALLELISM: u32 = 64;
+
+const KDF_NONE: u8 = 0x0;
+const KDF_ARGON2ID: u8 = 0x1;
+
+#[derive(Debug, DeriveError)]
+pub enum KdfError {
+    #[error("generating random bytes failed: {0}")]
+    GenerateRandom(String),
+
+    #[error("invalid key derivation parameters: {0}")]
+    InvalidParams(String),
+
+    #[error("snapshot key was not derived from a password")]
+    NotPasswordProtected,
+}
+
+/// Parameters of the key derivation that was used to create the snapshot key. They are stored in the header of a
+/// snapshot file so that the key can be re-derived from the password alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfParams {
+    /// The snapshot is encrypted with a raw [`Key`] provided by the user.
+    None,
+
+    /// The snapshot key is derived from a password with Argon2id.
+    Argon2id {
+        salt: [u8; SALT_LENGTH],
+        /// Memory cost in KiB.
+        mem_cost: u32,
+        /// Number of passes.
+        time_cost: u32,
+        /// Degree of parallelism.
+        parallelism: u32,
+    },
+}
+
+impl KdfParams {
+    /// Create new Argon2id parameters with the default costs and a fresh random salt.
+    pub fn argon2id() -> Result<Self, KdfError> {
+        Self::argon2id_with_costs(DEFAULT_MEM_COST, DEFAULT_TIME_COST, DEFAULT_PARALLELISM)
+    }
+
+    /// Create new Argon2id parameters with the specified costs and a fresh random salt.
+    pub fn argon2id_with_costs(mem_cost: u32, time_cost: u32, parallelism: u32) -> Result<Self, KdfError> {
+        let mut salt = [0u8; SALT_LENGTH];
+        rand::fill(&mut salt).map_err(|e| KdfError::GenerateRandom(format!("{}", e)))?;
+
+        let params = KdfParams::Argon2id {
+            salt,
+            mem_cost,
+            time_cost,
+            parallelism,
+        };
+        params.argon2()?;
+
+        Ok(params)
+    }
+
+    /// Derive the snapshot [`Key`] from the password.
+    pub fn derive_key(&self, password: &[u8]) -> Result<Key, KdfError> {
+        let salt = match self {
+            KdfParams::None => return Err(KdfError::NotPasswordProtected),
+            KdfParams::Argon2id { salt, .. } => salt,
+        };
+
+        let mut key: Key = [0u8; 32];
+        self.argon2()?
+            .hash_password_into(password, salt, &mut key)
+            .map_err(|e| KdfError::InvalidParams(format!("{}", e)))?;
+
+        Ok(key)
+    }
+
+    /// Serialize the parameters into their fixed-size header representation.
+    pub fn to_bytes(&self) -> [u8; KDF_HEADER_LENGTH] {
+        let mut bytes = [0u8; KDF_HEADER_LENGTH];
+
+        if let KdfParams::Argon2id {
+            salt,
+            mem_cost,
+            time_cost,
+            parallelism,
+        } = self
+        {
+            bytes[0] = KDF_ARGON2ID;
+            bytes[1..5].copy_from_slice(&mem_cost.to_le_bytes());
+            bytes[5..9].copy_from_slice(&time_cost.to_le_bytes());
+            bytes[9..13].copy_from_slice(&parallelism.to_le_bytes());
+            bytes[13..].copy_from_slice(salt);
+        }
+
+        bytes
+    }
+
+    /// Parse the parameters from their header representation.
+    pub fn from_bytes(bytes: &[u8; KDF_HEADER_LENGTH]) -> Result<Self, KdfError> {
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().expect("slice has length 4"));
+
+        match bytes[0] {
+            KDF_NONE if bytes[1..].iter().all(|b| *b == 0) => Ok(KdfParams::None),
+            KDF_NONE => Err(KdfError::InvalidParams("unexpected parameters for raw key".into())),
+            KDF_ARGON2ID => {
+                let mut salt = [0u8; SALT_LENGTH];
+                salt.copy_from_slice(&bytes[13..]);
+
+                let params = KdfParams::Argon2id {
+                    salt,
+                    mem_cost: u32_at(1),
+                    time_cost: u32_at(5),
+                    parallelism: u32_at(9),
+                };
+                params.argon2()?;
+
+                Ok(params)
+            }
+            other => Err(KdfError::InvalidParams(format!("unknown algorithm `{}`", other))),
+        }
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>, KdfError> {
+        match *self {
+            KdfParams::None => Err(KdfError::NotPasswordProtected),
+            KdfParams::Argon2id {
+                mem_cost,
+                time_cost,
+                parallelism,
+                ..
+            } => {
+                check_cost("memory cost", mem_cost, MAX_MEM_COST)?;
+                check_cost("time cost", time_cost, MAX_TIME_COST)?;
+                check_cost("parallelism", parallelism, MAX_PARALLELISM)?;
+                let params = Params::new(mem_cost, time_cost, parallelism, Some(32))
+                    .map_err(|e| KdfError::InvalidParams(format!("{}", e)))?;
+                Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+            }
+        }
+    }
+}
+
+fn check_cost(name: &str, value: u32, max: u32) -> Result<(), KdfError> {
+    if value > max {
+        return Err(KdfError::InvalidParams(format!(
+            "{} {} exceeds the maximum of {}",
+            name, value, max
+        )));
+    }
+    Ok(())
+}
+
 /// a wrapper around the [`HMAC_SHA256`][crypto::macs::hmac::HMAC_SHA256] function used to derive a hash from a given
 /// password.
+///
+/// **Note**: this is not a memory-hard function and should not be used to derive snapshot keys from user passwords.
+/// Use a password based snapshot (see [`write_to_with_password`][crate::snapshot::write_to_with_password]) instead,
+/// which stores the salt and the Argon2id parameters in the snapshot header.
 pub fn naive_kdf(password: &[u8], salt: &[u8; 32], key: &mut [u8; 32]) {
     crypto::macs::hmac::HMAC_SHA256(password, salt, key);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cheap_params() -> KdfParams {
+        KdfParams::argon2id_with_costs(64, 1, 1).unwrap()
+    }
+
+    #[test]
+    fn test_params_roundtrip() {
+        let params = cheap_params();
+        let parsed = KdfParams::from_bytes(&params.to_bytes()).unwrap();
+        assert_eq!(params, parsed);
+
+        let none = KdfParams::from_bytes(&KdfParams::None.to_bytes()).unwrap();
+        assert_eq!(none, KdfParams::None);
+    }
+
+    #[test]
+    fn test_derive_key() {
+        let params = cheap_params();
+        let key0 = params.derive_key(b"password").unwrap();
+        let key1 = params.derive_key(b"password").unwrap();
+        let key2 = params.derive_key(b"passw0rd").unwrap();
+        assert_eq!(key0, key1);
+        assert_ne!(key0, key2);
+
+        // a different salt must result in a different key
+        let other = cheap_params().derive_key(b"password").unwrap();
+        assert_ne!(key0, other);
+
+        assert!(matches!(
+            KdfParams::None.derive_key(b"password"),
+            Err(KdfError::NotPasswordProtected)
+        ));
+    }
+
+    #[test]
+    fn test_reject_invalid_params() {
+        let mut bytes = cheap_params().to_bytes();
+        bytes[1..5].copy_from_slice(&(MAX_MEM_COST + 1).to_le_bytes());
+        assert!(KdfParams::from_bytes(&bytes).is_err());
+
+        let mut bytes = cheap_params().to_bytes();
+        bytes[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(KdfParams::from_bytes(&bytes), Err(KdfError::InvalidParams(_))));
+
+        let mut bytes = cheap_params().to_bytes();
+        bytes[9..13].copy_from_slice(&(MAX_PARALLELISM + 1).to_le_bytes());
+        assert!(matches!(KdfParams::from_bytes(&bytes), Err(KdfError::InvalidParams(_))));
+
+        let mut bytes = cheap_params().to_bytes();
+        bytes[0] = 0xff;
+        assert!(KdfParams::from_bytes(&bytes).is_err());
+
+        let mut bytes = KdfParams::None.to_bytes();
+        bytes[20] = 0x1;
+        assert!(KdfParams::from_bytes(&bytes).is_err());
+    }
+}
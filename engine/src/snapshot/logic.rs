@@ -2,27 +2,29 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::{
-    fs::{rename, File, OpenOptions},
+    fs::{remove_file, rename, File, OpenOptions},
     io::{Read, Write},
     path::Path,
 };
 
 use crypto::{
     ciphers::{chacha::XChaCha20Poly1305, traits::Aead},
-    hashes::{blake2b, Digest},
     keys::x25519,
     utils::rand,
 };
 use thiserror::Error as DeriveError;
 
-use crate::snapshot::{compress, decompress};
+use crate::snapshot::{
+    kdf::{KdfParams, KDF_HEADER_LENGTH},
+    migration,
+    stream::{nonce, SnapshotReader, SnapshotWriter},
+};
 
 /// Magic bytes (bytes 0-4 in a snapshot file) aka PARTI
 pub const MAGIC: [u8; 5] = [0x50, 0x41, 0x52, 0x54, 0x49];
 
 /// Current version bytes (bytes 5-6 in a snapshot file)
-pub const VERSION: [u8; 2] = [0x2, 0x0];
-// pub const OLD_VERSION: [u8; 2] = [0x2, 0x0];
+pub const VERSION: [u8; 2] = [0x4, 0x0];
 
 /// Key size for the ephemeral key
 const KEY_SIZE: usize = 32;
@@ -61,95 +63,197 @@ pub enum WriteError {
     CorruptedData(String),
 }
 
-/// Encrypt the opaque plaintext bytestring using the specified [`Key`] and optional associated data
-/// and writes the ciphertext to the specifed output
-pub fn write<O: Write>(plain: &[u8], output: &mut O, key: &Key, associated_data: &[u8]) -> Result<(), WriteError> {
-    // write magic and version bytes
-    output.write_all(&MAGIC)?;
-    output.write_all(&VERSION)?;
+/// Secret from which the snapshot key is obtained.
+#[derive(Clone, Copy)]
+pub(crate) enum Secret<'a> {
+    Key(&'a Key),
+    Password(&'a [u8]),
+}
 
-    // create ephemeral key pair.
-    let ephemeral_key = x25519::SecretKey::generate().map_err(|e| WriteError::GenerateRandom(format!("{}", e)))?;
+impl Secret<'_> {
+    pub(crate) fn key(&self, params: &KdfParams) -> Result<Key, ReadError> {
+        match self {
+            Secret::Key(key) => Ok(**key),
+            Secret::Password(password) => params
+                .derive_key(password)
+                .map_err(|e| ReadError::CorruptedContent(format!("Key derivation failed: {}", e))),
+        }
+    }
+}
 
-    // get public key.
-    let ephemeral_pk = ephemeral_key.public_key();
+/// Create a [`SnapshotWriter`] that encrypts everything written to it using the specified [`Key`] and optional
+/// associated data. The header is written to the output immediately, the stream has to be completed with
+/// [`SnapshotWriter::finish`].
+pub fn writer<O: Write>(output: O, key: &Key, associated_data: &[u8]) -> Result<SnapshotWriter<O>, WriteError> {
+    writer_with_params(output, key, &KdfParams::None, associated_data)
+}
 
-    let ephemeral_pk_bytes = ephemeral_pk.to_bytes();
+/// Create a [`SnapshotWriter`] with a key that is derived from the password with the specified [`KdfParams`]. The
+/// parameters are written into the header, so that [`reader_with_password`] is able to re-derive the key.
+pub fn writer_with_password<O: Write>(
+    output: O,
+    password: &[u8],
+    params: &KdfParams,
+    associated_data: &[u8],
+) -> Result<SnapshotWriter<O>, WriteError> {
+    let key = params
+        .derive_key(password)
+        .map_err(|e| WriteError::CorruptedData(format!("Key derivation failed: {}", e)))?;
+    writer_with_params(output, &key, params, associated_data)
+}
 
-    // write public key into output.
-    output.write_all(&ephemeral_pk_bytes)?;
+fn writer_with_params<O: Write>(
+    mut output: O,
+    key: &Key,
+    params: &KdfParams,
+    associated_data: &[u8],
+) -> Result<SnapshotWriter<O>, WriteError> {
+    // write magic and version bytes
+    output.write_all(&MAGIC)?;
+    output.write_all(&VERSION)?;
 
-    // secret key now expects an array
-    let mut key_bytes = [0u8; x25519::SECRET_KEY_LENGTH];
-    key_bytes.clone_from_slice(key);
+    // write the key derivation parameters.
+    output.write_all(&params.to_bytes())?;
 
-    // get `x25519` secret key from public key.
-    let pk = x25519::SecretKey::from_bytes(key_bytes).public_key();
+    SnapshotWriter::new(output, key, associated_data)
+}
 
-    let pk_bytes = pk.to_bytes();
+/// Encrypt the opaque plaintext bytestring using the specified [`Key`] and optional associated data
+/// and writes the ciphertext to the specifed output
+pub fn write<O: Write>(plain: &[u8], output: &mut O, key: &Key, associated_data: &[u8]) -> Result<(), WriteError> {
+    let mut w = writer(output, key, associated_data)?;
+    w.write_all(plain)?;
+    w.finish()?;
 
-    // do a diffie_hellman exchange to make a shared secret key.
-    let shared = ephemeral_key.diffie_hellman(&pk);
+    Ok(())
+}
 
-    // compute the nonce using the ephemeral keys.
-    let nonce = {
-        let mut i = ephemeral_pk.to_bytes().to_vec();
-        i.extend_from_slice(&pk_bytes);
-        let res = blake2b::Blake2b256::digest(&i).to_vec();
-        let v: Nonce = res[0..NONCE_SIZE].try_into().expect("slice with incorrect length");
-        v
-    };
+/// Derive the snapshot key from the password with the specified [`KdfParams`], encrypt the opaque plaintext
+/// bytestring with it and write the ciphertext to the specified output. The parameters are written into the header, so
+/// that [`read_with_password`] is able to re-derive the key.
+pub fn write_with_password<O: Write>(
+    plain: &[u8],
+    output: &mut O,
+    password: &[u8],
+    params: &KdfParams,
+    associated_data: &[u8],
+) -> Result<(), WriteError> {
+    let mut w = writer_with_password(output, password, params, associated_data)?;
+    w.write_all(plain)?;
+    w.finish()?;
 
-    // create the XChaCha20Poly1305 tag.
-    let mut tag = [0; XChaCha20Poly1305::TAG_LENGTH];
+    Ok(())
+}
 
-    // creates the ciphertext.
-    let mut ct = vec![0; plain.len()];
+/// Create a [`SnapshotReader`] that decrypts the input using the specified [`Key`] and the associated data specified
+/// during encryption. Only snapshots of the current [`VERSION`] can be read as a stream, use [`read`] for older
+/// snapshots.
+pub fn reader<I: Read>(input: I, key: &Key, associated_data: &[u8]) -> Result<SnapshotReader<I>, ReadError> {
+    reader_with_secret(input, Secret::Key(key), associated_data)
+}
 
-    // decrypt the plain text into the ciphertext buffer.
-    XChaCha20Poly1305::try_encrypt(&shared.to_bytes(), &nonce, associated_data, plain, &mut ct, &mut tag)
-        .map_err(|e| WriteError::CorruptedData(format!("Encryption failed: {}", e)))?;
+/// Create a [`SnapshotReader`] that re-derives the key from the password with the parameters stored in the header.
+pub fn reader_with_password<I: Read>(
+    input: I,
+    password: &[u8],
+    associated_data: &[u8],
+) -> Result<SnapshotReader<I>, ReadError> {
+    reader_with_secret(input, Secret::Password(password), associated_data)
+}
 
-    // write tag and ciphertext into the output.
-    output.write_all(&tag)?;
-    output.write_all(&ct)?;
+fn reader_with_secret<I: Read>(
+    mut input: I,
+    secret: Secret,
+    associated_data: &[u8],
+) -> Result<SnapshotReader<I>, ReadError> {
+    let version = check_header(&mut input)?;
+    if version != VERSION {
+        return Err(ReadError::UnsupportedVersion {
+            expected: VERSION,
+            found: version,
+        });
+    }
 
-    Ok(())
+    let params = read_params(&mut input)?;
+    SnapshotReader::new(input, &secret.key(&params)?, associated_data)
 }
 
 /// Read ciphertext from the input, decrypts it using the specified key and the associated data
 /// specified during encryption and returns the plaintext
+///
+/// The payload of snapshots of former versions is upgraded to the current version, so that the plaintext is the same
+/// for every version.
 pub fn read<I: Read>(input: &mut I, key: &Key, associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
+    read_with_secret(input, Secret::Key(key), associated_data)
+}
+
+/// Read ciphertext from the input, re-derive the key from the password with the parameters stored in the header and
+/// decrypt the ciphertext using the associated data specified during encryption. Returns the plaintext.
+pub fn read_with_password<I: Read>(
+    input: &mut I,
+    password: &[u8],
+    associated_data: &[u8],
+) -> Result<Vec<u8>, ReadError> {
+    read_with_secret(input, Secret::Password(password), associated_data)
+}
+
+fn read_with_secret<I: Read>(input: &mut I, secret: Secret, associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
     // check the header for structure.
-    check_header(input)?;
+    let version = check_header(input)?;
+
+    match version {
+        VERSION => {
+            let params = read_params(input)?;
+            let r = SnapshotReader::new(input, &secret.key(&params)?, associated_data)?;
+            read_stream(r, read_to_end)
+        }
+        _ => migration::read_and_upgrade(input, version, secret, associated_data),
+    }
+}
+
+/// Run `f` on the reader and verify that the complete stream was authenticated afterwards. Errors of the reader take
+/// precedence over the error returned by `f`.
+fn read_stream<I, T, F>(mut reader: SnapshotReader<I>, f: F) -> Result<T, ReadError>
+where
+    I: Read,
+    F: FnOnce(&mut dyn Read) -> Result<T, ReadError>,
+{
+    let res = f(&mut reader);
+    if let Some(e) = reader.take_error() {
+        return Err(e);
+    }
+    let t = res?;
+    reader.finish()?;
+
+    Ok(t)
+}
 
+pub(crate) fn read_params(input: &mut dyn Read) -> Result<KdfParams, ReadError> {
+    let mut params = [0u8; KDF_HEADER_LENGTH];
+    input.read_exact(&mut params)?;
+
+    KdfParams::from_bytes(&params).map_err(|e| ReadError::CorruptedContent(format!("{}", e)))
+}
+
+/// Read the single encrypted body of the snapshot versions prior to the chunked format.
+pub(crate) fn read_body(input: &mut dyn Read, key: &Key, associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
     // create ephemeral private key.
     let mut ephemeral_pk = [0; x25519::PUBLIC_KEY_LENGTH];
     // get ephemeral private key from input.
     input.read_exact(&mut ephemeral_pk)?;
 
-    // creating the secret key now expects an array
-    let mut key_bytes = [0u8; x25519::SECRET_KEY_LENGTH];
-    key_bytes.clone_from_slice(key);
-
     // derive public key from ephemeral private key
     let ephemeral_pk = x25519::PublicKey::from_bytes(ephemeral_pk);
 
     // get x25519 key pair from ephemeral private key.
-    let sk = x25519::SecretKey::from_bytes(key_bytes);
+    let sk = x25519::SecretKey::from_bytes(*key);
     let pk = sk.public_key();
 
     // diffie hellman to create the shared secret.
     let shared = sk.diffie_hellman(&ephemeral_pk);
 
     // compute the nonce using the ephemeral keys.
-    let nonce = {
-        let mut i = ephemeral_pk.to_bytes().to_vec();
-        i.extend_from_slice(&pk.to_bytes());
-        let res = blake2b::Blake2b256::digest(&i).to_vec();
-        let v: Nonce = res[0..NONCE_SIZE].try_into().expect("slice with incorrect length");
-        v
-    };
+    let nonce = nonce(&ephemeral_pk, &pk);
 
     // create and read tag from input.
     let mut tag = [0; XChaCha20Poly1305::TAG_LENGTH];
@@ -174,12 +278,64 @@ pub fn read<I: Read>(input: &mut I, key: &Key, associated_data: &[u8]) -> Result
 /// This is achieved by creating a temporary file in the same directory as the specified path (same
 /// filename with a salted suffix). This is currently known to be problematic if the path is a
 /// symlink and/or if the target path resides in a directory without user write permission.
+///
+/// If writing fails, the temporary file is removed and an existing file at the path is left untouched.
 pub fn write_to(plain: &[u8], path: &Path, key: &Key, associated_data: &[u8]) -> Result<(), WriteError> {
+    write_to_stream(path, key, associated_data, |w| Ok(w.write_all(plain)?))
+}
+
+/// Atomically encrypt and [`write_with_password`](fn.write_with_password.html) the specified plaintext to the
+/// specified path. The key is derived from the password with Argon2id using a fresh random salt and the default costs.
+pub fn write_to_with_password(
+    plain: &[u8],
+    path: &Path,
+    password: &[u8],
+    associated_data: &[u8],
+) -> Result<(), WriteError> {
+    write_to_stream_with_password(path, password, associated_data, |w| Ok(w.write_all(plain)?))
+}
+
+/// Atomically write a snapshot to the specified path, `f` writes the plaintext into the encrypting [`Write`] adapter.
+/// The plaintext is never held in a single buffer, see [`write_to`] for the handling of the file.
+pub fn write_to_stream<F>(path: &Path, key: &Key, associated_data: &[u8], f: F) -> Result<(), WriteError>
+where
+    F: FnOnce(&mut dyn Write) -> Result<(), WriteError>,
+{
+    write_atomically(path, |file| {
+        let mut w = writer(file, key, associated_data)?;
+        f(&mut w)?;
+        w.finish()?;
+        Ok(())
+    })
+}
+
+/// Atomically write a snapshot to the specified path with a key that is derived from the password with Argon2id, see
+/// [`write_to_stream`].
+pub fn write_to_stream_with_password<F>(
+    path: &Path,
+    password: &[u8],
+    associated_data: &[u8],
+    f: F,
+) -> Result<(), WriteError>
+where
+    F: FnOnce(&mut dyn Write) -> Result<(), WriteError>,
+{
+    let params = KdfParams::argon2id().map_err(|e| WriteError::GenerateRandom(format!("{}", e)))?;
+    write_atomically(path, |file| {
+        let mut w = writer_with_password(file, password, &params, associated_data)?;
+        f(&mut w)?;
+        w.finish()?;
+        Ok(())
+    })
+}
+
+fn write_atomically<F>(path: &Path, f: F) -> Result<(), WriteError>
+where
+    F: FnOnce(&mut File) -> Result<(), WriteError>,
+{
     // TODO: if path exists and is a symlink, resolve it and then append the salt
     // TODO: if the sibling tempfile isn't writeable (e.g. directory permissions), write to
 
-    let compressed_plain = compress(plain);
-
     let mut salt = [0u8; 6];
     rand::fill(&mut salt).map_err(|e| WriteError::GenerateRandom(format!("{}", e)))?;
 
@@ -188,22 +344,77 @@ pub fn write_to(plain: &[u8], path: &Path, key: &Key, associated_data: &[u8]) ->
     s.push(hex::encode(salt));
     let tmp = Path::new(&s);
 
-    let mut f = OpenOptions::new().write(true).create_new(true).open(tmp)?;
-    write(&compressed_plain, &mut f, key, associated_data)?;
-    f.sync_all()?;
+    let mut file = OpenOptions::new().write(true).create_new(true).open(tmp)?;
+    let res = f(&mut file)
+        .and_then(|_| file.sync_all().map_err(WriteError::from))
+        .and_then(|_| rename(tmp, path).map_err(WriteError::from));
 
-    rename(tmp, path)?;
+    if res.is_err() {
+        let _ = remove_file(tmp);
+    }
 
-    Ok(())
+    res
 }
 
 /// [`read`](fn.read.html) and decrypt the ciphertext from the specified path
 pub fn read_from(path: &Path, key: &Key, associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
-    let mut f: File = OpenOptions::new().read(true).open(path)?;
-    check_min_file_len(&mut f)?;
-    let pt = read(&mut f, key, associated_data)?;
+    read_from_stream(path, key, associated_data, read_to_end)
+}
 
-    decompress(&pt).map_err(|e| ReadError::CorruptedContent(format!("Decompression failed: {}", e)))
+/// [`read_with_password`](fn.read_with_password.html) and decrypt the ciphertext from the specified path
+pub fn read_from_with_password(path: &Path, password: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
+    read_from_stream_with_password(path, password, associated_data, read_to_end)
+}
+
+/// Read a snapshot from the specified path, `f` reads the plaintext from the decrypting [`Read`] adapter. The
+/// stream is verified to be complete after `f` returned.
+///
+/// Snapshots of former versions are decrypted into a single buffer first and upgraded to the current version with the
+/// registered migrations.
+pub fn read_from_stream<T, F>(path: &Path, key: &Key, associated_data: &[u8], f: F) -> Result<T, ReadError>
+where
+    F: FnOnce(&mut dyn Read) -> Result<T, ReadError>,
+{
+    read_file(path, Secret::Key(key), associated_data, f)
+}
+
+/// Read a snapshot from the specified path with a key that is re-derived from the password, see
+/// [`read_from_stream`].
+pub fn read_from_stream_with_password<T, F>(
+    path: &Path,
+    password: &[u8],
+    associated_data: &[u8],
+    f: F,
+) -> Result<T, ReadError>
+where
+    F: FnOnce(&mut dyn Read) -> Result<T, ReadError>,
+{
+    read_file(path, Secret::Password(password), associated_data, f)
+}
+
+fn read_file<T, F>(path: &Path, secret: Secret, associated_data: &[u8], f: F) -> Result<T, ReadError>
+where
+    F: FnOnce(&mut dyn Read) -> Result<T, ReadError>,
+{
+    let mut file: File = OpenOptions::new().read(true).open(path)?;
+    check_min_file_len(&mut file)?;
+
+    let version = check_header(&mut file)?;
+    if version == VERSION {
+        let params = read_params(&mut file)?;
+        let r = SnapshotReader::new(file, &secret.key(&params)?, associated_data)?;
+        return read_stream(r, f);
+    }
+
+    let pt = migration::read_and_upgrade(&mut file, version, secret, associated_data)?;
+
+    f(&mut pt.as_slice())
+}
+
+fn read_to_end(input: &mut dyn Read) -> Result<Vec<u8>, ReadError> {
+    let mut pt = Vec::new();
+    input.read_to_end(&mut pt)?;
+    Ok(pt)
 }
 
 fn check_min_file_len(input: &mut File) -> Result<(), ReadError> {
@@ -215,8 +426,9 @@ fn check_min_file_len(input: &mut File) -> Result<(), ReadError> {
     }
 }
 
-/// Checks the header for a specific structure; explicitly the magic and version bytes.
-fn check_header<I: Read>(input: &mut I) -> Result<(), ReadError> {
+/// Checks the header for a specific structure; explicitly the magic and version bytes. Returns the version of the
+/// snapshot format.
+fn check_header<I: Read>(input: &mut I) -> Result<[u8; 2], ReadError> {
     // check the magic bytes
     let mut magic = [0u8; 5];
     input.read_exact(&mut magic)?;
@@ -228,14 +440,14 @@ fn check_header<I: Read>(input: &mut I) -> Result<(), ReadError> {
     let mut version = [0u8; 2];
     input.read_exact(&mut version)?;
 
-    if version != VERSION {
+    if !migration::is_supported(version) {
         return Err(ReadError::UnsupportedVersion {
             expected: VERSION,
             found: version,
         });
     }
 
-    Ok(())
+    Ok(version)
 }
 
 #[cfg(test)]
@@ -328,6 +540,127 @@ mod test {
         assert_eq!(bs0, bs1);
     }
 
+    #[test]
+    fn test_snapshot_failed_write() {
+        let f = tempfile::tempdir().unwrap();
+        let pb = f.path().join("snapshot");
+
+        let key: Key = random_key();
+        let bs0 = random_bytestring();
+        let ad = random_bytestring();
+        write_to(&bs0, &pb, &key, &ad).unwrap();
+
+        let res = write_to_stream(&pb, &random_key(), &ad, |w| {
+            w.write_all(&random_bytestring())?;
+            Err(WriteError::CorruptedData("Serialization failed.".into()))
+        });
+        assert!(res.is_err());
+
+        // the former snapshot is untouched and the temporary file was removed.
+        assert_eq!(read_from(&pb, &key, &ad).unwrap(), bs0);
+        assert_eq!(std::fs::read_dir(f.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_password_write_read() {
+        let params = KdfParams::argon2id_with_costs(64, 1, 1).unwrap();
+        let bs0 = random_bytestring();
+        let ad = random_bytestring();
+
+        let mut buf = Vec::new();
+        write_with_password(&bs0, &mut buf, b"password", &params, &ad).unwrap();
+
+        let bs1 = read_with_password(&mut buf.as_slice(), b"password", &ad).unwrap();
+        assert_eq!(bs0, bs1);
+
+        // the derived key can be used directly as well.
+        let key = params.derive_key(b"password").unwrap();
+        let bs2 = read(&mut buf.as_slice(), &key, &ad).unwrap();
+        assert_eq!(bs0, bs2);
+
+        assert!(read_with_password(&mut buf.as_slice(), b"wrong password", &ad).is_err());
+    }
+
+    #[test]
+    fn test_password_snapshot() {
+        let f = tempfile::tempdir().unwrap();
+        let pb = f.path().join("snapshot");
+
+        let bs0 = random_bytestring();
+        let ad = random_bytestring();
+
+        write_to_with_password(&bs0, &pb, b"password", &ad).unwrap();
+        let bs1 = read_from_with_password(&pb, b"password", &ad).unwrap();
+        assert_eq!(bs0, bs1);
+    }
+
+    #[test]
+    fn test_password_oversized_params() {
+        let params = KdfParams::argon2id_with_costs(64, 1, 1).unwrap();
+        let bs0 = random_bytestring();
+        let ad = random_bytestring();
+
+        let mut buf = Vec::new();
+        write_with_password(&bs0, &mut buf, b"password", &params, &ad).unwrap();
+
+        // overwrite the time cost in the header, the reader must refuse it before running the key derivation.
+        let offset = MAGIC.len() + VERSION.len() + 5;
+        buf[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            read_with_password(&mut buf.as_slice(), b"password", &ad),
+            Err(ReadError::CorruptedContent(_))
+        ));
+    }
+
+    #[test]
+    fn test_password_read_key_snapshot() {
+        let key: Key = random_key();
+        let bs0 = random_bytestring();
+        let ad = random_bytestring();
+
+        let mut buf = Vec::new();
+        write(&bs0, &mut buf, &key, &ad).unwrap();
+        assert!(read_with_password(&mut buf.as_slice(), b"password", &ad).is_err());
+    }
+
+    #[test]
+    fn test_stream_snapshot() {
+        let f = tempfile::tempdir().unwrap();
+        let pb = f.path().join("snapshot");
+
+        let key: Key = random_key();
+        let ad = random_bytestring();
+        let data: Vec<Vec<u8>> = (0..64).map(|_| random::bytestring(4 * 4096)).collect();
+
+        write_to_stream(&pb, &key, &ad, |w| {
+            for d in &data {
+                w.write_all(&(d.len() as u32).to_le_bytes())?;
+                w.write_all(d)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        let read = read_from_stream(&pb, &key, &ad, |r| {
+            let mut read = Vec::new();
+            for _ in 0..data.len() {
+                let mut len = [0u8; 4];
+                r.read_exact(&mut len)?;
+                let mut d = vec![0u8; u32::from_le_bytes(len) as usize];
+                r.read_exact(&mut d)?;
+                read.push(d);
+            }
+            Ok(read)
+        })
+        .unwrap();
+        assert_eq!(data, read);
+
+        // the remaining segments are verified, even if the stream is not consumed completely.
+        assert!(read_from_stream(&pb, &key, &ad, |r| Ok(r.read_exact(&mut [0u8; 4])?)).is_ok());
+        let res = read_from_stream(&pb, &random_key(), &ad, |r| Ok(r.read_exact(&mut [0u8; 4])?));
+        assert!(matches!(res, Err(ReadError::CorruptedContent(_))));
+    }
+
     struct TestVector {
         key: &'static str,
         ad: &'static str,
@@ -371,7 +704,10 @@ mod test {
             let data = hex::decode(tv.data).unwrap();
             let snapshot = hex::decode(tv.snapshot).unwrap();
 
-            let pt = read(&mut snapshot.as_slice(), &key, &ad).unwrap();
+            // the vectors were written by the raw encryption of version 2, so the payload is read without upgrading it.
+            let mut input = snapshot.as_slice();
+            let version = check_header(&mut input).unwrap();
+            let pt = migration::read_payload(&mut input, version, Secret::Key(&key), &ad).unwrap();
 
             assert_eq!(pt, data);
         }
@@ -0,0 +1,229 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Registry of the historic snapshot layouts.
+//!
+//! Each former version of the snapshot format is registered with a function that reads and decrypts its payload and
+//! a function that upgrades the payload to the payload of the next version. Reading a snapshot of a former version
+//! applies all upgrades in order, so that the caller always receives the payload of the current [`VERSION`]. Writing
+//! the snapshot again stores it in the current format.
+
+use std::io::Read;
+
+use crate::snapshot::{
+    decompress,
+    logic::{read_body, read_params, Secret},
+    ReadError, VERSION,
+};
+
+/// Reads and decrypts the payload of a layout, starting after the magic and version bytes.
+type ReadFn = fn(&mut dyn Read, Secret, &[u8]) -> Result<Vec<u8>, ReadError>;
+
+/// Upgrades the payload of a layout to the payload of the next version.
+type UpgradeFn = fn(Vec<u8>) -> Result<Vec<u8>, ReadError>;
+
+/// A former version of the snapshot format.
+pub(crate) struct Migration {
+    /// Version bytes of the layout.
+    pub version: [u8; 2],
+    read: ReadFn,
+    upgrade: UpgradeFn,
+}
+
+/// Version bytes of the snapshot format with the key derivation header and a single encrypted body.
+pub const VERSION_3: [u8; 2] = [0x3, 0x0];
+
+/// Version bytes of the snapshot format without the key derivation header.
+pub const VERSION_2: [u8; 2] = [0x2, 0x0];
+
+/// All former versions of the snapshot format, ordered from the oldest to the newest one.
+pub(crate) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: VERSION_2,
+        read: read_v2,
+        upgrade: upgrade_v2,
+    },
+    Migration {
+        version: VERSION_3,
+        read: read_v3,
+        upgrade: upgrade_v3,
+    },
+];
+
+/// Returns all snapshot versions that can be read, ordered from the oldest to the current one.
+pub fn supported_versions() -> Vec<[u8; 2]> {
+    MIGRATIONS.iter().map(|m| m.version).chain(Some(VERSION)).collect()
+}
+
+/// Checks if snapshots of the version can be read.
+pub(crate) fn is_supported(version: [u8; 2]) -> bool {
+    version == VERSION || MIGRATIONS.iter().any(|m| m.version == version)
+}
+
+/// Read the payload of the specified former version without upgrading it.
+pub(crate) fn read_payload(
+    input: &mut dyn Read,
+    version: [u8; 2],
+    secret: Secret,
+    associated_data: &[u8],
+) -> Result<Vec<u8>, ReadError> {
+    let migration = find(version)?;
+    (migration.read)(input, secret, associated_data)
+}
+
+/// Read the payload of the specified former version and upgrade it to the payload of the current version.
+pub(crate) fn read_and_upgrade(
+    input: &mut dyn Read,
+    version: [u8; 2],
+    secret: Secret,
+    associated_data: &[u8],
+) -> Result<Vec<u8>, ReadError> {
+    let mut payload = read_payload(input, version, secret, associated_data)?;
+    for migration in MIGRATIONS.iter().skip_while(|m| m.version != version) {
+        payload = (migration.upgrade)(payload)?;
+    }
+
+    Ok(payload)
+}
+
+fn find(version: [u8; 2]) -> Result<&'static Migration, ReadError> {
+    MIGRATIONS
+        .iter()
+        .find(|m| m.version == version)
+        .ok_or(ReadError::UnsupportedVersion {
+            expected: VERSION,
+            found: version,
+        })
+}
+
+fn read_v2(input: &mut dyn Read, secret: Secret, associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
+    match secret {
+        Secret::Key(key) => read_body(input, key, associated_data),
+        // snapshots of this version do not contain key derivation parameters.
+        Secret::Password(_) => Err(ReadError::UnsupportedVersion {
+            expected: VERSION,
+            found: VERSION_2,
+        }),
+    }
+}
+
+/// Version 3 only added the key derivation header, the payload is unchanged.
+fn upgrade_v2(payload: Vec<u8>) -> Result<Vec<u8>, ReadError> {
+    Ok(payload)
+}
+
+fn read_v3(input: &mut dyn Read, secret: Secret, associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
+    let params = read_params(input)?;
+    read_body(input, &secret.key(&params)?, associated_data)
+}
+
+/// The payload of version 3 is compressed as a whole, version 4 compresses the single segments.
+fn upgrade_v3(payload: Vec<u8>) -> Result<Vec<u8>, ReadError> {
+    decompress(&payload).map_err(|e| ReadError::CorruptedContent(format!("Decompression failed: {}", e)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::snapshot::{
+        compress, kdf::KdfParams, read, read_from, read_from_with_password, read_with_password, stream::nonce, Key,
+        MAGIC,
+    };
+    use crypto::{
+        ciphers::{chacha::XChaCha20Poly1305, traits::Aead},
+        keys::x25519,
+        utils::rand,
+    };
+    use std::{fs, path::Path};
+
+    fn random_key() -> Key {
+        let mut key: Key = [0u8; 32];
+        rand::fill(&mut key).expect("Unable to fill buffer");
+        key
+    }
+
+    /// writes a snapshot in the layout of version 2 or, if params are given, version 3.
+    fn write_legacy(path: &Path, plain: &[u8], key: &Key, params: Option<KdfParams>, ad: &[u8]) {
+        let mut out = MAGIC.to_vec();
+        match params {
+            Some(params) => {
+                out.extend_from_slice(&VERSION_3);
+                out.extend_from_slice(&params.to_bytes());
+            }
+            None => out.extend_from_slice(&VERSION_2),
+        }
+
+        let ephemeral_key = x25519::SecretKey::generate().unwrap();
+        let ephemeral_pk = ephemeral_key.public_key();
+        let pk = x25519::SecretKey::from_bytes(*key).public_key();
+        let shared = ephemeral_key.diffie_hellman(&pk);
+        out.extend_from_slice(&ephemeral_pk.to_bytes());
+
+        let pt = compress(plain);
+        let mut tag = [0; XChaCha20Poly1305::TAG_LENGTH];
+        let mut ct = vec![0; pt.len()];
+        XChaCha20Poly1305::try_encrypt(
+            &shared.to_bytes(),
+            &nonce(&ephemeral_pk, &pk),
+            ad,
+            &pt,
+            &mut ct,
+            &mut tag,
+        )
+        .unwrap();
+        out.extend_from_slice(&tag);
+        out.extend_from_slice(&ct);
+
+        fs::write(path, out).unwrap();
+    }
+
+    #[test]
+    fn test_registry_order() {
+        let versions = supported_versions();
+        assert_eq!(versions.last(), Some(&VERSION));
+
+        // the versions have to be ordered, so that the upgrades are applied in order.
+        let mut sorted = versions.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(versions, sorted);
+    }
+
+    #[test]
+    fn test_migrate_v2() {
+        let f = tempfile::tempdir().unwrap();
+        let pb = f.path().join("snapshot");
+        let key = random_key();
+        let plain = b"snapshot of version 2".repeat(64);
+
+        write_legacy(&pb, &plain, &key, None, b"ad");
+
+        assert_eq!(read_from(&pb, &key, b"ad").unwrap(), plain);
+        assert!(read_from(&pb, &random_key(), b"ad").is_err());
+        assert!(read_from_with_password(&pb, b"password", b"ad").is_err());
+
+        // `read` upgrades the payload like `read_from`, the payload of version 2 is compressed as a whole.
+        let snapshot = fs::read(&pb).unwrap();
+        assert_eq!(read(&mut snapshot.as_slice(), &key, b"ad").unwrap(), plain);
+        let mut body = &snapshot[MAGIC.len() + VERSION_2.len()..];
+        let payload = read_payload(&mut body, VERSION_2, Secret::Key(&key), b"ad").unwrap();
+        assert_eq!(payload, compress(&plain));
+    }
+
+    #[test]
+    fn test_migrate_v3() {
+        let f = tempfile::tempdir().unwrap();
+        let pb = f.path().join("snapshot");
+        let params = KdfParams::argon2id_with_costs(64, 1, 1).unwrap();
+        let key = params.derive_key(b"password").unwrap();
+        let plain = b"snapshot of version 3".repeat(64);
+
+        write_legacy(&pb, &plain, &key, Some(params), &[]);
+
+        assert_eq!(read_from_with_password(&pb, b"password", &[]).unwrap(), plain);
+        assert_eq!(read_from(&pb, &key, &[]).unwrap(), plain);
+        assert!(read_from_with_password(&pb, b"wrong password", &[]).is_err());
+        let payload = read_with_password(&mut fs::read(&pb).unwrap().as_slice(), b"password", &[]).unwrap();
+        assert_eq!(payload, plain);
+    }
+}
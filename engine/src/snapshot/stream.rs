@@ -0,0 +1,423 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Chunked encryption of the snapshot body.
+//!
+//! The plaintext is split into segments of at most [`SEGMENT_SIZE`] bytes, that are compressed and encrypted
+//! independently following the STREAM construction: the nonce of each segment consists of a prefix that is unique per
+//! snapshot, a big-endian segment counter and the flags of the segment. Re-ordering, dropping or truncating segments
+//! therefore results in a decryption failure. Each segment is written as
+//!
+//! ```text
+//! flags (1 byte) | length of the ciphertext (4 bytes, LE) | tag (16 bytes) | ciphertext
+//! ```
+
+use std::io::{self, Read, Write};
+
+use crypto::{
+    ciphers::{chacha::XChaCha20Poly1305, traits::Aead},
+    hashes::{blake2b, Digest},
+    keys::x25519,
+};
+
+use crate::snapshot::{compress, decompress, Key, Nonce, ReadError, WriteError};
+
+/// Maximum size of the plaintext of a single segment (64 KiB).
+pub const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// The last 5 bytes of the nonce are used for the segment counter and the flags.
+const NONCE_PREFIX_LENGTH: usize = XChaCha20Poly1305::NONCE_LENGTH - 5;
+
+type NoncePrefix = [u8; NONCE_PREFIX_LENGTH];
+
+/// Marks the final segment of the stream.
+const FLAG_LAST: u8 = 0x1;
+
+/// Marks a segment whose plaintext was compressed before the encryption.
+const FLAG_COMPRESSED: u8 = 0x2;
+
+/// Length of the unencrypted segment header.
+const SEGMENT_HEADER_LENGTH: usize = 1 + 4 + XChaCha20Poly1305::TAG_LENGTH;
+
+/// Adapter that encrypts everything that is written to it in segments and writes them to the inner writer.
+///
+/// The stream has to be completed with [`SnapshotWriter::finish`], otherwise the final segment is missing and the
+/// snapshot can not be read.
+pub struct SnapshotWriter<W: Write> {
+    inner: W,
+    key: Key,
+    nonce_prefix: NoncePrefix,
+    associated_data: Vec<u8>,
+    counter: u32,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> SnapshotWriter<W> {
+    /// Create a new writer that encrypts with a key that is agreed upon between the snapshot [`Key`] and a fresh
+    /// ephemeral key. The public ephemeral key is written to the output immediately.
+    pub(crate) fn new(mut inner: W, key: &Key, associated_data: &[u8]) -> Result<Self, WriteError> {
+        // create ephemeral key pair.
+        let ephemeral_key = x25519::SecretKey::generate().map_err(|e| WriteError::GenerateRandom(format!("{}", e)))?;
+        let ephemeral_pk = ephemeral_key.public_key();
+
+        // write public key into output.
+        inner.write_all(&ephemeral_pk.to_bytes())?;
+
+        // do a diffie_hellman exchange with the public key of the snapshot key to make a shared secret key.
+        let pk = x25519::SecretKey::from_bytes(*key).public_key();
+        let shared = ephemeral_key.diffie_hellman(&pk);
+        let nonce = nonce(&ephemeral_pk, &pk);
+
+        Ok(Self {
+            inner,
+            key: shared.to_bytes(),
+            nonce_prefix: nonce_prefix(&nonce),
+            associated_data: associated_data.to_vec(),
+            counter: 0,
+            buffer: Vec::with_capacity(SEGMENT_SIZE),
+        })
+    }
+
+    /// Encrypt the remaining data as the final segment and return the inner writer.
+    pub fn finish(mut self) -> Result<W, WriteError> {
+        self.write_segment(true)?;
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+
+    fn write_segment(&mut self, last: bool) -> Result<(), WriteError> {
+        let compressed = compress(&self.buffer);
+        let (mut flags, pt) = if compressed.len() < self.buffer.len() {
+            (FLAG_COMPRESSED, compressed.as_slice())
+        } else {
+            (0, self.buffer.as_slice())
+        };
+        if last {
+            flags |= FLAG_LAST;
+        }
+
+        let nonce = segment_nonce(&self.nonce_prefix, self.counter, flags);
+        let mut tag = [0; XChaCha20Poly1305::TAG_LENGTH];
+        let mut ct = vec![0; pt.len()];
+
+        XChaCha20Poly1305::try_encrypt(&self.key, &nonce, &self.associated_data, pt, &mut ct, &mut tag)
+            .map_err(|e| WriteError::CorruptedData(format!("Encryption failed: {}", e)))?;
+
+        self.inner.write_all(&[flags])?;
+        self.inner.write_all(&(ct.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&tag)?;
+        self.inner.write_all(&ct)?;
+
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| WriteError::CorruptedData("Too many segments.".into()))?;
+        self.buffer.clear();
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for SnapshotWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // a full segment is only written once more data arrives, so that the last segment is never empty.
+        if self.buffer.len() == SEGMENT_SIZE {
+            self.write_segment(false).map_err(|e| match e {
+                WriteError::Io(e) => e,
+                e => io::Error::other(e),
+            })?;
+        }
+
+        let n = buf.len().min(SEGMENT_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Adapter that reads the segments from the inner reader and decrypts them.
+///
+/// Only authenticated plaintext is returned, reading fails if the stream ends before the final segment.
+pub struct SnapshotReader<R: Read> {
+    inner: R,
+    key: Key,
+    nonce_prefix: NoncePrefix,
+    associated_data: Vec<u8>,
+    counter: u32,
+    buffer: Vec<u8>,
+    position: usize,
+    finished: bool,
+    error: Option<ReadError>,
+}
+
+impl<R: Read> SnapshotReader<R> {
+    /// Create a new reader that reads the public ephemeral key from the input and agrees on the decryption key with
+    /// the snapshot [`Key`].
+    pub(crate) fn new(mut inner: R, key: &Key, associated_data: &[u8]) -> Result<Self, ReadError> {
+        // get ephemeral public key from input.
+        let mut ephemeral_pk = [0; x25519::PUBLIC_KEY_LENGTH];
+        inner.read_exact(&mut ephemeral_pk)?;
+        let ephemeral_pk = x25519::PublicKey::from_bytes(ephemeral_pk);
+
+        // diffie hellman to create the shared secret.
+        let sk = x25519::SecretKey::from_bytes(*key);
+        let shared = sk.diffie_hellman(&ephemeral_pk);
+        let nonce = nonce(&ephemeral_pk, &sk.public_key());
+
+        Ok(Self {
+            inner,
+            key: shared.to_bytes(),
+            nonce_prefix: nonce_prefix(&nonce),
+            associated_data: associated_data.to_vec(),
+            counter: 0,
+            buffer: Vec::new(),
+            position: 0,
+            finished: false,
+            error: None,
+        })
+    }
+
+    /// Read the remaining segments, verify that the stream is complete and return the inner reader.
+    pub fn finish(mut self) -> Result<R, ReadError> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        while !self.finished {
+            self.read_segment()?;
+        }
+
+        Ok(self.inner)
+    }
+
+    /// Take the error that caused the last failed read. [`Read`] only allows returning [`io::Error`]s, this keeps
+    /// the original error, e.g. a failed decryption.
+    pub fn take_error(&mut self) -> Option<ReadError> {
+        self.error.take()
+    }
+
+    fn read_segment(&mut self) -> Result<(), ReadError> {
+        let mut header = [0u8; SEGMENT_HEADER_LENGTH];
+        self.inner.read_exact(&mut header).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => ReadError::CorruptedContent("Missing final segment.".into()),
+            _ => ReadError::Io(e),
+        })?;
+
+        let flags = header[0];
+        if flags & !(FLAG_LAST | FLAG_COMPRESSED) != 0 {
+            return Err(ReadError::CorruptedContent(format!("Invalid segment flags: {}", flags)));
+        }
+        let len = u32::from_le_bytes(header[1..5].try_into().expect("slice has length 4")) as usize;
+        if len > SEGMENT_SIZE {
+            return Err(ReadError::CorruptedContent(format!("Invalid segment length: {}", len)));
+        }
+        let tag = &header[5..];
+
+        let mut ct = vec![0; len];
+        self.inner.read_exact(&mut ct)?;
+
+        let nonce = segment_nonce(&self.nonce_prefix, self.counter, flags);
+        let mut pt = vec![0; len];
+
+        XChaCha20Poly1305::try_decrypt(&self.key, &nonce, &self.associated_data, &mut pt, &ct, tag)
+            .map_err(|e| ReadError::CorruptedContent(format!("Decryption failed: {}", e)))?;
+
+        if flags & FLAG_COMPRESSED != 0 {
+            pt = decompress(&pt).map_err(|e| ReadError::CorruptedContent(format!("Decompression failed: {}", e)))?;
+            if pt.len() > SEGMENT_SIZE {
+                return Err(ReadError::CorruptedContent("Segment exceeds the maximum size.".into()));
+            }
+        }
+
+        if flags & FLAG_LAST != 0 {
+            if self.inner.read(&mut [0u8; 1])? != 0 {
+                return Err(ReadError::CorruptedContent(
+                    "Unexpected data after the final segment.".into(),
+                ));
+            }
+            self.finished = true;
+        }
+
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| ReadError::CorruptedContent("Too many segments.".into()))?;
+        self.buffer = pt;
+        self.position = 0;
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for SnapshotReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffer.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            if let Err(e) = self.read_segment() {
+                let err = match &e {
+                    ReadError::Io(io) => io::Error::new(io.kind(), e.to_string()),
+                    _ => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+                };
+                self.error = Some(e);
+                return Err(err);
+            }
+        }
+
+        let n = buf.len().min(self.buffer.len() - self.position);
+        buf[..n].copy_from_slice(&self.buffer[self.position..self.position + n]);
+        self.position += n;
+
+        Ok(n)
+    }
+}
+
+/// compute the nonce using the ephemeral keys.
+pub(crate) fn nonce(ephemeral_pk: &x25519::PublicKey, pk: &x25519::PublicKey) -> Nonce {
+    let mut i = ephemeral_pk.to_bytes().to_vec();
+    i.extend_from_slice(&pk.to_bytes());
+    let res = blake2b::Blake2b256::digest(&i).to_vec();
+    res[0..XChaCha20Poly1305::NONCE_LENGTH]
+        .try_into()
+        .expect("slice with incorrect length")
+}
+
+fn nonce_prefix(nonce: &Nonce) -> NoncePrefix {
+    nonce[..NONCE_PREFIX_LENGTH]
+        .try_into()
+        .expect("slice with incorrect length")
+}
+
+fn segment_nonce(prefix: &NoncePrefix, counter: u32, flags: u8) -> Nonce {
+    let mut nonce = [0u8; XChaCha20Poly1305::NONCE_LENGTH];
+    nonce[..NONCE_PREFIX_LENGTH].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LENGTH..NONCE_PREFIX_LENGTH + 4].copy_from_slice(&counter.to_be_bytes());
+    nonce[NONCE_PREFIX_LENGTH + 4] = flags;
+    nonce
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crypto::utils::rand;
+
+    fn random_key() -> Key {
+        let mut key: Key = [0u8; 32];
+        rand::fill(&mut key).expect("Unable to fill buffer");
+        key
+    }
+
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut bs = vec![0u8; len];
+        rand::fill(&mut bs).expect("Unable to fill buffer");
+        bs
+    }
+
+    fn encrypt(plain: &[u8], key: &Key, ad: &[u8]) -> Vec<u8> {
+        let mut writer = SnapshotWriter::new(Vec::new(), key, ad).unwrap();
+        writer.write_all(plain).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn decrypt(ct: &[u8], key: &Key, ad: &[u8]) -> Result<Vec<u8>, ReadError> {
+        let mut reader = SnapshotReader::new(ct, key, ad)?;
+        let mut pt = Vec::new();
+        if reader.read_to_end(&mut pt).is_err() {
+            return Err(reader.take_error().unwrap());
+        }
+        reader.finish()?;
+        Ok(pt)
+    }
+
+    /// returns the offsets of all segments in the encrypted stream.
+    fn segments(ct: &[u8]) -> Vec<usize> {
+        let mut offsets = Vec::new();
+        let mut i = x25519::PUBLIC_KEY_LENGTH;
+        while i < ct.len() {
+            offsets.push(i);
+            let len = u32::from_le_bytes(ct[i + 1..i + 5].try_into().unwrap()) as usize;
+            i += SEGMENT_HEADER_LENGTH + len;
+        }
+        offsets
+    }
+
+    #[test]
+    fn test_stream_roundtrip() {
+        let key = random_key();
+        let ad = random_bytes(128);
+
+        for size in [
+            0,
+            1,
+            SEGMENT_SIZE - 1,
+            SEGMENT_SIZE,
+            SEGMENT_SIZE + 1,
+            3 * SEGMENT_SIZE + 17,
+        ] {
+            let plain = random_bytes(size);
+            let ct = encrypt(&plain, &key, &ad);
+            assert_eq!(segments(&ct).len(), size.max(1).div_ceil(SEGMENT_SIZE));
+            assert_eq!(decrypt(&ct, &key, &ad).unwrap(), plain);
+        }
+
+        // compressible data is compressed per segment.
+        let plain = vec![0x42; 4 * SEGMENT_SIZE];
+        let ct = encrypt(&plain, &key, &ad);
+        assert!(ct.len() < SEGMENT_SIZE);
+        assert_eq!(decrypt(&ct, &key, &ad).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_stream_wrong_key_or_ad() {
+        let key = random_key();
+        let plain = random_bytes(2 * SEGMENT_SIZE);
+        let ct = encrypt(&plain, &key, b"ad");
+
+        assert!(decrypt(&ct, &random_key(), b"ad").is_err());
+        assert!(decrypt(&ct, &key, b"other ad").is_err());
+    }
+
+    #[test]
+    fn test_stream_truncated() {
+        let key = random_key();
+        let plain = random_bytes(3 * SEGMENT_SIZE);
+        let ct = encrypt(&plain, &key, &[]);
+        let offsets = segments(&ct);
+
+        // dropping the final segment.
+        assert!(decrypt(&ct[..offsets[2]], &key, &[]).is_err());
+
+        // marking a previous segment as the last one.
+        let mut truncated = ct[..offsets[2]].to_vec();
+        truncated[offsets[1]] |= FLAG_LAST;
+        assert!(decrypt(&truncated, &key, &[]).is_err());
+
+        // appending data after the final segment.
+        let mut extended = ct.clone();
+        extended.push(0);
+        assert!(decrypt(&extended, &key, &[]).is_err());
+    }
+
+    #[test]
+    fn test_stream_reordered() {
+        let key = random_key();
+        let plain = random_bytes(3 * SEGMENT_SIZE);
+        let ct = encrypt(&plain, &key, &[]);
+        let offsets = segments(&ct);
+
+        let mut reordered = ct[..offsets[0]].to_vec();
+        reordered.extend_from_slice(&ct[offsets[1]..offsets[2]]);
+        reordered.extend_from_slice(&ct[offsets[0]..offsets[1]]);
+        reordered.extend_from_slice(&ct[offsets[2]..]);
+        assert!(decrypt(&reordered, &key, &[]).is_err());
+    }
+}
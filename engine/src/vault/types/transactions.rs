@@ -7,7 +7,7 @@
 use crate::vault::{
     crypto_box::{Decrypt, Encrypt},
     types::{
-        utils::{BlobId, ChainId, RecordHint, Val},
+        utils::{BlobId, ChainId, RecordHint, RecordId, Val},
         AsView, AsViewMut,
     },
 };
@@ -16,6 +16,7 @@ use serde::{Deserialize, Serialize};
 use std::{
     fmt::{self, Debug, Formatter},
     hash::Hash,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 /// A generic transaction type enum.  Data Transactions refer to `SealedBlobs` while revocation transactions are used to
@@ -78,6 +79,64 @@ pub struct DataTransaction {
 
     /// a record hint
     pub record_hint: RecordHint,
+
+    /// expiry timestamp of the data in milliseconds since the unix epoch, `0` if the data does not expire.
+    pub expires: Val,
+
+    /// bitmask of the usages that are denied by the policy of the data, `0` if the data is unrestricted.
+    pub denied: Val,
+
+    /// remaining uses of the data plus one, `0` if the uses of the data are unlimited.
+    pub uses: Val,
+
+    /// id of the previous version of the record, all zeros if the record has no previous version.
+    pub prev: ChainId,
+
+    /// bitmask of the [`RecordAttributes`] flags, `0` if the transaction was written without attributes.
+    pub flags: Val,
+}
+
+/// flag that marks a data transaction that was written with [`RecordAttributes`].
+const FLAG_ATTRIBUTES: u64 = 1;
+
+/// flag that marks a hidden record.
+const FLAG_HIDDEN: u64 = 1 << 1;
+
+/// The usage policy of the data of a record. The policy is stored in the data transaction, so that it is encrypted
+/// and persisted with the record.
+///
+/// The vault does not interpret the bits of `denied`; it is up to the user of the vault to map usages onto them and
+/// to check them with [`RecordPolicy::permits`] before accessing the data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecordPolicy {
+    /// bitmask of the denied usages.
+    pub denied: u64,
+    /// number of remaining uses, or [`None`] if the uses are unlimited. The record is revoked after its last use.
+    pub uses: Option<u64>,
+    /// expiry timestamp, or [`None`] if the data does not expire.
+    pub expires: Option<SystemTime>,
+}
+
+impl RecordPolicy {
+    /// check if the policy permits all of the usages in the `usage` bitmask.
+    pub fn permits(&self, usage: u64) -> bool {
+        self.denied & usage == 0
+    }
+
+    /// check if the policy does not allow any further uses.
+    pub fn is_exhausted(&self) -> bool {
+        self.uses == Some(0)
+    }
+}
+
+/// The attributes of a record. Unlike the [`RecordPolicy`], the attributes are set when the record is created and
+/// kept when its data is updated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecordAttributes {
+    /// the previous version of the record, if the record is a version of a chain of records.
+    pub prev: Option<RecordId>,
+    /// hidden records are not listed.
+    pub hidden: bool,
 }
 
 /// a typed transaction
@@ -98,18 +157,81 @@ pub struct RevocationTransaction {
 }
 
 impl DataTransaction {
-    /// create a new data transaction from a [`ChainId`], a len, a [`BlobId`] and a [`RecordHint`].
-    pub fn new(id: ChainId, len: u64, blob: BlobId, record_hint: RecordHint) -> Transaction {
+    /// create a new data transaction from a [`ChainId`], a len, a [`BlobId`], a [`RecordHint`], the
+    /// [`RecordPolicy`] of the data and the [`RecordAttributes`] of the record, if there are any.
+    pub fn new(
+        id: ChainId,
+        len: u64,
+        blob: BlobId,
+        record_hint: RecordHint,
+        policy: &RecordPolicy,
+        attributes: Option<RecordAttributes>,
+    ) -> Transaction {
         let mut transaction = Transaction::default();
         let view: &mut Self = transaction.view_mut();
 
+        if let Some(attributes) = attributes {
+            let hidden = if attributes.hidden { FLAG_HIDDEN } else { 0 };
+            if let Some(prev) = attributes.prev {
+                view.prev = prev.into();
+            }
+            view.flags = (FLAG_ATTRIBUTES | hidden).into();
+        }
+
         view.type_id = (TransactionType::Data as u64).into();
         view.len = len.into();
         view.id = id;
         view.blob = blob;
         view.record_hint = record_hint;
+        view.denied = policy.denied.into();
+        view.uses = policy.uses.map_or(0, |uses| uses.saturating_add(1)).into();
+        view.expires = policy
+            .expires
+            .map(|time| {
+                // timestamps before the unix epoch are stored as the earliest possible expiry.
+                let millis = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+                millis.max(1)
+            })
+            .unwrap_or(0)
+            .into();
         transaction
     }
+
+    /// get the expiry timestamp of the data.
+    pub fn expires(&self) -> Option<SystemTime> {
+        match self.expires.u64() {
+            0 => None,
+            millis => Some(UNIX_EPOCH + Duration::from_millis(millis)),
+        }
+    }
+
+    /// check if the data has expired.
+    pub fn is_expired(&self) -> bool {
+        matches!(self.expires(), Some(expires) if expires <= SystemTime::now())
+    }
+
+    /// get the usage policy of the data.
+    pub fn policy(&self) -> RecordPolicy {
+        RecordPolicy {
+            denied: self.denied.u64(),
+            uses: self.uses.u64().checked_sub(1),
+            expires: self.expires(),
+        }
+    }
+
+    /// get the attributes of the record, or [`None`] if the transaction was written without attributes.
+    pub fn attributes(&self) -> Option<RecordAttributes> {
+        let flags = self.flags.u64();
+        if flags & FLAG_ATTRIBUTES == 0 {
+            return None;
+        }
+
+        let prev = self.prev;
+        Some(RecordAttributes {
+            prev: prev.as_ref().iter().any(|b| *b != 0).then_some(RecordId(prev)),
+            hidden: flags & FLAG_HIDDEN != 0,
+        })
+    }
 }
 
 impl TypedTransaction for DataTransaction {
@@ -151,7 +273,10 @@ impl Transaction {
     }
 }
 
-const TRANSACTION_MAX_BYTES: usize = 112;
+const TRANSACTION_MAX_BYTES: usize = 144;
+
+/// size of the transactions that were written before records had attributes.
+const LEGACY_TRANSACTION_BYTES: usize = 112;
 
 impl Default for Transaction {
     fn default() -> Self {
@@ -163,6 +288,12 @@ impl TryFrom<Vec<u8>> for Transaction {
     fn try_from(vec: Vec<u8>) -> Result<Self, Self::Error> {
         match vec.len() {
             TRANSACTION_MAX_BYTES => Ok(Self(vec)),
+            // the attributes of a legacy transaction are zero.
+            LEGACY_TRANSACTION_BYTES => {
+                let mut vec = vec;
+                vec.resize(TRANSACTION_MAX_BYTES, 0);
+                Ok(Self(vec))
+            }
             _ => Err(()),
         }
     }
@@ -300,6 +300,12 @@ impl Display for RecordId {
     }
 }
 
+impl From<RecordId> for ChainId {
+    fn from(id: RecordId) -> Self {
+        id.0
+    }
+}
+
 impl TryFrom<Vec<u8>> for RecordId {
     type Error = InvalidLength;
 
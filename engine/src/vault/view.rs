@@ -4,14 +4,16 @@
 use crate::vault::{
     crypto_box::{BoxProvider, Decrypt, Encrypt, Key},
     types::{
-        transactions::{DataTransaction, RevocationTransaction, SealedBlob, SealedTransaction},
+        transactions::{
+            DataTransaction, RecordAttributes, RecordPolicy, RevocationTransaction, SealedBlob, SealedTransaction,
+        },
         utils::{BlobId, ChainId, RecordHint, RecordId, VaultId},
     },
 };
 
 use runtime::GuardedVec;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, convert::Infallible, fmt::Debug};
+use std::{collections::HashMap, convert::Infallible, fmt::Debug, time::SystemTime};
 use thiserror::Error as DeriveError;
 
 use super::{crypto_box::DecryptError, types::transactions::Transaction};
@@ -41,6 +43,12 @@ pub enum RecordError<TProvErr: Debug> {
 
     #[error("no record with `{0:?}`")]
     RecordNotFound(ChainId),
+
+    #[error("usage of record `{0:?}` is not permitted by its policy")]
+    PolicyViolation(ChainId),
+
+    #[error("counter `{0}` is out of range")]
+    CounterOverflow(usize),
 }
 
 /// A view over the data inside of a collection of [`Vault`] types.
@@ -48,6 +56,24 @@ pub enum RecordError<TProvErr: Debug> {
 pub struct DbView<P: BoxProvider> {
     /// A hashmap of the [`Vault`] types.
     pub vaults: HashMap<VaultId, Vault<P>>,
+    /// Journal of the active transaction.
+    #[serde(skip)]
+    journal: Option<Journal<P>>,
+}
+
+/// Copy-on-write journal of a transaction on a [`DbView`]. Holds the original state of every [`Vault`] that was
+/// changed during the transaction, or [`None`] if the [`Vault`] did not exist yet.
+#[derive(Clone)]
+struct Journal<P: BoxProvider> {
+    vaults: HashMap<VaultId, Option<VaultJournal<P>>>,
+}
+
+/// Original key of a [`Vault`] and the original sealed [`Record`]s that were changed, or [`None`] if the [`Record`]
+/// did not exist yet.
+#[derive(Clone)]
+struct VaultJournal<P: BoxProvider> {
+    key: Key<P>,
+    entries: HashMap<ChainId, Option<Record>>,
 }
 
 /// A enclave of data that is encrypted under one [`Key`].
@@ -75,11 +101,93 @@ impl<P: BoxProvider> DbView<P> {
     pub fn new() -> DbView<P> {
         let vaults = HashMap::new();
 
-        Self { vaults }
+        Self { vaults, journal: None }
+    }
+
+    /// Starts a transaction. Until the transaction is committed or rolled back, the original state of each [`Record`]
+    /// is copied before it is changed for the first time. Does nothing if a transaction is already active.
+    pub fn begin_transaction(&mut self) {
+        if self.journal.is_none() {
+            self.journal = Some(Journal { vaults: HashMap::new() });
+        }
+    }
+
+    /// Commits the active transaction and drops the copies of the original [`Record`]s.
+    pub fn commit_transaction(&mut self) {
+        self.journal = None;
+    }
+
+    /// Rolls back the active transaction by restoring the original sealed [`Record`]s and keys of all changed
+    /// [`Vault`]s, and removing the [`Vault`]s that were created in it. Returns the restored key of each changed
+    /// [`Vault`], or [`None`] if the [`Vault`] was removed.
+    pub fn rollback_transaction(&mut self) -> Vec<(VaultId, Option<Key<P>>)> {
+        let journal = match self.journal.take() {
+            Some(journal) => journal,
+            None => return Vec::new(),
+        };
+
+        journal
+            .vaults
+            .into_iter()
+            .map(|(vid, original)| match original {
+                Some(VaultJournal { key, entries }) => {
+                    let vault = self.vaults.entry(vid).or_insert_with(|| Vault::init_vault(&key));
+                    vault.key = key.clone();
+                    entries.into_iter().for_each(|(id, record)| match record {
+                        Some(record) => {
+                            vault.entries.insert(id, record);
+                        }
+                        None => {
+                            vault.entries.remove(&id);
+                        }
+                    });
+                    (vid, Some(key))
+                }
+                None => {
+                    self.vaults.remove(&vid);
+                    (vid, None)
+                }
+            })
+            .collect()
+    }
+
+    /// Copies the original state of the [`Record`]s into the journal of the active transaction, unless they were
+    /// already copied before. Also keeps track of whether the [`Vault`] existed before the transaction.
+    fn journal_records<I: IntoIterator<Item = ChainId>>(&mut self, vid: VaultId, ids: I) {
+        let journal = match self.journal.as_mut() {
+            Some(journal) => journal,
+            None => return,
+        };
+
+        let vault = self.vaults.get(&vid);
+        let original = journal.vaults.entry(vid).or_insert_with(|| {
+            vault.map(|vault| VaultJournal {
+                key: vault.key.clone(),
+                entries: HashMap::new(),
+            })
+        });
+        if let (Some(original), Some(vault)) = (original, vault) {
+            ids.into_iter().for_each(|id| {
+                original
+                    .entries
+                    .entry(id)
+                    .or_insert_with(|| vault.entries.get(&id).cloned());
+            });
+        }
+    }
+
+    /// Copies the original state of all [`Record`]s of the [`Vault`] into the journal of the active transaction.
+    fn journal_vault(&mut self, vid: VaultId) {
+        let ids: Vec<ChainId> = match self.vaults.get(&vid) {
+            Some(vault) => vault.entries.keys().copied().collect(),
+            None => Vec::new(),
+        };
+        self.journal_records(vid, ids);
     }
 
     /// Initialize a new [`Vault`] if it doesn't exist.
     pub fn init_vault(&mut self, key: &Key<P>, vid: VaultId) {
+        self.journal_records(vid, None);
         self.vaults.entry(vid).or_insert_with(|| Vault::init_vault(key));
     }
 
@@ -92,15 +200,89 @@ impl<P: BoxProvider> DbView<P> {
         data: &[u8],
         record_hint: RecordHint,
     ) -> Result<(), RecordError<P::Error>> {
+        self.write_with_expiry(key, vid, rid, data, record_hint, None)
+    }
+
+    /// Write a new record to a [`Vault`] that expires at the given time. Will instead update a [`Record`] if it
+    /// already exists. Expired records are treated as revoked and removed on garbage collection.
+    pub fn write_with_expiry(
+        &mut self,
+        key: &Key<P>,
+        vid: VaultId,
+        rid: RecordId,
+        data: &[u8],
+        record_hint: RecordHint,
+        expires: Option<SystemTime>,
+    ) -> Result<(), RecordError<P::Error>> {
+        let policy = RecordPolicy {
+            expires,
+            ..Default::default()
+        };
+        self.write_with_policy(key, vid, rid, data, record_hint, policy)
+    }
+
+    /// Write a new record with a [`RecordPolicy`] to a [`Vault`]. Will instead update a [`Record`] and replace its
+    /// policy if it already exists. The policy is stored with the record and persisted with it.
+    pub fn write_with_policy(
+        &mut self,
+        key: &Key<P>,
+        vid: VaultId,
+        rid: RecordId,
+        data: &[u8],
+        record_hint: RecordHint,
+        policy: RecordPolicy,
+    ) -> Result<(), RecordError<P::Error>> {
+        self.write_with_attributes(key, vid, rid, data, record_hint, policy, RecordAttributes::default())
+    }
+
+    /// Write a new record with a [`RecordPolicy`] and [`RecordAttributes`] to a [`Vault`]. Will instead update a
+    /// [`Record`] and replace its policy if it already exists, the attributes of an existing [`Record`] are kept.
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_with_attributes(
+        &mut self,
+        key: &Key<P>,
+        vid: VaultId,
+        rid: RecordId,
+        data: &[u8],
+        record_hint: RecordHint,
+        policy: RecordPolicy,
+        attributes: RecordAttributes,
+    ) -> Result<(), RecordError<P::Error>> {
+        self.journal_records(vid, Some(rid.0));
         if !self.vaults.contains_key(&vid) {
             self.init_vault(key, vid);
         }
 
         let vault = self.vaults.get_mut(&vid).expect("Vault was initiated");
-        vault.add_or_update_record(key, rid.0, data, record_hint)
+        vault.add_or_update_record(key, rid.0, data, record_hint, &policy, attributes)
     }
 
-    /// Lists all of the [`RecordHint`] values and [`RecordId`] values for the given [`Vault`].
+    /// Get the [`RecordPolicy`] of the specified [`Record`].
+    pub fn get_policy(&self, key: &Key<P>, vid: VaultId, rid: RecordId) -> Result<RecordPolicy, RecordError<P::Error>> {
+        let vault = self.vaults.get(&vid).ok_or(RecordError::RecordNotFound(rid.0))?;
+        vault.get_policy(key, rid.0)
+    }
+
+    /// Get the [`RecordAttributes`] of the specified [`Record`], or [`None`] if the [`Record`] was written before
+    /// records had attributes.
+    pub fn get_attributes(
+        &self,
+        key: &Key<P>,
+        vid: VaultId,
+        rid: RecordId,
+    ) -> Result<Option<RecordAttributes>, RecordError<P::Error>> {
+        let vault = self.vaults.get(&vid).ok_or(RecordError::RecordNotFound(rid.0))?;
+        vault.get_attributes(key, rid.0)
+    }
+
+    /// Get the [`RecordHint`] of the specified [`Record`].
+    pub fn get_hint(&self, key: &Key<P>, vid: VaultId, rid: RecordId) -> Result<RecordHint, RecordError<P::Error>> {
+        let vault = self.vaults.get(&vid).ok_or(RecordError::RecordNotFound(rid.0))?;
+        vault.get_hint(key, rid.0)
+    }
+
+    /// Lists all of the [`RecordHint`] values and [`RecordId`] values for the given [`Vault`], except for hidden
+    /// [`Record`]s.
     pub fn list_hints_and_ids(&self, key: &Key<P>, vid: VaultId) -> Vec<(RecordId, RecordHint)> {
         if let Some(vault) = self.vaults.get(&vid) {
             vault.list_hints_and_ids(key)
@@ -118,7 +300,8 @@ impl<P: BoxProvider> DbView<P> {
         }
     }
 
-    /// Get access the decrypted [`GuardedVec`] of the specified [`Record`].
+    /// Get access the decrypted [`GuardedVec`] of the specified [`Record`]. Consumes one use of the [`Record`] if its
+    /// uses are limited by its [`RecordPolicy`].
     pub fn get_guard<E, F>(
         &mut self,
         key: &Key<P>,
@@ -130,13 +313,14 @@ impl<P: BoxProvider> DbView<P> {
         F: FnOnce(GuardedVec<u8>) -> Result<(), E>,
         E: Debug,
     {
+        self.journal_records(vid, Some(rid.0));
         let vault = self.vaults.get_mut(&vid).ok_or(VaultError::VaultNotFound(vid))?;
-        let guard = vault.get_guard(key, rid.0).map_err(VaultError::Record)?;
+        let guard = vault.use_guard(key, rid.0).map_err(VaultError::Record)?;
         f(guard).map_err(VaultError::Procedure)
     }
 
     /// Access the decrypted [`GuardedVec`] of the specified [`Record`] and place the return value into the second
-    /// specified [`Record`]
+    /// specified [`Record`]. Consumes one use of the first [`Record`] if its uses are limited by its [`RecordPolicy`].
     #[allow(clippy::too_many_arguments)]
     pub fn exec_proc<E, F>(
         &mut self,
@@ -153,25 +337,76 @@ impl<P: BoxProvider> DbView<P> {
         F: FnOnce(GuardedVec<u8>) -> Result<Vec<u8>, E>,
         E: Debug,
     {
+        self.exec_proc_with_attributes(
+            key0,
+            vid0,
+            rid0,
+            key1,
+            vid1,
+            rid1,
+            hint,
+            RecordPolicy::default(),
+            RecordAttributes::default(),
+            f,
+        )
+    }
+
+    /// Like [`DbView::exec_proc`], but stores the given [`RecordPolicy`] with the data of the second [`Record`] and
+    /// creates it with the given [`RecordAttributes`] if it does not exist yet.
+    #[allow(clippy::too_many_arguments)]
+    pub fn exec_proc_with_attributes<E, F>(
+        &mut self,
+        key0: &Key<P>,
+        vid0: VaultId,
+        rid0: RecordId,
+        key1: &Key<P>,
+        vid1: VaultId,
+        rid1: RecordId,
+        hint: RecordHint,
+        policy: RecordPolicy,
+        attributes: RecordAttributes,
+        f: F,
+    ) -> Result<(), VaultError<P::Error, E>>
+    where
+        F: FnOnce(GuardedVec<u8>) -> Result<Vec<u8>, E>,
+        E: Debug,
+    {
+        self.journal_records(vid0, Some(rid0.0));
         let vault = self.vaults.get_mut(&vid0).ok_or(VaultError::VaultNotFound(vid0))?;
 
-        let guard = vault.get_guard(key0, rid0.0).map_err(VaultError::Record)?;
+        let guard = vault.use_guard(key0, rid0.0).map_err(VaultError::Record)?;
 
         let data = f(guard).map_err(VaultError::Procedure)?;
 
-        self.write(key1, vid1, rid1, &data, hint).map_err(VaultError::Record)
+        self.write_with_attributes(key1, vid1, rid1, &data, hint, policy, attributes)
+            .map_err(VaultError::Record)
     }
 
     /// Add a revocation transaction to the [`Record`]
     pub fn revoke_record(&mut self, key: &Key<P>, vid: VaultId, rid: RecordId) -> Result<(), RecordError<P::Error>> {
+        self.journal_records(vid, Some(rid.0));
         if let Some(vault) = self.vaults.get_mut(&vid) {
             vault.revoke(key, rid.0)?;
         }
         Ok(())
     }
 
-    /// Garbage collect a [`Vault`]. Deletes any records that contain revocation transactions.
+    /// Re-encrypt all [`Record`]s of the [`Vault`] with the new [`Key`], including their data and revocation
+    /// transactions. The [`Vault`] is left unchanged if any of the [`Record`]s can not be decrypted with the old key.
+    pub fn rotate_vault_key(
+        &mut self,
+        old_key: &Key<P>,
+        new_key: &Key<P>,
+        vid: VaultId,
+    ) -> Result<(), VaultError<P::Error>> {
+        self.journal_vault(vid);
+        let vault = self.vaults.get_mut(&vid).ok_or(VaultError::VaultNotFound(vid))?;
+        vault.rotate_key(old_key, new_key).map_err(VaultError::Record)
+    }
+
+    /// Garbage collect a [`Vault`]. Deletes any records that contain revocation transactions or have expired.
     pub fn garbage_collect_vault(&mut self, key: &Key<P>, vid: VaultId) {
+        self.journal_vault(vid);
         if let Some(vault) = self.vaults.get_mut(&vid) {
             if &vault.key == key {
                 vault.garbage_collect();
@@ -181,6 +416,8 @@ impl<P: BoxProvider> DbView<P> {
 
     /// Clears the entire [`Vault`] from memory.
     pub fn clear(&mut self) {
+        let vids: Vec<VaultId> = self.vaults.keys().copied().collect();
+        vids.into_iter().for_each(|vid| self.journal_vault(vid));
         self.vaults.clear();
     }
 }
@@ -196,25 +433,30 @@ impl<P: BoxProvider> Vault<P> {
         }
     }
 
-    /// Adds a new [`Record`] to the [`Vault`] if the [`Record`] doesn't already exist. Otherwise, updates the data in
-    /// the existing [`Record`] as long as it hasn't been revoked.
+    /// Adds a new [`Record`] to the [`Vault`] if the [`Record`] doesn't already exist or has expired. Otherwise,
+    /// updates the data and the policy of the existing [`Record`]. Fails if the [`Record`] has been revoked.
     pub fn add_or_update_record(
         &mut self,
         key: &Key<P>,
         id: ChainId,
         data: &[u8],
         record_hint: RecordHint,
+        policy: &RecordPolicy,
+        attributes: RecordAttributes,
     ) -> Result<(), RecordError<P::Error>> {
         if key != &self.key {
             return Err(RecordError::InvalidKey);
         }
 
         let blob_id = BlobId::random::<P>().map_err(RecordError::Provider)?;
-        if let Some(entry) = self.entries.get_mut(&id) {
-            entry.update(key, id, data)?
-        } else {
-            let entry = Record::new(key, id, blob_id, data, record_hint).map_err(RecordError::Provider)?;
-            self.entries.insert(id, entry);
+        match self.entries.get_mut(&id) {
+            Some(entry) if entry.revoke.is_some() || !entry.is_expired(key) => entry.update(key, id, data, policy)?,
+            // an expired record is replaced, so that its id can be reused.
+            _ => {
+                let entry = Record::new(key, id, blob_id, data, record_hint, policy, attributes)
+                    .map_err(RecordError::Provider)?;
+                self.entries.insert(id, entry);
+            }
         }
 
         Ok(())
@@ -229,6 +471,7 @@ impl<P: BoxProvider> Vault<P> {
                 .entries
                 .values()
                 .into_iter()
+                .filter(|entry| !matches!(entry.get_attributes(key), Ok(Some(attributes)) if attributes.hidden))
                 .filter_map(|entry| entry.get_hint_and_id(key).ok())
                 .collect();
         }
@@ -239,7 +482,10 @@ impl<P: BoxProvider> Vault<P> {
     /// Check if the [`Vault`] contains a [`Record`]
     fn contains_record(&self, key: &Key<P>, rid: RecordId) -> bool {
         if key == &self.key {
-            self.entries.values().into_iter().any(|entry| entry.check_id(rid))
+            self.entries
+                .values()
+                .into_iter()
+                .any(|entry| entry.check_id(rid) && !entry.is_expired(key))
         } else {
             false
         }
@@ -265,13 +511,68 @@ impl<P: BoxProvider> Vault<P> {
         entry.get_blob(key, id)
     }
 
-    /// Sorts through all of the vault entries and garbage collects any revoked entries.
+    /// Gets the decrypted [`GuardedVec`] from the [`Record`] and consumes one of its uses, if they are limited by its
+    /// [`RecordPolicy`]. The [`Record`] is revoked after its last use.
+    pub fn use_guard(&mut self, key: &Key<P>, id: ChainId) -> Result<GuardedVec<u8>, RecordError<P::Error>> {
+        let guard = self.get_guard(key, id)?;
+        let entry = self.entries.get_mut(&id).ok_or(RecordError::RecordNotFound(id))?;
+        entry.consume_use(key)?;
+        Ok(guard)
+    }
+
+    /// Gets the [`RecordPolicy`] of the [`Record`].
+    pub fn get_policy(&self, key: &Key<P>, id: ChainId) -> Result<RecordPolicy, RecordError<P::Error>> {
+        if key != &self.key {
+            return Err(RecordError::InvalidKey);
+        }
+        let entry = self.entries.get(&id).ok_or(RecordError::RecordNotFound(id))?;
+        entry.get_policy(key)
+    }
+
+    /// Gets the [`RecordAttributes`] of the [`Record`].
+    pub fn get_attributes(&self, key: &Key<P>, id: ChainId) -> Result<Option<RecordAttributes>, RecordError<P::Error>> {
+        if key != &self.key {
+            return Err(RecordError::InvalidKey);
+        }
+        let entry = self.entries.get(&id).ok_or(RecordError::RecordNotFound(id))?;
+        entry.get_attributes(key)
+    }
+
+    /// Gets the [`RecordHint`] of the [`Record`].
+    pub fn get_hint(&self, key: &Key<P>, id: ChainId) -> Result<RecordHint, RecordError<P::Error>> {
+        if key != &self.key {
+            return Err(RecordError::InvalidKey);
+        }
+        let entry = self.entries.get(&id).ok_or(RecordError::RecordNotFound(id))?;
+        entry.get_hint_and_id(key).map(|(_, hint)| hint)
+    }
+
+    /// Re-encrypts all entries with the new [`Key`] and replaces the key of the [`Vault`].
+    pub fn rotate_key(&mut self, old_key: &Key<P>, new_key: &Key<P>) -> Result<(), RecordError<P::Error>> {
+        if old_key != &self.key {
+            return Err(RecordError::InvalidKey);
+        }
+
+        // re-seal all entries first, so that the vault is not changed on failure.
+        let entries = self
+            .entries
+            .iter()
+            .map(|(id, entry)| Ok((*id, entry.reseal(old_key, new_key)?)))
+            .collect::<Result<HashMap<ChainId, Record>, RecordError<P::Error>>>()?;
+
+        self.entries = entries;
+        self.key = new_key.clone();
+
+        Ok(())
+    }
+
+    /// Sorts through all of the vault entries and garbage collects any revoked or expired entries.
     pub fn garbage_collect(&mut self) {
-        // get the keys of the entries with the revocation transactions.
+        // get the keys of the entries with the revocation transactions or an expired data transaction.
         let garbage: Vec<ChainId> = self
             .entries
             .iter()
-            .filter(|(_, entry)| entry.revoke.is_some())
+            .filter(|(_, entry)| entry.revoke.is_some() || entry.is_expired(&self.key))
             .map(|(c, _)| *c)
             .collect();
 
@@ -290,9 +591,11 @@ impl Record {
         blob: BlobId,
         data: &[u8],
         hint: RecordHint,
+        policy: &RecordPolicy,
+        attributes: RecordAttributes,
     ) -> Result<Record, P::Error> {
         let len = data.len() as u64;
-        let dtx = DataTransaction::new(id, len, blob, hint);
+        let dtx = DataTransaction::new(id, len, blob, hint, policy, Some(attributes));
 
         let blob: SealedBlob = data.encrypt(key, blob)?;
         let data = dtx.encrypt(key, id)?;
@@ -307,16 +610,70 @@ impl Record {
 
     fn get_transaction<P: BoxProvider>(&self, key: &Key<P>) -> Result<Transaction, RecordError<P::Error>> {
         // check if a revocation transaction exists.
-        if self.revoke.is_none() {
-            // decrypt data transaction.
-            self.data.decrypt(key, self.id).map_err(|err| match err {
-                DecryptError::Invalid => {
-                    RecordError::CorruptedContent("Could not convert bytes into transaction structure".into())
-                }
-                DecryptError::Provider(e) => RecordError::Provider(e),
-            })
-        } else {
-            Err(RecordError::RecordNotFound(self.id))
+        if self.revoke.is_some() {
+            return Err(RecordError::RecordNotFound(self.id));
+        }
+
+        // decrypt data transaction.
+        let tx: Transaction = self.data.decrypt(key, self.id).map_err(|err| match err {
+            DecryptError::Invalid => {
+                RecordError::CorruptedContent("Could not convert bytes into transaction structure".into())
+            }
+            DecryptError::Provider(e) => RecordError::Provider(e),
+        })?;
+
+        // expired data and data without remaining uses is treated like revoked data.
+        match tx.typed::<DataTransaction>() {
+            Some(dtx) if dtx.is_expired() || dtx.policy().is_exhausted() => Err(RecordError::RecordNotFound(self.id)),
+            _ => Ok(tx),
+        }
+    }
+
+    /// Check if the data of this [`Record`] has expired or has no remaining uses.
+    fn is_expired<P: BoxProvider>(&self, key: &Key<P>) -> bool {
+        match self.data.decrypt(key, self.id) {
+            Ok(tx) => {
+                matches!(tx.typed::<DataTransaction>(), Some(dtx) if dtx.is_expired() || dtx.policy().is_exhausted())
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Gets the [`RecordPolicy`] of the [`Record`].
+    fn get_policy<P: BoxProvider>(&self, key: &Key<P>) -> Result<RecordPolicy, RecordError<P::Error>> {
+        let tx = self.get_transaction(key)?;
+        let tx = tx.typed::<DataTransaction>().ok_or_else(|| {
+            RecordError::CorruptedContent("Could not type decrypted transaction as data-transaction".into())
+        })?;
+        Ok(tx.policy())
+    }
+
+    /// Gets the [`RecordAttributes`] of the [`Record`].
+    fn get_attributes<P: BoxProvider>(&self, key: &Key<P>) -> Result<Option<RecordAttributes>, RecordError<P::Error>> {
+        let tx = self.get_transaction(key)?;
+        let tx = tx.typed::<DataTransaction>().ok_or_else(|| {
+            RecordError::CorruptedContent("Could not type decrypted transaction as data-transaction".into())
+        })?;
+        Ok(tx.attributes())
+    }
+
+    /// Consume one use of the [`Record`], if its uses are limited. Revokes the [`Record`] after its last use.
+    fn consume_use<P: BoxProvider>(&mut self, key: &Key<P>) -> Result<(), RecordError<P::Error>> {
+        let tx = self.get_transaction(key)?;
+        let tx = tx.typed::<DataTransaction>().ok_or_else(|| {
+            RecordError::CorruptedContent("Could not type decrypted transaction as data-transaction".into())
+        })?;
+
+        let mut policy = tx.policy();
+        match policy.uses {
+            None => Ok(()),
+            Some(uses) if uses <= 1 => self.revoke(key, self.id),
+            Some(uses) => {
+                policy.uses = Some(uses - 1);
+                let dtx = DataTransaction::new(tx.id, tx.len.u64(), tx.blob, tx.record_hint, &policy, tx.attributes());
+                self.data = dtx.encrypt(key, self.id).map_err(RecordError::Provider)?;
+                Ok(())
+            }
         }
     }
 
@@ -370,6 +727,7 @@ impl Record {
         key: &Key<P>,
         id: ChainId,
         new_data: &[u8],
+        policy: &RecordPolicy,
     ) -> Result<(), RecordError<P::Error>> {
         // check if ids match
         if self.id != id {
@@ -384,7 +742,14 @@ impl Record {
         // create a new sealed blob with the new_data.
         let blob: SealedBlob = new_data.encrypt(key, tx.blob).map_err(RecordError::Provider)?;
         // create a new sealed transaction with the new_data length.
-        let dtx = DataTransaction::new(tx.id, new_data.len() as u64, tx.blob, tx.record_hint);
+        let dtx = DataTransaction::new(
+            tx.id,
+            new_data.len() as u64,
+            tx.blob,
+            tx.record_hint,
+            policy,
+            tx.attributes(),
+        );
         let data = dtx.encrypt(key, tx.id).map_err(RecordError::Provider)?;
 
         self.blob = blob;
@@ -393,6 +758,44 @@ impl Record {
         Ok(())
     }
 
+    /// Decrypt the blob and the transactions of this [`Record`] and re-encrypt them with the new key.
+    fn reseal<P: BoxProvider>(&self, old_key: &Key<P>, new_key: &Key<P>) -> Result<Record, RecordError<P::Error>> {
+        let open = |tx: &SealedTransaction| -> Result<Transaction, RecordError<P::Error>> {
+            tx.decrypt(old_key, self.id).map_err(|err| match err {
+                DecryptError::Invalid => {
+                    RecordError::CorruptedContent("Could not convert bytes into transaction structure".into())
+                }
+                DecryptError::Provider(e) => RecordError::Provider(e),
+            })
+        };
+
+        let data = open(&self.data)?;
+        let blob_id = data
+            .typed::<DataTransaction>()
+            .ok_or_else(|| {
+                RecordError::CorruptedContent("Could not type decrypted transaction as data-transaction".into())
+            })?
+            .blob;
+
+        let blob: Vec<u8> = self.blob.decrypt(old_key, blob_id).map_err(|err| match err {
+            DecryptError::Invalid => RecordError::CorruptedContent("Could not decrypt blob".into()),
+            DecryptError::Provider(e) => RecordError::Provider(e),
+        })?;
+        let blob = blob.encrypt(new_key, blob_id).map_err(RecordError::Provider)?;
+
+        let revoke = match &self.revoke {
+            Some(revoke) => Some(open(revoke)?.encrypt(new_key, self.id).map_err(RecordError::Provider)?),
+            None => None,
+        };
+
+        Ok(Record {
+            id: self.id,
+            data: data.encrypt(new_key, self.id).map_err(RecordError::Provider)?,
+            revoke,
+            blob,
+        })
+    }
+
     // add a revocation transaction to the [`Record`].
     fn revoke<P: BoxProvider>(&mut self, key: &Key<P>, id: ChainId) -> Result<(), RecordError<P::Error>> {
         // check if id and id match.
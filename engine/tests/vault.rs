@@ -2,11 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 mod utils;
-use std::convert::Infallible;
+use std::{
+    convert::Infallible,
+    time::{Duration, SystemTime},
+};
 
 use utils::provider::Provider;
 
-use engine::vault::{DbView, Key, RecordHint, RecordId, VaultId};
+use engine::vault::{DbView, Key, RecordAttributes, RecordHint, RecordId, RecordPolicy, VaultId};
 
 #[test]
 fn test_vaults() {
@@ -142,3 +145,347 @@ fn test_vaults() {
     })
     .unwrap();
 }
+
+#[test]
+fn test_rotate_vault_key() {
+    let mut view: DbView<Provider> = DbView::new();
+
+    let key0 = Key::random();
+    let key1 = Key::random();
+    let vid = VaultId::random::<Provider>().unwrap();
+    let rid0 = RecordId::random::<Provider>().unwrap();
+    let rid1 = RecordId::random::<Provider>().unwrap();
+
+    view.init_vault(&key0, vid);
+    view.write(&key0, vid, rid0, b"test0", RecordHint::new(b"hint").unwrap())
+        .unwrap();
+    view.write(&key0, vid, rid1, b"test1", RecordHint::new(b"hint").unwrap())
+        .unwrap();
+    view.revoke_record(&key0, vid, rid1).unwrap();
+
+    // rotating with the wrong key fails and leaves the vault unchanged.
+    assert!(view.rotate_vault_key(&key1, &key1, vid).is_err());
+    assert!(view.contains_record(&key0, vid, rid0));
+
+    view.rotate_vault_key(&key0, &key1, vid).unwrap();
+
+    // the old key can not be used anymore.
+    assert!(view.get_guard::<Infallible, _>(&key0, vid, rid0, |_| Ok(())).is_err());
+    assert!(view.list_hints_and_ids(&key0, vid).is_empty());
+
+    view.get_guard::<Infallible, _>(&key1, vid, rid0, |g| {
+        assert_eq!(b"test0", &(*g.borrow()));
+
+        Ok(())
+    })
+    .unwrap();
+
+    // the revocation is kept.
+    assert_eq!(view.list_hints_and_ids(&key1, vid).len(), 1);
+
+    view.garbage_collect_vault(&key1, vid);
+
+    assert!(view.contains_record(&key1, vid, rid0));
+    assert!(!view.contains_record(&key1, vid, rid1));
+}
+
+#[test]
+fn test_write_revoked_record() {
+    let mut view: DbView<Provider> = DbView::new();
+
+    let key = Key::random();
+    let vid = VaultId::random::<Provider>().unwrap();
+    let rid = RecordId::random::<Provider>().unwrap();
+
+    view.write(&key, vid, rid, b"test0", RecordHint::new(b"hint").unwrap())
+        .unwrap();
+    view.revoke_record(&key, vid, rid).unwrap();
+    assert!(!view.contains_record(&key, vid, rid));
+
+    // writing to a revoked record fails until it was garbage collected.
+    assert!(view
+        .write(&key, vid, rid, b"test1", RecordHint::new(b"new hint").unwrap())
+        .is_err());
+    assert!(!view.contains_record(&key, vid, rid));
+
+    view.garbage_collect_vault(&key, vid);
+    view.write(&key, vid, rid, b"test1", RecordHint::new(b"new hint").unwrap())
+        .unwrap();
+    assert!(view.contains_record(&key, vid, rid));
+    assert_eq!(
+        view.list_hints_and_ids(&key, vid),
+        vec![(rid, RecordHint::new(b"new hint").unwrap())]
+    );
+
+    view.get_guard::<Infallible, _>(&key, vid, rid, |g| {
+        assert_eq!(b"test1", &(*g.borrow()));
+
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_record_attributes() {
+    let mut view: DbView<Provider> = DbView::new();
+
+    let key = Key::random();
+    let vid = VaultId::random::<Provider>().unwrap();
+    let rid0 = RecordId::random::<Provider>().unwrap();
+    let rid1 = RecordId::random::<Provider>().unwrap();
+    let rid2 = RecordId::random::<Provider>().unwrap();
+
+    view.write(&key, vid, rid0, b"test0", RecordHint::new(b"hint0").unwrap())
+        .unwrap();
+    let linked = RecordAttributes {
+        prev: Some(rid0),
+        hidden: false,
+    };
+    view.write_with_attributes(
+        &key,
+        vid,
+        rid1,
+        b"test1",
+        RecordHint::new(b"hint1").unwrap(),
+        RecordPolicy::default(),
+        linked,
+    )
+    .unwrap();
+    let hidden = RecordAttributes {
+        prev: None,
+        hidden: true,
+    };
+    view.write_with_attributes(
+        &key,
+        vid,
+        rid2,
+        b"test2",
+        RecordHint::new(b"hint2").unwrap(),
+        RecordPolicy::default(),
+        hidden,
+    )
+    .unwrap();
+
+    assert_eq!(
+        view.get_attributes(&key, vid, rid0).unwrap(),
+        Some(RecordAttributes::default())
+    );
+    assert_eq!(view.get_attributes(&key, vid, rid1).unwrap(), Some(linked));
+    assert_eq!(
+        view.get_hint(&key, vid, rid1).unwrap(),
+        RecordHint::new(b"hint1").unwrap()
+    );
+
+    // the attributes are kept when the record is updated.
+    view.write(&key, vid, rid1, b"test3", RecordHint::new(b"hint3").unwrap())
+        .unwrap();
+    assert_eq!(view.get_attributes(&key, vid, rid1).unwrap(), Some(linked));
+
+    // hidden records are not listed, but can be accessed.
+    let mut list = view.list_hints_and_ids(&key, vid);
+    list.sort_by_key(|(_, hint)| *hint);
+    assert_eq!(
+        list,
+        vec![
+            (rid0, RecordHint::new(b"hint0").unwrap()),
+            (rid1, RecordHint::new(b"hint1").unwrap())
+        ]
+    );
+    assert!(view.contains_record(&key, vid, rid2));
+    view.get_guard::<Infallible, _>(&key, vid, rid2, |g| {
+        assert_eq!(b"test2", &(*g.borrow()));
+
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_record_expiry() {
+    let mut view: DbView<Provider> = DbView::new();
+
+    let key = Key::random();
+    let vid = VaultId::random::<Provider>().unwrap();
+    let rid0 = RecordId::random::<Provider>().unwrap();
+    let rid1 = RecordId::random::<Provider>().unwrap();
+
+    let expired = SystemTime::now() - Duration::from_secs(1);
+    let valid = SystemTime::now() + Duration::from_secs(3600);
+
+    view.write_with_expiry(
+        &key,
+        vid,
+        rid0,
+        b"test0",
+        RecordHint::new(b"hint").unwrap(),
+        Some(expired),
+    )
+    .unwrap();
+    view.write_with_expiry(
+        &key,
+        vid,
+        rid1,
+        b"test1",
+        RecordHint::new(b"hint").unwrap(),
+        Some(valid),
+    )
+    .unwrap();
+
+    // expired records are treated as revoked.
+    assert!(!view.contains_record(&key, vid, rid0));
+    assert!(view.get_guard::<Infallible, _>(&key, vid, rid0, |_| Ok(())).is_err());
+    assert!(view
+        .exec_proc::<Infallible, _>(&key, vid, rid0, &key, vid, rid1, RecordHint::new(b"").unwrap(), |_| {
+            Ok(Vec::new())
+        })
+        .is_err());
+    assert_eq!(view.list_hints_and_ids(&key, vid).len(), 1);
+
+    view.get_guard::<Infallible, _>(&key, vid, rid1, |g| {
+        assert_eq!(b"test1", &(*g.borrow()));
+
+        Ok(())
+    })
+    .unwrap();
+
+    // garbage collection keeps the record that has not expired yet.
+    view.garbage_collect_vault(&key, vid);
+    assert!(view.contains_record(&key, vid, rid1));
+    assert_eq!(view.list_hints_and_ids(&key, vid).len(), 1);
+
+    // writing to an expired record replaces it.
+    view.write_with_expiry(
+        &key,
+        vid,
+        rid1,
+        b"test1",
+        RecordHint::new(b"hint").unwrap(),
+        Some(expired),
+    )
+    .unwrap();
+    view.write(&key, vid, rid1, b"test2", RecordHint::new(b"hint").unwrap())
+        .unwrap();
+    view.get_guard::<Infallible, _>(&key, vid, rid1, |g| {
+        assert_eq!(b"test2", &(*g.borrow()));
+
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_record_policy() {
+    let mut view: DbView<Provider> = DbView::new();
+
+    let key = Key::random();
+    let vid = VaultId::random::<Provider>().unwrap();
+    let rid = RecordId::random::<Provider>().unwrap();
+
+    let policy = RecordPolicy {
+        denied: 0b101,
+        uses: Some(2),
+        expires: None,
+    };
+    view.write_with_policy(&key, vid, rid, b"test", RecordHint::new(b"hint").unwrap(), policy)
+        .unwrap();
+
+    let stored = view.get_policy(&key, vid, rid).unwrap();
+    assert_eq!(stored, policy);
+    assert!(stored.permits(0b010));
+    assert!(!stored.permits(0b100));
+
+    // the policy survives the rotation of the vault key.
+    let new_key = Key::random();
+    view.rotate_vault_key(&key, &new_key, vid).unwrap();
+    let key = new_key;
+
+    // each access consumes one use.
+    view.get_guard::<Infallible, _>(&key, vid, rid, |g| {
+        assert_eq!(b"test", &(*g.borrow()));
+
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(view.get_policy(&key, vid, rid).unwrap().uses, Some(1));
+
+    // the record is revoked after its last use.
+    view.get_guard::<Infallible, _>(&key, vid, rid, |_| Ok(())).unwrap();
+    assert!(!view.contains_record(&key, vid, rid));
+    assert!(view.get_guard::<Infallible, _>(&key, vid, rid, |_| Ok(())).is_err());
+    assert!(view
+        .write(&key, vid, rid, b"test", RecordHint::new(b"hint").unwrap())
+        .is_err());
+
+    // records without remaining uses are not usable at all.
+    let rid = RecordId::random::<Provider>().unwrap();
+    let policy = RecordPolicy {
+        uses: Some(0),
+        ..Default::default()
+    };
+    view.write_with_policy(&key, vid, rid, b"test", RecordHint::new(b"hint").unwrap(), policy)
+        .unwrap();
+    assert!(!view.contains_record(&key, vid, rid));
+
+    // overwriting a record replaces its policy.
+    view.write(&key, vid, rid, b"test", RecordHint::new(b"hint").unwrap())
+        .unwrap();
+    assert_eq!(view.get_policy(&key, vid, rid).unwrap(), RecordPolicy::default());
+}
+
+#[test]
+fn test_transaction_rollback() {
+    let mut view: DbView<Provider> = DbView::new();
+
+    let key0 = Key::random();
+    let vid0 = VaultId::random::<Provider>().unwrap();
+    let rid0 = RecordId::random::<Provider>().unwrap();
+    let rid1 = RecordId::random::<Provider>().unwrap();
+
+    let key1 = Key::random();
+    let vid1 = VaultId::random::<Provider>().unwrap();
+
+    let limited = RecordPolicy {
+        uses: Some(1),
+        ..Default::default()
+    };
+    view.write(&key0, vid0, rid0, b"test0", RecordHint::new(b"hint").unwrap())
+        .unwrap();
+    view.write_with_policy(&key0, vid0, rid1, b"test1", RecordHint::new(b"hint").unwrap(), limited)
+        .unwrap();
+
+    // a committed transaction is kept.
+    view.begin_transaction();
+    view.write(&key0, vid0, rid0, b"test2", RecordHint::new(b"hint").unwrap())
+        .unwrap();
+    view.commit_transaction();
+    assert!(view.rollback_transaction().is_empty());
+
+    // changes of a rolled back transaction are undone.
+    view.begin_transaction();
+    view.write(&key0, vid0, rid0, b"test3", RecordHint::new(b"hint").unwrap())
+        .unwrap();
+    view.get_guard::<Infallible, _>(&key0, vid0, rid1, |_| Ok(())).unwrap();
+    assert!(!view.contains_record(&key0, vid0, rid1));
+    view.write(&key1, vid1, rid0, b"test4", RecordHint::new(b"hint").unwrap())
+        .unwrap();
+    let new_key = Key::random();
+    view.rotate_vault_key(&key0, &new_key, vid0).unwrap();
+    view.garbage_collect_vault(&new_key, vid0);
+
+    let mut keys = view.rollback_transaction();
+    keys.sort_by_key(|(vid, _)| *vid == vid1);
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0].0, vid0);
+    assert!(keys[0].1.as_ref() == Some(&key0));
+    assert_eq!(keys[1].0, vid1);
+    assert!(keys[1].1.is_none());
+
+    view.get_guard::<Infallible, _>(&key0, vid0, rid0, |g| {
+        assert_eq!(b"test2", &(*g.borrow()));
+
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(view.get_policy(&key0, vid0, rid1).unwrap(), limited);
+    assert!(!view.vaults.contains_key(&vid1));
+}
@@ -18,6 +18,78 @@ macro_rules! line_error {
     };
 }
 
+// Reads a snapshot that is protected by the password. Snapshots written by former versions of the commandline are
+// encrypted with a key that `naive_kdf` derived from the password and a zeroed salt. They are read with that key and
+// rewritten right away with a key that is derived from the password with Argon2id. Prints the error and returns
+// `false` if the snapshot can not be read.
+fn read_snapshot(
+    stronghold: &mut iota_stronghold::Stronghold,
+    client_path: Vec<u8>,
+    password: &[u8],
+    filename: Option<String>,
+    path: Option<PathBuf>,
+) -> bool {
+    let error = match block_on(stronghold.read_snapshot_with_password(
+        client_path.clone(),
+        None,
+        &password.to_vec(),
+        filename.clone(),
+        path.clone(),
+    )) {
+        Ok(Ok(())) => return true,
+        Ok(Err(e)) => e.to_string(),
+        Err(e) => e.to_string(),
+    };
+
+    let mut key = [0u8; 32];
+    let salt = [0u8; 32];
+    naive_kdf(password, &salt, &mut key);
+    match block_on(stronghold.read_snapshot(client_path, None, &key.to_vec(), filename.clone(), path.clone())) {
+        Ok(Ok(())) => {
+            println!("Migrating snapshot of a former version to a password protected snapshot.");
+            write_snapshot(stronghold, password, filename, path)
+        }
+        _ => {
+            println!("[Error] Reading snapshot failed: {}", error);
+            false
+        }
+    }
+}
+
+// Writes the state of the stronghold to a snapshot that is protected by the password. Prints the error and returns
+// `false` if the snapshot can not be written.
+fn write_snapshot(
+    stronghold: &mut iota_stronghold::Stronghold,
+    password: &[u8],
+    filename: Option<String>,
+    path: Option<PathBuf>,
+) -> bool {
+    match block_on(stronghold.write_all_to_snapshot_with_password(&password.to_vec(), filename, path)) {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            println!("[Error] Writing snapshot failed: {}", e);
+            false
+        }
+        Err(e) => {
+            println!("[Error] Writing snapshot failed: {}", e);
+            false
+        }
+    }
+}
+
+// Prints the hints and ids of the records in the vault.
+fn print_hints_and_ids(stronghold: &mut iota_stronghold::Stronghold, vault_path: Vec<u8>) {
+    match block_on(stronghold.list_hints_and_ids(vault_path)) {
+        Ok(list) => {
+            println!("Hints and Ids:");
+            for (id, hint) in list {
+                println!("{}: {:?}", id, hint);
+            }
+        }
+        Err(e) => println!("[Error] Listing records failed: {}", e),
+    }
+}
+
 // Writes data to the unencrypted store. Requires a password, the plaintext and the record path.  Record path must be a
 // number.
 fn write_to_store_command(matches: &ArgMatches, stronghold: &mut iota_stronghold::Stronghold, client_path: Vec<u8>) {
@@ -25,44 +97,27 @@ fn write_to_store_command(matches: &ArgMatches, stronghold: &mut iota_stronghold
         if let Some(pass) = matches.value_of("password") {
             if let Some(plain) = matches.value_of("plain") {
                 if let Some(rid) = matches.value_of("rpath") {
-                    let mut key = [0u8; 32];
-                    let salt = [0u8; 32];
-                    naive_kdf(pass.as_bytes(), &salt, &mut key);
+                    let password = pass.as_bytes().to_vec();
 
                     let home_dir = home_dir().expect(line_error!());
                     let snapshot = home_dir.join("snapshots").join("commandline.stronghold");
 
-                    if snapshot.exists() {
-                        let result = block_on(stronghold.read_snapshot(
-                            client_path,
-                            None,
-                            &key.to_vec(),
-                            Some("commandline".to_string()),
-                            None,
-                        ))
-                        .unwrap();
-                        if let Err(e) = result {
-                            println!("[Error] Reading snapshot failed: {}", e);
-                            return;
-                        }
+                    if snapshot.exists()
+                        && !read_snapshot(stronghold, client_path, &password, Some("commandline".to_string()), None)
+                    {
+                        return;
                     }
 
-                    let old_value =
-                        block_on(stronghold.write_to_store(rid.into(), plain.as_bytes().to_vec(), None)).unwrap();
-                    match old_value {
-                        Some(v) => println!("Wrote to store. Overwrote old data: {:?}", v),
-                        None => println!("Wrote to store."),
+                    match block_on(stronghold.write_to_store(rid.into(), plain.as_bytes().to_vec(), None)) {
+                        Ok(Some(v)) => println!("Wrote to store. Overwrote old data: {:?}", v),
+                        Ok(None) => println!("Wrote to store."),
+                        Err(e) => {
+                            println!("[Error] Writing to store failed: {}", e);
+                            return;
+                        }
                     }
 
-                    let result = block_on(stronghold.write_all_to_snapshot(
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    if let Err(e) = result {
-                        println!("[Error] Writing snapshot failed: {}", e);
-                    }
+                    write_snapshot(stronghold, &password, Some("commandline".to_string()), None);
                 };
             };
         };
@@ -76,26 +131,15 @@ fn encrypt_command(matches: &ArgMatches, stronghold: &mut iota_stronghold::Stron
         if let Some(pass) = matches.value_of("password") {
             if let Some(plain) = matches.value_of("plain") {
                 if let Some(rid) = matches.value_of("rpath") {
-                    let mut key = [0u8; 32];
-                    let salt = [0u8; 32];
-                    naive_kdf(pass.as_bytes(), &salt, &mut key);
+                    let password = pass.as_bytes().to_vec();
 
                     let home_dir = home_dir().expect(line_error!());
                     let snapshot = home_dir.join("snapshots").join("commandline.stronghold");
 
-                    if snapshot.exists() {
-                        let result = block_on(stronghold.read_snapshot(
-                            client_path,
-                            None,
-                            &key.to_vec(),
-                            Some("commandline".to_string()),
-                            None,
-                        ))
-                        .unwrap();
-                        if let Err(e) = result {
-                            println!("[Error] Reading snapshot failed: {}", e);
-                            return;
-                        }
+                    if snapshot.exists()
+                        && !read_snapshot(stronghold, client_path, &password, Some("commandline".to_string()), None)
+                    {
+                        return;
                     }
 
                     let result = block_on(stronghold.write_to_vault(
@@ -103,25 +147,20 @@ fn encrypt_command(matches: &ArgMatches, stronghold: &mut iota_stronghold::Stron
                         plain.as_bytes().to_vec(),
                         RecordHint::new("some hint").expect(line_error!()),
                         vec![],
-                    ))
-                    .unwrap();
+                    ));
                     match result {
-                        Ok(()) => println!("Wrote to vault."),
+                        Ok(Ok(())) => println!("Wrote to vault."),
+                        Ok(Err(e)) => {
+                            println!("[Error] Writing to vault failed: {}", e);
+                            return;
+                        }
                         Err(e) => {
                             println!("[Error] Writing to vault failed: {}", e);
                             return;
                         }
                     }
 
-                    let result = block_on(stronghold.write_all_to_snapshot(
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    if let Err(e) = result {
-                        println!("[Error] Writing snapshot failed: {}", e);
-                    }
+                    write_snapshot(stronghold, &password, Some("commandline".to_string()), None);
                 };
             };
         };
@@ -133,9 +172,7 @@ fn snapshot_command(matches: &ArgMatches, stronghold: &mut iota_stronghold::Stro
     if let Some(matches) = matches.subcommand_matches("snapshot") {
         if let Some(pass) = matches.value_of("password") {
             if let Some(ref path) = matches.value_of("path") {
-                let mut key = [0u8; 32];
-                let salt = [0u8; 32];
-                naive_kdf(pass.as_bytes(), &salt, &mut key);
+                let password = pass.as_bytes().to_vec();
 
                 let path = Path::new(path);
 
@@ -147,28 +184,13 @@ fn snapshot_command(matches: &ArgMatches, stronghold: &mut iota_stronghold::Stro
                 out.push(Path::new("recompute.stronghold"));
 
                 if input.exists() {
-                    let result =
-                        block_on(stronghold.read_snapshot(client_path, None, &key.to_vec(), None, Some(input)))
-                            .unwrap();
-                    match result {
-                        Ok(()) => println!("Read snapshot"),
-                        Err(e) => {
-                            println!("[Error] Reading snapshot failed: {}", e);
-                            return;
-                        }
+                    if !read_snapshot(stronghold, client_path, &password, None, Some(input)) {
+                        return;
                     }
+                    println!("Read snapshot");
 
-                    let result = block_on(stronghold.write_all_to_snapshot(
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    match result {
-                        Ok(()) => println!("Wrote to snapshot."),
-                        Err(e) => {
-                            println!("[Error] Writing snapshot failed: {}", e);
-                        }
+                    if write_snapshot(stronghold, &password, Some("commandline".to_string()), None) {
+                        println!("Wrote to snapshot.");
                     }
                 } else {
                     println!("[Error] The path you entered does not contain a valid snapshot");
@@ -183,34 +205,17 @@ fn list_command(matches: &ArgMatches, stronghold: &mut iota_stronghold::Strongho
     if let Some(matches) = matches.subcommand_matches("list") {
         if let Some(pass) = matches.value_of("password") {
             if let Some(path) = matches.value_of("rpath") {
-                let mut key = [0u8; 32];
-                let salt = [0u8; 32];
-                naive_kdf(pass.as_bytes(), &salt, &mut key);
+                let password = pass.as_bytes().to_vec();
 
                 let home_dir = home_dir().expect(line_error!());
                 let snapshot = home_dir.join("snapshots").join("commandline.stronghold");
 
                 if snapshot.exists() {
-                    let result = block_on(stronghold.read_snapshot(
-                        client_path,
-                        None,
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    if let Err(e) = result {
-                        println!("[Error] Reading snapshot failed: {}", e);
+                    if !read_snapshot(stronghold, client_path, &password, Some("commandline".to_string()), None) {
                         return;
                     }
 
-                    let list =
-                        block_on(stronghold.list_hints_and_ids(Location::generic(path, path).vault_path().to_vec()))
-                            .unwrap();
-                    println!("Hints and Ids:");
-                    for (id, hint) in list {
-                        println!("{}: {:?}", id, hint);
-                    }
+                    print_hints_and_ids(stronghold, Location::generic(path, path).vault_path().to_vec());
                 } else {
                     println!("[Error] Could not find a snapshot at the home path. Try writing first.");
                 }
@@ -224,31 +229,20 @@ fn read_from_store_command(matches: &ArgMatches, stronghold: &mut iota_stronghol
     if let Some(matches) = matches.subcommand_matches("read") {
         if let Some(pass) = matches.value_of("password") {
             if let Some(rpath) = matches.value_of("rpath") {
-                let mut key = [0u8; 32];
-                let salt = [0u8; 32];
-                naive_kdf(pass.as_bytes(), &salt, &mut key);
+                let password = pass.as_bytes().to_vec();
 
                 let home_dir = home_dir().expect(line_error!());
                 let snapshot = home_dir.join("snapshots").join("commandline.stronghold");
 
                 if snapshot.exists() {
-                    let result = block_on(stronghold.read_snapshot(
-                        client_path,
-                        None,
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    if let Err(e) = result {
-                        println!("[Error] Reading snapshot failed: {}", e);
+                    if !read_snapshot(stronghold, client_path, &password, Some("commandline".to_string()), None) {
                         return;
                     }
 
-                    let data = block_on(stronghold.read_from_store(rpath.into())).unwrap();
-                    match data {
-                        Some(data) => println!("Data: {:?}", std::str::from_utf8(&data).unwrap()),
-                        None => println!("No Data in the store for this key."),
+                    match block_on(stronghold.read_from_store(rpath.into())) {
+                        Ok(Some(data)) => println!("Data: {:?}", String::from_utf8_lossy(&data)),
+                        Ok(None) => println!("No Data in the store for this key."),
+                        Err(e) => println!("[Error] Reading from store failed: {}", e),
                     }
                 } else {
                     println!("[Error] Could not find a snapshot at the home path. Try writing first.");
@@ -264,45 +258,29 @@ fn revoke_command(matches: &ArgMatches, stronghold: &mut iota_stronghold::Strong
     if let Some(matches) = matches.subcommand_matches("revoke") {
         if let Some(pass) = matches.value_of("password") {
             if let Some(id) = matches.value_of("rpath") {
-                let mut key = [0u8; 32];
-                let salt = [0u8; 32];
-                naive_kdf(pass.as_bytes(), &salt, &mut key);
+                let password = pass.as_bytes().to_vec();
 
                 let home_dir = home_dir().expect(line_error!());
                 let snapshot = home_dir.join("snapshots").join("commandline.stronghold");
 
                 if snapshot.exists() {
-                    let result = block_on(stronghold.read_snapshot(
-                        client_path,
-                        None,
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    if let Err(e) = result {
-                        println!("[Error] Reading snapshot failed: {}", e);
+                    if !read_snapshot(stronghold, client_path, &password, Some("commandline".to_string()), None) {
                         return;
                     }
 
-                    let result = block_on(stronghold.delete_data(Location::generic(id, id), false)).unwrap();
-                    match result {
-                        Ok(()) => println!("Deleted data."),
+                    match block_on(stronghold.delete_data(Location::generic(id, id), false)) {
+                        Ok(Ok(())) => println!("Deleted data."),
+                        Ok(Err(e)) => {
+                            println!("[Error] Deleting data failed: {}", e);
+                            return;
+                        }
                         Err(e) => {
                             println!("[Error] Deleting data failed: {}", e);
                             return;
                         }
                     }
 
-                    let result = block_on(stronghold.write_all_to_snapshot(
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    if let Err(e) = result {
-                        println!("[Error] Writing snapshot failed: {}", e);
-                    }
+                    write_snapshot(stronghold, &password, Some("commandline".to_string()), None);
                 } else {
                     println!("[Error] Could not find a snapshot at the home path. Try writing first.");
                 }
@@ -320,50 +298,28 @@ fn garbage_collect_vault_command(
     if let Some(matches) = matches.subcommand_matches("garbage_collect") {
         if let Some(pass) = matches.value_of("password") {
             if let Some(id) = matches.value_of("rpath") {
-                let mut key = [0u8; 32];
-                let salt = [0u8; 32];
-                naive_kdf(pass.as_bytes(), &salt, &mut key);
+                let password = pass.as_bytes().to_vec();
 
                 let home_dir = home_dir().expect(line_error!());
                 let snapshot = home_dir.join("snapshots").join("commandline.stronghold");
 
                 if snapshot.exists() {
-                    let result = block_on(stronghold.read_snapshot(
-                        client_path,
-                        None,
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    if let Err(e) = result {
-                        println!("[Error] Reading snapshot failed: {}", e);
+                    if !read_snapshot(stronghold, client_path, &password, Some("commandline".to_string()), None) {
                         return;
                     }
 
-                    let result =
-                        block_on(stronghold.garbage_collect(Location::generic(id, id).vault_path().to_vec())).unwrap();
-                    match result {
-                        true => println!("Garbage collected."),
-                        false => println!("[Error] Vault does not exist."),
+                    match block_on(stronghold.garbage_collect(Location::generic(id, id).vault_path().to_vec())) {
+                        Ok(true) => println!("Garbage collected."),
+                        Ok(false) => println!("[Error] Vault does not exist."),
+                        Err(e) => {
+                            println!("[Error] Garbage collecting failed: {}", e);
+                            return;
+                        }
                     }
 
-                    let list = block_on(stronghold.list_hints_and_ids(Location::generic(id, id).vault_path().to_vec()))
-                        .unwrap();
-                    println!("Hints and Ids:");
-                    for (id, hint) in list {
-                        println!("{}: {:?}", id, hint);
-                    }
+                    print_hints_and_ids(stronghold, Location::generic(id, id).vault_path().to_vec());
 
-                    let result = block_on(stronghold.write_all_to_snapshot(
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    if let Err(e) = result {
-                        println!("[Error] Writing snapshot failed: {}", e);
-                    }
+                    write_snapshot(stronghold, &password, Some("commandline".to_string()), None);
                 } else {
                     println!("[Error] Could not find a snapshot at the home path. Try writing first.");
                 }
@@ -378,40 +334,25 @@ fn purge_command(matches: &ArgMatches, stronghold: &mut iota_stronghold::Strongh
     if let Some(matches) = matches.subcommand_matches("purge") {
         if let Some(pass) = matches.value_of("password") {
             if let Some(id) = matches.value_of("id") {
-                let mut key = [0u8; 32];
-                let salt = [0u8; 32];
-                naive_kdf(pass.as_bytes(), &salt, &mut key);
+                let password = pass.as_bytes().to_vec();
 
                 let home_dir = home_dir().expect(line_error!());
                 let snapshot = home_dir.join("snapshots").join("commandline.stronghold");
 
                 if snapshot.exists() {
-                    let result = block_on(stronghold.read_snapshot(
-                        client_path,
-                        None,
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    if let Err(e) = result {
-                        println!("[Error] Reading snapshot failed: {}", e);
+                    if !read_snapshot(stronghold, client_path, &password, Some("commandline".to_string()), None) {
                         return;
                     }
 
-                    let result = block_on(stronghold.delete_data(Location::generic(id, id), true)).unwrap();
-
-                    println!("Delete Data: {:?}", result);
-
-                    let result = block_on(stronghold.write_all_to_snapshot(
-                        &key.to_vec(),
-                        Some("commandline".to_string()),
-                        None,
-                    ))
-                    .unwrap();
-                    if let Err(e) = result {
-                        println!("[Error] Writing snapshot failed: {}", e);
+                    match block_on(stronghold.delete_data(Location::generic(id, id), true)) {
+                        Ok(result) => println!("Delete Data: {:?}", result),
+                        Err(e) => {
+                            println!("[Error] Deleting data failed: {}", e);
+                            return;
+                        }
                     }
+
+                    write_snapshot(stronghold, &password, Some("commandline".to_string()), None);
                 } else {
                     println!("[Error] Could not find a snapshot at the home path. Try writing first.");
                 }